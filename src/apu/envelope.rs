@@ -0,0 +1,115 @@
+/// Volume envelope of the square and noise channels, set with NRx2.
+#[derive(Clone)]
+pub struct Envelope {
+    initial_volume: u8,
+    increase: bool,
+    period: u8,
+
+    volume: u8,
+    timer: u8,
+}
+
+impl Envelope {
+    pub fn new() -> Envelope {
+        Envelope {
+            initial_volume: 0,
+            increase: false,
+            period: 0,
+            volume: 0,
+            timer: 0,
+        }
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.initial_volume = value >> 4;
+        self.increase = value & 0x08 != 0;
+        self.period = value & 0x07;
+    }
+
+    /// The DAC of the channel is off when the upper 5 bits of NRx2 are 0.
+    pub fn dac_enabled(&self) -> bool {
+        self.initial_volume != 0 || self.increase
+    }
+
+    pub fn trigger(&mut self) {
+        self.volume = self.initial_volume;
+        self.timer = self.period;
+    }
+
+    pub fn clock(&mut self) {
+        if self.period == 0 {
+            return;
+        }
+
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+
+        if self.timer > 0 {
+            return;
+        }
+
+        self.timer = self.period;
+
+        if self.increase && self.volume < 15 {
+            self.volume += 1;
+        } else if !self.increase && self.volume > 0 {
+            self.volume -= 1;
+        }
+    }
+
+    pub fn volume(&self) -> u8 {
+        self.volume
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decrease_volume_every_period() {
+        let mut envelope = Envelope::new();
+        envelope.write(0xF2);
+        envelope.trigger();
+
+        envelope.clock();
+        assert_eq!(envelope.volume(), 15);
+
+        envelope.clock();
+        assert_eq!(envelope.volume(), 14);
+    }
+
+    #[test]
+    fn volume_stays_within_range() {
+        let mut envelope = Envelope::new();
+        envelope.write(0xF9);
+        envelope.trigger();
+
+        envelope.clock();
+
+        assert_eq!(envelope.volume(), 15);
+    }
+
+    #[test]
+    fn period_0_stops_the_envelope() {
+        let mut envelope = Envelope::new();
+        envelope.write(0x80);
+        envelope.trigger();
+
+        envelope.clock();
+
+        assert_eq!(envelope.volume(), 8);
+    }
+
+    #[test]
+    fn dac() {
+        let mut envelope = Envelope::new();
+
+        envelope.write(0x07);
+        assert!(!envelope.dac_enabled());
+
+        envelope.write(0x08);
+        assert!(envelope.dac_enabled());
+    }
+}
@@ -0,0 +1,80 @@
+/// Turns the channel off after a number of frame sequencer length clocks,
+/// if enabled.
+#[derive(Clone)]
+pub struct LengthCounter {
+    enabled: bool,
+    counter: u16,
+    max: u16,
+}
+
+impl LengthCounter {
+    pub fn new(max: u16) -> LengthCounter {
+        LengthCounter {
+            enabled: false,
+            counter: 0,
+            max,
+        }
+    }
+
+    /// The length registers hold the number of clocks already elapsed.
+    pub fn load(&mut self, value: u8) {
+        self.counter = self.max - (u16::from(value) % self.max);
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    pub fn trigger(&mut self) {
+        if self.counter == 0 {
+            self.counter = self.max;
+        }
+    }
+
+    /// Returns true when the counter expires and the channel must be
+    /// disabled.
+    pub fn clock(&mut self) -> bool {
+        if !self.enabled || self.counter == 0 {
+            return false;
+        }
+
+        self.counter -= 1;
+        self.counter == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expires_after_the_loaded_length() {
+        let mut length = LengthCounter::new(64);
+        length.load(62);
+        length.set_enabled(true);
+
+        assert!(!length.clock());
+        assert!(length.clock());
+        assert!(!length.clock());
+    }
+
+    #[test]
+    fn does_not_count_when_disabled() {
+        let mut length = LengthCounter::new(64);
+        length.load(63);
+
+        assert!(!length.clock());
+    }
+
+    #[test]
+    fn trigger_reloads_an_expired_counter() {
+        let mut length = LengthCounter::new(256);
+        length.set_enabled(true);
+        length.trigger();
+
+        for _ in 0..255 {
+            assert!(!length.clock());
+        }
+        assert!(length.clock());
+    }
+}
@@ -0,0 +1,401 @@
+use std::mem;
+
+use crate::apu::noise::Noise;
+use crate::apu::square::Square;
+use crate::apu::wave::Wave;
+
+mod envelope;
+mod length;
+mod noise;
+mod square;
+mod wave;
+
+pub const APU_BEGIN: u16 = 0xFF10;
+pub const NR50_ADDR: u16 = 0xFF24;
+pub const NR51_ADDR: u16 = 0xFF25;
+pub const NR52_ADDR: u16 = 0xFF26;
+pub const WAVE_RAM_BEGIN: u16 = 0xFF30;
+pub const APU_END: u16 = 0xFF3F;
+
+// T-cycles per second.
+const CLOCK_RATE: u32 = 4_194_304;
+
+// The frame sequencer runs at 512 Hz.
+const FRAME_SEQUENCER_PERIOD: u16 = 8192;
+
+// Bits that always read as 1 in 0xFF10-0xFF2F. Some registers can only be
+// written, and some bits do not exist.
+const READ_MASKS: [u8; 0x20] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10-NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // NR20-NR24
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // NR40-NR44
+    0x00, 0x00, 0x70, // NR50-NR52
+    0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, // Unused
+];
+
+#[derive(Clone)]
+pub struct Apu {
+    channel1: Square,
+    channel2: Square,
+    channel3: Wave,
+    channel4: Noise,
+
+    // Last values written to 0xFF10-0xFF2F.
+    registers: [u8; 0x20],
+    wave_ram: [u8; 16],
+
+    powered: bool,
+
+    frame_sequencer_timer: u16,
+    frame_sequencer_step: u8,
+
+    // Stereo samples are only produced when there is an output rate.
+    sample_rate: Option<u32>,
+    sample_timer: u32,
+    samples: Vec<f32>,
+
+    // The output goes through a high-pass filter that removes the DC
+    // offset of the DACs, like the capacitors of the real hardware.
+    capacitor_factor: f32,
+    capacitors: [f32; 2],
+}
+
+impl Default for Apu {
+    fn default() -> Apu {
+        Apu::new()
+    }
+}
+
+impl Apu {
+    /// Creates an APU that does not produce samples.
+    pub fn new() -> Apu {
+        Apu {
+            channel1: Square::new(true),
+            channel2: Square::new(false),
+            channel3: Wave::new(),
+            channel4: Noise::new(),
+            registers: [0; 0x20],
+            wave_ram: [0; 16],
+            powered: false,
+            frame_sequencer_timer: FRAME_SEQUENCER_PERIOD,
+            frame_sequencer_step: 0,
+            sample_rate: None,
+            sample_timer: 0,
+            samples: Vec::new(),
+            capacitor_factor: 0.0,
+            capacitors: [0.0; 2],
+        }
+    }
+
+    /// Creates an APU that produces stereo samples at the given rate, in Hz.
+    pub fn with_sample_rate(sample_rate: u32) -> Apu {
+        let mut apu = Apu::new();
+        apu.sample_rate = Some(sample_rate);
+        apu.capacitor_factor = 0.999_958_f32.powf(CLOCK_RATE as f32 / sample_rate as f32);
+        apu
+    }
+
+    pub fn handles(address: u16) -> bool {
+        (APU_BEGIN..=APU_END).contains(&address)
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            NR52_ADDR => {
+                let mut value = 0x70;
+
+                if self.powered {
+                    value |= 0x80;
+                }
+
+                let channels = [
+                    self.channel1.enabled(),
+                    self.channel2.enabled(),
+                    self.channel3.enabled(),
+                    self.channel4.enabled(),
+                ];
+
+                for (bit, &enabled) in channels.iter().enumerate() {
+                    if enabled {
+                        value |= 1 << bit;
+                    }
+                }
+
+                value
+            }
+            WAVE_RAM_BEGIN..=APU_END => self.wave_ram[usize::from(address - WAVE_RAM_BEGIN)],
+            _ => {
+                let index = usize::from(address - APU_BEGIN);
+                self.registers[index] | READ_MASKS[index]
+            }
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        if address >= WAVE_RAM_BEGIN {
+            self.wave_ram[usize::from(address - WAVE_RAM_BEGIN)] = value;
+            return;
+        }
+
+        if address == NR52_ADDR {
+            self.write_nr52(value);
+            return;
+        }
+
+        // While the APU is off, the registers can't be written.
+        if !self.powered {
+            return;
+        }
+
+        let index = (address - APU_BEGIN) as u8;
+        self.registers[usize::from(index)] = value;
+
+        match index {
+            0x00..=0x04 => self.channel1.write(index, value),
+            0x05..=0x09 => self.channel2.write(index - 0x05, value),
+            0x0A..=0x0E => self.channel3.write(index - 0x0A, value),
+            0x0F..=0x13 => self.channel4.write(index - 0x0F, value),
+            _ => (),
+        }
+    }
+
+    pub fn tick(&mut self, m_cycles: u8) {
+        for _ in 0..(u16::from(m_cycles) * 4) {
+            if self.powered {
+                self.step();
+            }
+
+            if let Some(sample_rate) = self.sample_rate {
+                self.sample_timer += sample_rate;
+
+                if self.sample_timer >= CLOCK_RATE {
+                    self.sample_timer -= CLOCK_RATE;
+                    self.push_sample();
+                }
+            }
+        }
+    }
+
+    /// Returns the samples produced since the last call, interleaved left and
+    /// right, in the range -1.0 to 1.0.
+    pub fn take_samples(&mut self) -> Vec<f32> {
+        mem::take(&mut self.samples)
+    }
+
+    pub fn sample_rate(&self) -> Option<u32> {
+        self.sample_rate
+    }
+
+    fn write_nr52(&mut self, value: u8) {
+        let powered = value & 0x80 != 0;
+
+        // Turning the APU off clears all its registers.
+        if self.powered && !powered {
+            self.channel1 = Square::new(true);
+            self.channel2 = Square::new(false);
+            self.channel3 = Wave::new();
+            self.channel4 = Noise::new();
+            self.registers = [0; 0x20];
+        } else if !self.powered && powered {
+            self.frame_sequencer_timer = FRAME_SEQUENCER_PERIOD;
+            self.frame_sequencer_step = 0;
+        }
+
+        self.powered = powered;
+    }
+
+    // One T-cycle.
+    fn step(&mut self) {
+        self.channel1.step();
+        self.channel2.step();
+        self.channel3.step(&self.wave_ram);
+        self.channel4.step();
+
+        self.frame_sequencer_timer -= 1;
+
+        if self.frame_sequencer_timer == 0 {
+            self.frame_sequencer_timer = FRAME_SEQUENCER_PERIOD;
+            self.clock_frame_sequencer();
+        }
+    }
+
+    // Length counters are clocked at 256 Hz, the sweep at 128 Hz and the
+    // envelopes at 64 Hz.
+    fn clock_frame_sequencer(&mut self) {
+        let step = self.frame_sequencer_step;
+
+        if step & 1 == 0 {
+            self.channel1.clock_length();
+            self.channel2.clock_length();
+            self.channel3.clock_length();
+            self.channel4.clock_length();
+        }
+
+        if step == 2 || step == 6 {
+            self.channel1.clock_sweep();
+        }
+
+        if step == 7 {
+            self.channel1.clock_envelope();
+            self.channel2.clock_envelope();
+            self.channel4.clock_envelope();
+        }
+
+        self.frame_sequencer_step = (step + 1) % 8;
+    }
+
+    fn push_sample(&mut self) {
+        let outputs = [
+            Apu::dac(self.channel1.output()),
+            Apu::dac(self.channel2.output()),
+            Apu::dac(self.channel3.output()),
+            Apu::dac(self.channel4.output()),
+        ];
+
+        let nr50 = self.registers[usize::from(NR50_ADDR - APU_BEGIN)];
+        let nr51 = self.registers[usize::from(NR51_ADDR - APU_BEGIN)];
+
+        // NR51 has the channels sent to the right in the lower bits, and to
+        // the left in the upper ones. NR50 has the volume of each side.
+        let sides = [(nr51 >> 4, (nr50 >> 4) & 0x07), (nr51, nr50 & 0x07)];
+
+        for (side, &(channels, volume)) in sides.iter().enumerate() {
+            let mixed: f32 = outputs
+                .iter()
+                .enumerate()
+                .filter(|(channel, _)| channels & (1 << channel) != 0)
+                .map(|(_, output)| output)
+                .sum();
+
+            let sample = mixed / 4.0 * f32::from(volume + 1) / 8.0;
+
+            let filtered = sample - self.capacitors[side];
+            self.capacitors[side] = sample - filtered * self.capacitor_factor;
+
+            self.samples.push(filtered);
+        }
+    }
+
+    // Converts the digital output of a channel, 0-15, to -1.0-1.0. A disabled
+    // DAC outputs 0.
+    fn dac(output: Option<u8>) -> f32 {
+        match output {
+            Some(value) => f32::from(value) / 7.5 - 1.0,
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn powered_apu(sample_rate: u32) -> Apu {
+        let mut apu = Apu::with_sample_rate(sample_rate);
+        apu.write(NR52_ADDR, 0x80);
+        apu.write(NR50_ADDR, 0x77);
+        apu.write(NR51_ADDR, 0xFF);
+        apu
+    }
+
+    #[test]
+    fn produces_samples_at_the_output_rate() {
+        let mut apu = powered_apu(32_768);
+
+        // 1/64 of a second.
+        for _ in 0..(CLOCK_RATE / 4 / 64) {
+            apu.tick(1);
+        }
+
+        assert_eq!(apu.take_samples().len(), 2 * 512);
+        assert_eq!(apu.take_samples().len(), 0);
+    }
+
+    #[test]
+    fn no_samples_without_output_rate() {
+        let mut apu = Apu::new();
+
+        apu.tick(100);
+
+        assert!(apu.take_samples().is_empty());
+    }
+
+    #[test]
+    fn nr52_reports_enabled_channels() {
+        let mut apu = powered_apu(48_000);
+
+        apu.write(0xFF12, 0xF0);
+        apu.write(0xFF14, 0x80);
+        apu.write(0xFF21, 0xF0);
+        apu.write(0xFF23, 0x80);
+
+        assert_eq!(apu.read(NR52_ADDR), 0xF9);
+    }
+
+    #[test]
+    fn power_off_clears_registers() {
+        let mut apu = powered_apu(48_000);
+        apu.write(0xFF12, 0xF0);
+        apu.write(0xFF14, 0x80);
+
+        apu.write(NR52_ADDR, 0);
+        apu.write(0xFF12, 0xF0);
+
+        assert_eq!(apu.read(0xFF12), 0);
+        assert_eq!(apu.read(NR50_ADDR), 0);
+        assert_eq!(apu.read(NR52_ADDR), 0x70);
+    }
+
+    #[test]
+    fn wave_ram_is_accessible_while_off() {
+        let mut apu = Apu::new();
+
+        apu.write(0xFF30, 0x42);
+
+        assert_eq!(apu.read(0xFF30), 0x42);
+    }
+
+    #[test]
+    fn unused_bits_read_as_1() {
+        let mut apu = powered_apu(48_000);
+
+        apu.write(0xFF11, 0x80);
+
+        assert_eq!(apu.read(0xFF11), 0xBF);
+        assert_eq!(apu.read(0xFF13), 0xFF);
+        assert_eq!(apu.read(0xFF15), 0xFF);
+        assert_eq!(apu.read(0xFF2F), 0xFF);
+    }
+
+    #[test]
+    fn frame_sequencer_clocks_length() {
+        let mut apu = powered_apu(48_000);
+        apu.write(0xFF12, 0xF0);
+        apu.write(0xFF11, 62);
+        apu.write(0xFF14, 0xC0);
+
+        // 2 length clocks happen in 3 frame sequencer steps.
+        for _ in 0..(3 * u32::from(FRAME_SEQUENCER_PERIOD) / 4) {
+            apu.tick(1);
+        }
+
+        assert_eq!(apu.read(NR52_ADDR) & 0x01, 0);
+    }
+
+    #[test]
+    fn panning() {
+        let mut apu = powered_apu(CLOCK_RATE / 4);
+        apu.write(NR51_ADDR, 0x10);
+        apu.write(0xFF12, 0xF0);
+        apu.write(0xFF11, 0xC0);
+        apu.write(0xFF13, 0xFF);
+        apu.write(0xFF14, 0x87);
+
+        apu.tick(1);
+        let samples = apu.take_samples();
+
+        assert!(samples[0] != 0.0);
+        assert_eq!(samples[1], 0.0);
+    }
+}
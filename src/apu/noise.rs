@@ -0,0 +1,158 @@
+use crate::apu::envelope::Envelope;
+use crate::apu::length::LengthCounter;
+
+const DIVISORS: [u16; 8] = [8, 16, 32, 48, 64, 80, 96, 112];
+
+/// Channel 4. Outputs the inverse of bit 0 of a 15-bit linear feedback shift
+/// register, or 7-bit in width mode.
+#[derive(Clone)]
+pub struct Noise {
+    length: LengthCounter,
+    envelope: Envelope,
+
+    clock_shift: u8,
+    width_mode: bool,
+    divisor_code: u8,
+
+    timer: u32,
+    lfsr: u16,
+
+    enabled: bool,
+}
+
+impl Noise {
+    pub fn new() -> Noise {
+        Noise {
+            length: LengthCounter::new(64),
+            envelope: Envelope::new(),
+            clock_shift: 0,
+            width_mode: false,
+            divisor_code: 0,
+            timer: 0,
+            lfsr: 0x7FFF,
+            enabled: false,
+        }
+    }
+
+    /// Writes NR40-NR44, given as 0-4. NR40 does not exist.
+    pub fn write(&mut self, register: u8, value: u8) {
+        match register {
+            1 => self.length.load(value & 0x3F),
+            2 => {
+                self.envelope.write(value);
+
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => {
+                self.clock_shift = value >> 4;
+                self.width_mode = value & 0x08 != 0;
+                self.divisor_code = value & 0x07;
+            }
+            4 => {
+                self.length.set_enabled(value & 0x40 != 0);
+
+                if value & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    pub fn step(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+
+        if self.timer == 0 {
+            self.timer = self.period();
+
+            let feedback = (self.lfsr & 1) ^ ((self.lfsr >> 1) & 1);
+            self.lfsr = (self.lfsr >> 1) | (feedback << 14);
+
+            if self.width_mode {
+                self.lfsr = (self.lfsr & !(1 << 6)) | (feedback << 6);
+            }
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    /// Digital output, 0-15, or None if the DAC is off.
+    pub fn output(&self) -> Option<u8> {
+        if !self.envelope.dac_enabled() {
+            return None;
+        }
+
+        if !self.enabled || self.lfsr & 1 != 0 {
+            return Some(0);
+        }
+
+        Some(self.envelope.volume())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn period(&self) -> u32 {
+        u32::from(DIVISORS[usize::from(self.divisor_code)]) << self.clock_shift
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.length.trigger();
+        self.envelope.trigger();
+        self.timer = self.period();
+        self.lfsr = 0x7FFF;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lfsr_sequence() {
+        let mut noise = Noise::new();
+        noise.write(2, 0xF0);
+        noise.write(4, 0x80);
+
+        for _ in 0..8 {
+            noise.step();
+        }
+
+        // 0x7FFF shifted right with a feedback of 0.
+        assert_eq!(noise.lfsr, 0x3FFF);
+        assert_eq!(noise.output(), Some(0));
+    }
+
+    #[test]
+    fn width_mode_has_a_period_of_127() {
+        let mut noise = Noise::new();
+        noise.write(2, 0xF0);
+        noise.write(3, 0x08);
+        noise.write(4, 0x80);
+
+        let mut outputs = Vec::new();
+        for _ in 0..254 {
+            for _ in 0..8 {
+                noise.step();
+            }
+            outputs.push(noise.output().unwrap());
+        }
+
+        assert_eq!(outputs[..127], outputs[127..]);
+        assert!(outputs.contains(&15));
+    }
+}
@@ -0,0 +1,296 @@
+use crate::apu::envelope::Envelope;
+use crate::apu::length::LengthCounter;
+
+const DUTY_PATTERNS: [[u8; 8]; 4] = [
+    [0, 0, 0, 0, 0, 0, 0, 1],
+    [1, 0, 0, 0, 0, 0, 0, 1],
+    [1, 0, 0, 0, 0, 1, 1, 1],
+    [0, 1, 1, 1, 1, 1, 1, 0],
+];
+
+/// Frequency sweep of channel 1, set with NR10.
+#[derive(Clone)]
+struct Sweep {
+    period: u8,
+    negate: bool,
+    shift: u8,
+
+    enabled: bool,
+    timer: u8,
+    shadow_frequency: u16,
+}
+
+impl Sweep {
+    fn new() -> Sweep {
+        Sweep {
+            period: 0,
+            negate: false,
+            shift: 0,
+            enabled: false,
+            timer: 0,
+            shadow_frequency: 0,
+        }
+    }
+
+    fn write(&mut self, value: u8) {
+        self.period = (value >> 4) & 0x07;
+        self.negate = value & 0x08 != 0;
+        self.shift = value & 0x07;
+    }
+
+    // A period of 0 is treated as 8 by the timer.
+    fn reload_timer(&mut self) {
+        self.timer = if self.period == 0 { 8 } else { self.period };
+    }
+
+    fn next_frequency(&self) -> u16 {
+        let delta = self.shadow_frequency >> self.shift;
+
+        if self.negate {
+            self.shadow_frequency.wrapping_sub(delta)
+        } else {
+            self.shadow_frequency + delta
+        }
+    }
+}
+
+/// Channels 1 and 2. Only channel 1 has a sweep.
+#[derive(Clone)]
+pub struct Square {
+    sweep: Option<Sweep>,
+    length: LengthCounter,
+    envelope: Envelope,
+
+    duty: u8,
+    duty_position: u8,
+
+    // 11 bits. The period of the timer is (2048 - frequency) * 4 T-cycles.
+    frequency: u16,
+    timer: u16,
+
+    enabled: bool,
+}
+
+impl Square {
+    pub fn new(with_sweep: bool) -> Square {
+        Square {
+            sweep: if with_sweep { Some(Sweep::new()) } else { None },
+            length: LengthCounter::new(64),
+            envelope: Envelope::new(),
+            duty: 0,
+            duty_position: 0,
+            frequency: 0,
+            timer: 0,
+            enabled: false,
+        }
+    }
+
+    /// Writes NRx0-NRx4, given as 0-4.
+    pub fn write(&mut self, register: u8, value: u8) {
+        match register {
+            0 => {
+                if let Some(sweep) = &mut self.sweep {
+                    sweep.write(value);
+                }
+            }
+            1 => {
+                self.duty = value >> 6;
+                self.length.load(value & 0x3F);
+            }
+            2 => {
+                self.envelope.write(value);
+
+                if !self.envelope.dac_enabled() {
+                    self.enabled = false;
+                }
+            }
+            3 => self.frequency = (self.frequency & 0x700) | u16::from(value),
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | (u16::from(value & 0x07) << 8);
+                self.length.set_enabled(value & 0x40 != 0);
+
+                if value & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    pub fn step(&mut self) {
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+
+        if self.timer == 0 {
+            self.timer = (2048 - self.frequency) * 4;
+            self.duty_position = (self.duty_position + 1) % 8;
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    pub fn clock_envelope(&mut self) {
+        self.envelope.clock();
+    }
+
+    pub fn clock_sweep(&mut self) {
+        let sweep = match &mut self.sweep {
+            Some(sweep) => sweep,
+            None => return,
+        };
+
+        if sweep.timer > 0 {
+            sweep.timer -= 1;
+        }
+
+        if sweep.timer > 0 {
+            return;
+        }
+
+        sweep.reload_timer();
+
+        if !sweep.enabled || sweep.period == 0 {
+            return;
+        }
+
+        let frequency = sweep.next_frequency();
+
+        if frequency > 2047 {
+            self.enabled = false;
+        } else if sweep.shift != 0 {
+            sweep.shadow_frequency = frequency;
+            self.frequency = frequency;
+
+            // The new frequency is checked again for overflow, but not used.
+            if sweep.next_frequency() > 2047 {
+                self.enabled = false;
+            }
+        }
+    }
+
+    /// Digital output, 0-15, or None if the DAC is off.
+    pub fn output(&self) -> Option<u8> {
+        if !self.envelope.dac_enabled() {
+            return None;
+        }
+
+        if !self.enabled {
+            return Some(0);
+        }
+
+        Some(
+            DUTY_PATTERNS[usize::from(self.duty)][usize::from(self.duty_position)]
+                * self.envelope.volume(),
+        )
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn trigger(&mut self) {
+        self.enabled = self.envelope.dac_enabled();
+        self.length.trigger();
+        self.envelope.trigger();
+        self.timer = (2048 - self.frequency) * 4;
+
+        if let Some(sweep) = &mut self.sweep {
+            sweep.shadow_frequency = self.frequency;
+            sweep.reload_timer();
+            sweep.enabled = sweep.period != 0 || sweep.shift != 0;
+
+            if sweep.shift != 0 && sweep.next_frequency() > 2047 {
+                self.enabled = false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trigger_enables_the_channel() {
+        let mut square = Square::new(false);
+        square.write(2, 0xF0);
+
+        square.write(4, 0x80);
+
+        assert!(square.enabled());
+    }
+
+    #[test]
+    fn trigger_with_dac_off_does_not_enable() {
+        let mut square = Square::new(false);
+
+        square.write(4, 0x80);
+
+        assert!(!square.enabled());
+        assert_eq!(square.output(), None);
+    }
+
+    #[test]
+    fn duty_cycle() {
+        let mut square = Square::new(false);
+        square.write(1, 0x80);
+        square.write(2, 0xF0);
+        square.write(3, 0xFF);
+        square.write(4, 0x87);
+
+        // A frequency of 2047 advances the duty every 4 T-cycles.
+        let mut outputs = Vec::new();
+        for _ in 0..8 {
+            for _ in 0..4 {
+                square.step();
+            }
+            outputs.push(square.output().unwrap());
+        }
+
+        assert_eq!(outputs, vec![0, 0, 0, 0, 15, 15, 15, 15]);
+    }
+
+    #[test]
+    fn length_disables_the_channel() {
+        let mut square = Square::new(false);
+        square.write(1, 63);
+        square.write(2, 0xF0);
+        square.write(4, 0xC0);
+
+        square.clock_length();
+
+        assert!(!square.enabled());
+    }
+
+    #[test]
+    fn sweep_increases_frequency() {
+        let mut square = Square::new(true);
+        square.write(0, 0x11);
+        square.write(2, 0xF0);
+        square.write(3, 0x00);
+        square.write(4, 0x81);
+
+        square.clock_sweep();
+
+        assert_eq!(square.frequency, 0x180);
+        assert!(square.enabled());
+    }
+
+    #[test]
+    fn sweep_overflow_disables_the_channel() {
+        let mut square = Square::new(true);
+        square.write(0, 0x11);
+        square.write(2, 0xF0);
+        square.write(3, 0x00);
+        square.write(4, 0x86);
+
+        square.clock_sweep();
+
+        assert!(!square.enabled());
+    }
+}
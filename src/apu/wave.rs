@@ -0,0 +1,161 @@
+use crate::apu::length::LengthCounter;
+
+/// Channel 3. Plays the 32 4-bit samples of wave RAM, upper nibble first.
+#[derive(Clone)]
+pub struct Wave {
+    length: LengthCounter,
+
+    dac_enabled: bool,
+
+    // 0: mute, 1: 100%, 2: 50%, 3: 25%.
+    volume_code: u8,
+
+    // 11 bits. The period of the timer is (2048 - frequency) * 2 T-cycles.
+    frequency: u16,
+    timer: u16,
+
+    position: u8,
+    sample: u8,
+
+    enabled: bool,
+}
+
+impl Wave {
+    pub fn new() -> Wave {
+        Wave {
+            length: LengthCounter::new(256),
+            dac_enabled: false,
+            volume_code: 0,
+            frequency: 0,
+            timer: 0,
+            position: 0,
+            sample: 0,
+            enabled: false,
+        }
+    }
+
+    /// Writes NR30-NR34, given as 0-4.
+    pub fn write(&mut self, register: u8, value: u8) {
+        match register {
+            0 => {
+                self.dac_enabled = value & 0x80 != 0;
+
+                if !self.dac_enabled {
+                    self.enabled = false;
+                }
+            }
+            1 => self.length.load(value),
+            2 => self.volume_code = (value >> 5) & 0x03,
+            3 => self.frequency = (self.frequency & 0x700) | u16::from(value),
+            4 => {
+                self.frequency = (self.frequency & 0xFF) | (u16::from(value & 0x07) << 8);
+                self.length.set_enabled(value & 0x40 != 0);
+
+                if value & 0x80 != 0 {
+                    self.trigger();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    pub fn step(&mut self, wave_ram: &[u8]) {
+        if self.timer > 0 {
+            self.timer -= 1;
+        }
+
+        if self.timer == 0 {
+            self.timer = (2048 - self.frequency) * 2;
+            self.position = (self.position + 1) % 32;
+
+            let byte = wave_ram[usize::from(self.position / 2)];
+            self.sample = if self.position & 1 == 0 {
+                byte >> 4
+            } else {
+                byte & 0x0F
+            };
+        }
+    }
+
+    pub fn clock_length(&mut self) {
+        if self.length.clock() {
+            self.enabled = false;
+        }
+    }
+
+    /// Digital output, 0-15, or None if the DAC is off.
+    pub fn output(&self) -> Option<u8> {
+        if !self.dac_enabled {
+            return None;
+        }
+
+        if !self.enabled || self.volume_code == 0 {
+            return Some(0);
+        }
+
+        Some(self.sample >> (self.volume_code - 1))
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    // The first sample played after a trigger is the second one, the one at
+    // position 0 is skipped.
+    fn trigger(&mut self) {
+        self.enabled = self.dac_enabled;
+        self.length.trigger();
+        self.timer = (2048 - self.frequency) * 2;
+        self.position = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playing_wave(volume_code: u8) -> Wave {
+        let mut wave = Wave::new();
+        wave.write(0, 0x80);
+        wave.write(2, volume_code << 5);
+        wave.write(3, 0xFF);
+        wave.write(4, 0x87);
+        wave
+    }
+
+    #[test]
+    fn plays_wave_ram_nibbles() {
+        let wave_ram = [0x0F, 0xA5, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let mut wave = playing_wave(1);
+
+        let mut outputs = Vec::new();
+        for _ in 0..3 {
+            wave.step(&wave_ram);
+            wave.step(&wave_ram);
+            outputs.push(wave.output().unwrap());
+        }
+
+        assert_eq!(outputs, vec![0x0F, 0x0A, 0x05]);
+    }
+
+    #[test]
+    fn volume_shift() {
+        let wave_ram = [0x0C; 16];
+        let mut wave = playing_wave(3);
+
+        wave.step(&wave_ram);
+        wave.step(&wave_ram);
+
+        assert_eq!(wave.output(), Some(0x03));
+    }
+
+    #[test]
+    fn dac_off_disables_the_channel() {
+        let mut wave = playing_wave(1);
+
+        wave.write(0, 0);
+
+        assert!(!wave.enabled());
+        assert_eq!(wave.output(), None);
+    }
+}
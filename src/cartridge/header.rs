@@ -0,0 +1,385 @@
+use std::fmt;
+
+use crate::cartridge::{CartridgeError, ROM_BANK_SIZE};
+
+const HEADER_END: usize = 0x150;
+
+pub(crate) const TITLE_BEGIN: usize = 0x134;
+pub(crate) const TITLE_END: usize = 0x143; // Inclusive. It is the CGB flag in CGB games.
+pub(crate) const CGB_FLAG_ADDR: usize = 0x143;
+const NEW_LICENSEE_BEGIN: usize = 0x144;
+const SGB_FLAG_ADDR: usize = 0x146;
+const CARTRIDGE_TYPE_ADDR: usize = 0x147;
+const ROM_SIZE_ADDR: usize = 0x148;
+const RAM_SIZE_ADDR: usize = 0x149;
+const OLD_LICENSEE_ADDR: usize = 0x14B;
+const VERSION_ADDR: usize = 0x14C;
+pub(crate) const HEADER_CHECKSUM_ADDR: usize = 0x14D;
+const GLOBAL_CHECKSUM_ADDR: usize = 0x14E;
+
+// When the old licensee code has this value, the new one is used instead.
+const USE_NEW_LICENSEE: u8 = 0x33;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CgbSupport {
+    DmgOnly,
+    CgbCompatible,
+    CgbOnly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mbc {
+    NoMbc,
+    Mbc1,
+    Mbc2,
+    Mbc3,
+    Mbc5,
+    Mbc6,
+    Mbc7,
+    Mmm01,
+    PocketCamera,
+    Tama5,
+    HuC1,
+    HuC3,
+}
+
+// Describes the hardware in the cartridge: the memory bank controller and
+// the extra features that it supports.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CartridgeType {
+    pub code: u8,
+    pub mbc: Mbc,
+    pub ram: bool,
+    pub battery: bool,
+    pub timer: bool,
+    pub rumble: bool,
+}
+
+impl CartridgeType {
+    pub fn from_code(code: u8) -> Option<CartridgeType> {
+        // (MBC, RAM, battery, timer, rumble)
+        let (mbc, ram, battery, timer, rumble) = match code {
+            0x00 => (Mbc::NoMbc, false, false, false, false),
+            0x01 => (Mbc::Mbc1, false, false, false, false),
+            0x02 => (Mbc::Mbc1, true, false, false, false),
+            0x03 => (Mbc::Mbc1, true, true, false, false),
+            0x05 => (Mbc::Mbc2, false, false, false, false),
+            0x06 => (Mbc::Mbc2, false, true, false, false),
+            0x08 => (Mbc::NoMbc, true, false, false, false),
+            0x09 => (Mbc::NoMbc, true, true, false, false),
+            0x0B => (Mbc::Mmm01, false, false, false, false),
+            0x0C => (Mbc::Mmm01, true, false, false, false),
+            0x0D => (Mbc::Mmm01, true, true, false, false),
+            0x0F => (Mbc::Mbc3, false, true, true, false),
+            0x10 => (Mbc::Mbc3, true, true, true, false),
+            0x11 => (Mbc::Mbc3, false, false, false, false),
+            0x12 => (Mbc::Mbc3, true, false, false, false),
+            0x13 => (Mbc::Mbc3, true, true, false, false),
+            0x19 => (Mbc::Mbc5, false, false, false, false),
+            0x1A => (Mbc::Mbc5, true, false, false, false),
+            0x1B => (Mbc::Mbc5, true, true, false, false),
+            0x1C => (Mbc::Mbc5, false, false, false, true),
+            0x1D => (Mbc::Mbc5, true, false, false, true),
+            0x1E => (Mbc::Mbc5, true, true, false, true),
+            0x20 => (Mbc::Mbc6, false, false, false, false),
+            0x22 => (Mbc::Mbc7, true, true, false, true),
+            0xFC => (Mbc::PocketCamera, true, true, false, false),
+            0xFD => (Mbc::Tama5, true, true, true, false),
+            0xFE => (Mbc::HuC3, true, true, true, false),
+            0xFF => (Mbc::HuC1, true, true, false, false),
+            _ => return None,
+        };
+
+        Some(CartridgeType {
+            code,
+            mbc,
+            ram,
+            battery,
+            timer,
+            rumble,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Licensee {
+    Old(u8),
+    New(String),
+}
+
+impl Licensee {
+    pub fn is_nintendo(&self) -> bool {
+        match self {
+            Licensee::Old(code) => *code == 0x01,
+            Licensee::New(code) => code == "01",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CartridgeHeader {
+    pub title: String,
+    pub cgb_support: CgbSupport,
+    pub sgb_support: bool,
+    pub cartridge_type: CartridgeType,
+    pub rom_size: usize,
+    pub ram_size: usize,
+    pub licensee: Licensee,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+}
+
+impl CartridgeHeader {
+    /// Parses the header of the given ROM image. Fails if the image is too
+    /// short to contain it, if any of its fields has an invalid value, or if
+    /// its checksum does not match. The boot ROM refuses to run cartridges
+    /// with a wrong header checksum, so we do the same.
+    pub fn parse(rom: &[u8]) -> Result<CartridgeHeader, CartridgeError> {
+        if rom.len() < HEADER_END {
+            return Err(CartridgeError::Truncated {
+                expected: HEADER_END,
+                actual: rom.len(),
+            });
+        }
+
+        let header_checksum = rom[HEADER_CHECKSUM_ADDR];
+        let computed_checksum = CartridgeHeader::compute_header_checksum(rom);
+        if header_checksum != computed_checksum {
+            return Err(CartridgeError::HeaderChecksumMismatch {
+                expected: header_checksum,
+                actual: computed_checksum,
+            });
+        }
+
+        let cgb_support = match rom[CGB_FLAG_ADDR] {
+            0xC0 => CgbSupport::CgbOnly,
+            flag if flag & 0x80 != 0 => CgbSupport::CgbCompatible,
+            _ => CgbSupport::DmgOnly,
+        };
+
+        let cartridge_type = CartridgeType::from_code(rom[CARTRIDGE_TYPE_ADDR]).ok_or(
+            CartridgeError::UnknownCartridgeType(rom[CARTRIDGE_TYPE_ADDR]),
+        )?;
+
+        let rom_size = match rom[ROM_SIZE_ADDR] {
+            code @ 0x00..=0x08 => (2 * ROM_BANK_SIZE) << code,
+            code => return Err(CartridgeError::InvalidRomSize(code)),
+        };
+
+        let ram_size = match rom[RAM_SIZE_ADDR] {
+            0x00 => 0,
+            0x01 => 2 * 1024,
+            0x02 => 8 * 1024,
+            0x03 => 32 * 1024,
+            0x04 => 128 * 1024,
+            0x05 => 64 * 1024,
+            code => return Err(CartridgeError::InvalidRamSize(code)),
+        };
+
+        let licensee = match rom[OLD_LICENSEE_ADDR] {
+            USE_NEW_LICENSEE => Licensee::New(
+                rom[NEW_LICENSEE_BEGIN..NEW_LICENSEE_BEGIN + 2]
+                    .iter()
+                    .map(|&byte| byte as char)
+                    .collect(),
+            ),
+            code => Licensee::Old(code),
+        };
+
+        Ok(CartridgeHeader {
+            title: CartridgeHeader::parse_title(rom, cgb_support),
+            cgb_support,
+            sgb_support: rom[SGB_FLAG_ADDR] == 0x03,
+            cartridge_type,
+            rom_size,
+            ram_size,
+            licensee,
+            version: rom[VERSION_ADDR],
+            header_checksum,
+            global_checksum: u16::from(rom[GLOBAL_CHECKSUM_ADDR]) << 8
+                | u16::from(rom[GLOBAL_CHECKSUM_ADDR + 1]),
+        })
+    }
+
+    /// Checks the sum of all the bytes of the ROM against the one in the
+    /// header. Real hardware does not check it, and some ROMs get it wrong, so
+    /// this is not part of the parsing.
+    pub fn verify_global_checksum(&self, rom: &[u8]) -> Result<(), CartridgeError> {
+        let computed = CartridgeHeader::compute_global_checksum(rom);
+
+        if computed == self.global_checksum {
+            Ok(())
+        } else {
+            Err(CartridgeError::GlobalChecksumMismatch {
+                expected: self.global_checksum,
+                actual: computed,
+            })
+        }
+    }
+
+    pub fn compute_header_checksum(rom: &[u8]) -> u8 {
+        rom[TITLE_BEGIN..HEADER_CHECKSUM_ADDR]
+            .iter()
+            .fold(0_u8, |checksum, &byte| {
+                checksum.wrapping_sub(byte).wrapping_sub(1)
+            })
+    }
+
+    pub fn compute_global_checksum(rom: &[u8]) -> u16 {
+        rom.iter()
+            .enumerate()
+            .filter(|(addr, _)| *addr != GLOBAL_CHECKSUM_ADDR && *addr != GLOBAL_CHECKSUM_ADDR + 1)
+            .fold(0_u16, |checksum, (_, &byte)| {
+                checksum.wrapping_add(u16::from(byte))
+            })
+    }
+
+    // The title is padded with zeros. In CGB games, the last byte is used for
+    // the CGB flag.
+    fn parse_title(rom: &[u8], cgb_support: CgbSupport) -> String {
+        let end = match cgb_support {
+            CgbSupport::DmgOnly => TITLE_END + 1,
+            _ => TITLE_END,
+        };
+
+        rom[TITLE_BEGIN..end]
+            .iter()
+            .take_while(|&&byte| byte != 0)
+            .map(|&byte| byte as char)
+            .collect()
+    }
+}
+
+impl fmt::Display for CartridgeHeader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "Title: {}\nType: {:?} (0x{:02X})\nROM: {} KiB; RAM: {} KiB\nCGB: {:?}; SGB: {}\nLicensee: {:?}; Version: {}",
+            self.title,
+            self.cartridge_type.mbc,
+            self.cartridge_type.code,
+            self.rom_size / 1024,
+            self.ram_size / 1024,
+            self.cgb_support,
+            self.sgb_support,
+            self.licensee,
+            self.version,
+        )
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    // Builds a ROM image with a valid header and the given cartridge type.
+    pub fn rom_with_header(title: &str, cartridge_type: u8, rom_size: u8, ram_size: u8) -> Vec<u8> {
+        let mut rom = vec![0; (2 * ROM_BANK_SIZE) << rom_size];
+
+        for (i, byte) in title.bytes().enumerate() {
+            rom[TITLE_BEGIN + i] = byte;
+        }
+        rom[CARTRIDGE_TYPE_ADDR] = cartridge_type;
+        rom[ROM_SIZE_ADDR] = rom_size;
+        rom[RAM_SIZE_ADDR] = ram_size;
+        rom[OLD_LICENSEE_ADDR] = 0x01;
+        rom[HEADER_CHECKSUM_ADDR] = CartridgeHeader::compute_header_checksum(&rom);
+
+        let global_checksum = CartridgeHeader::compute_global_checksum(&rom);
+        rom[GLOBAL_CHECKSUM_ADDR] = (global_checksum >> 8) as u8;
+        rom[GLOBAL_CHECKSUM_ADDR + 1] = (global_checksum & 0xFF) as u8;
+
+        rom
+    }
+
+    #[test]
+    fn parse_header() {
+        let rom = rom_with_header("TETRIS", 0x03, 0x02, 0x03);
+
+        let header = CartridgeHeader::parse(&rom).unwrap();
+
+        assert_eq!(header.title, "TETRIS");
+        assert_eq!(header.cgb_support, CgbSupport::DmgOnly);
+        assert!(!header.sgb_support);
+        assert_eq!(header.cartridge_type.mbc, Mbc::Mbc1);
+        assert!(header.cartridge_type.ram);
+        assert!(header.cartridge_type.battery);
+        assert_eq!(header.rom_size, 128 * 1024);
+        assert_eq!(header.ram_size, 32 * 1024);
+        assert!(header.licensee.is_nintendo());
+        assert_eq!(header.version, 0);
+    }
+
+    #[test]
+    fn parse_cgb_header() {
+        let mut rom = rom_with_header("POKEMON GOLD", 0x10, 0x06, 0x03);
+        rom[CGB_FLAG_ADDR] = 0x80;
+        rom[SGB_FLAG_ADDR] = 0x03;
+        rom[OLD_LICENSEE_ADDR] = USE_NEW_LICENSEE;
+        rom[NEW_LICENSEE_BEGIN] = b'0';
+        rom[NEW_LICENSEE_BEGIN + 1] = b'1';
+        rom[HEADER_CHECKSUM_ADDR] = CartridgeHeader::compute_header_checksum(&rom);
+
+        let header = CartridgeHeader::parse(&rom).unwrap();
+
+        assert_eq!(header.title, "POKEMON GOLD");
+        assert_eq!(header.cgb_support, CgbSupport::CgbCompatible);
+        assert!(header.sgb_support);
+        assert_eq!(header.cartridge_type.mbc, Mbc::Mbc3);
+        assert!(header.cartridge_type.timer);
+        assert_eq!(header.licensee, Licensee::New(String::from("01")));
+        assert!(header.licensee.is_nintendo());
+    }
+
+    #[test]
+    fn parse_truncated_rom() {
+        let rom = vec![0; 0x100];
+
+        assert_eq!(
+            CartridgeHeader::parse(&rom),
+            Err(CartridgeError::Truncated {
+                expected: HEADER_END,
+                actual: 0x100
+            })
+        );
+    }
+
+    #[test]
+    fn parse_wrong_header_checksum() {
+        let mut rom = rom_with_header("TETRIS", 0x00, 0x00, 0x00);
+        rom[HEADER_CHECKSUM_ADDR] = rom[HEADER_CHECKSUM_ADDR].wrapping_add(1);
+
+        match CartridgeHeader::parse(&rom) {
+            Err(CartridgeError::HeaderChecksumMismatch { .. }) => (),
+            _ => panic!("The header checksum should not match"),
+        }
+    }
+
+    #[test]
+    fn parse_unknown_cartridge_type() {
+        let mut rom = rom_with_header("TETRIS", 0x00, 0x00, 0x00);
+        rom[CARTRIDGE_TYPE_ADDR] = 0x42;
+        rom[HEADER_CHECKSUM_ADDR] = CartridgeHeader::compute_header_checksum(&rom);
+
+        assert_eq!(
+            CartridgeHeader::parse(&rom),
+            Err(CartridgeError::UnknownCartridgeType(0x42))
+        );
+    }
+
+    #[test]
+    fn verify_global_checksum() {
+        let mut rom = rom_with_header("TETRIS", 0x00, 0x00, 0x00);
+        let header = CartridgeHeader::parse(&rom).unwrap();
+
+        assert_eq!(header.verify_global_checksum(&rom), Ok(()));
+
+        rom[0x200] = 0x42;
+
+        match header.verify_global_checksum(&rom) {
+            Err(CartridgeError::GlobalChecksumMismatch { .. }) => (),
+            _ => panic!("The global checksum should not match"),
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use crate::cartridge::{RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+const LOGO_BEGIN: usize = 0x104;
+const NINTENDO_LOGO: [u8; 48] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+
+// MBC1M multicarts are 8 Mbit, and contain several games, each of them in
+// a group of 16 banks with its own header.
+const MULTICART_SIZE: usize = 1024 * 1024;
+const MULTICART_GAME_BANKS: usize = 0x10;
+
+#[derive(Clone)]
+pub struct Mbc1 {
+    ram_enabled: bool,
+
+    // Lower 5 bits of the ROM bank number.
+    rom_bank: u8,
+
+    // 2-bit register that selects the RAM bank, or the upper bits of the ROM
+    // bank number.
+    bank_high: u8,
+
+    // In mode 0, the 2-bit register only affects 0x4000-0x7FFF. In mode 1, it
+    // also affects 0x0000-0x3FFF and the RAM.
+    advanced_banking_mode: bool,
+
+    // In multicarts the 2-bit register is connected one bit lower, so the
+    // ROM bank number only uses 4 bits from the low register.
+    multicart: bool,
+}
+
+impl Mbc1 {
+    pub fn new(rom: &[u8]) -> Mbc1 {
+        Mbc1 {
+            ram_enabled: false,
+            rom_bank: 1,
+            bank_high: 0,
+            advanced_banking_mode: false,
+            multicart: Mbc1::is_multicart(rom),
+        }
+    }
+
+    pub fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        let bank = if address < 0x4000 {
+            if self.advanced_banking_mode {
+                self.bank_high_bits()
+            } else {
+                0
+            }
+        } else {
+            self.bank_high_bits() | self.bank_low_bits()
+        };
+
+        let offset = bank * ROM_BANK_SIZE + (address as usize % ROM_BANK_SIZE);
+        rom[offset % rom.len()]
+    }
+
+    pub fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                // Bank 0 cannot be selected here. Only the 5 bits are checked,
+                // so writing 0x20 selects 0x21 instead.
+                self.rom_bank = match value & 0x1F {
+                    0 => 1,
+                    bank => bank,
+                }
+            }
+            0x4000..=0x5FFF => self.bank_high = value & 0b11,
+            _ => self.advanced_banking_mode = value & 1 == 1,
+        }
+    }
+
+    pub fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        match self.ram_offset(ram, address) {
+            Some(offset) => ram[offset],
+            None => 0xFF,
+        }
+    }
+
+    /// Returns whether the value was stored, which it is not when RAM is
+    /// disabled or missing.
+    pub fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool {
+        match self.ram_offset(ram, address) {
+            Some(offset) => {
+                ram[offset] = value;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn ram_offset(&self, ram: &[u8], address: u16) -> Option<usize> {
+        if !self.ram_enabled || ram.is_empty() {
+            return None;
+        }
+
+        let bank = if self.advanced_banking_mode {
+            usize::from(self.bank_high)
+        } else {
+            0
+        };
+
+        let offset = bank * RAM_BANK_SIZE + (address as usize - 0xA000);
+        Some(offset % ram.len())
+    }
+
+    fn bank_low_bits(&self) -> usize {
+        if self.multicart {
+            usize::from(self.rom_bank & 0x0F)
+        } else {
+            usize::from(self.rom_bank)
+        }
+    }
+
+    fn bank_high_bits(&self) -> usize {
+        if self.multicart {
+            usize::from(self.bank_high) << 4
+        } else {
+            usize::from(self.bank_high) << 5
+        }
+    }
+
+    // Multicarts cannot be told apart from the header. We detect them by
+    // checking whether the second game has the Nintendo logo too.
+    fn is_multicart(rom: &[u8]) -> bool {
+        if rom.len() != MULTICART_SIZE {
+            return false;
+        }
+
+        let logo_begin = MULTICART_GAME_BANKS * ROM_BANK_SIZE + LOGO_BEGIN;
+        rom[logo_begin..logo_begin + NINTENDO_LOGO.len()] == NINTENDO_LOGO
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each bank starts with its number so we can check which one is mapped.
+    fn rom_with_banks(n_banks: usize) -> Vec<u8> {
+        let mut rom = vec![0; n_banks * ROM_BANK_SIZE];
+
+        for bank in 0..n_banks {
+            rom[bank * ROM_BANK_SIZE] = bank as u8;
+        }
+
+        rom
+    }
+
+    #[test]
+    fn switch_rom_bank() {
+        let rom = rom_with_banks(8);
+        let mut mbc = Mbc1::new(&rom);
+
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 1);
+
+        mbc.write_rom(0x2000, 5);
+
+        assert_eq!(mbc.read_rom(&rom, 0x0000), 0);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 5);
+    }
+
+    #[test]
+    fn rom_bank_0_maps_to_1() {
+        let rom = rom_with_banks(64);
+        let mut mbc = Mbc1::new(&rom);
+
+        mbc.write_rom(0x2000, 0);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 1);
+
+        mbc.write_rom(0x2000, 0x20);
+        mbc.write_rom(0x4000, 1);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x21);
+    }
+
+    #[test]
+    fn rom_bank_is_masked_by_rom_size() {
+        let rom = rom_with_banks(4);
+        let mut mbc = Mbc1::new(&rom);
+
+        mbc.write_rom(0x2000, 6);
+
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 2);
+    }
+
+    #[test]
+    fn advanced_banking_mode_maps_upper_bits_in_bank_0_area() {
+        let rom = rom_with_banks(128);
+        let mut mbc = Mbc1::new(&rom);
+        mbc.write_rom(0x4000, 2);
+
+        assert_eq!(mbc.read_rom(&rom, 0x0000), 0);
+
+        mbc.write_rom(0x6000, 1);
+
+        assert_eq!(mbc.read_rom(&rom, 0x0000), 0x40);
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x41);
+    }
+
+    #[test]
+    fn ram_disabled_by_default() {
+        let rom = rom_with_banks(2);
+        let mut ram = vec![0; RAM_BANK_SIZE];
+        let mut mbc = Mbc1::new(&rom);
+
+        mbc.write_ram(&mut ram, 0xA000, 0x42);
+
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+        assert_eq!(ram[0], 0);
+    }
+
+    #[test]
+    fn switch_ram_bank() {
+        let rom = rom_with_banks(2);
+        let mut ram = vec![0; 4 * RAM_BANK_SIZE];
+        let mut mbc = Mbc1::new(&rom);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x6000, 1);
+        mbc.write_rom(0x4000, 2);
+
+        mbc.write_ram(&mut ram, 0xA001, 0x42);
+
+        assert_eq!(ram[2 * RAM_BANK_SIZE + 1], 0x42);
+        assert_eq!(mbc.read_ram(&ram, 0xA001), 0x42);
+
+        mbc.write_rom(0x6000, 0);
+
+        assert_eq!(mbc.read_ram(&ram, 0xA001), 0);
+    }
+
+    #[test]
+    fn not_multicart_without_second_logo() {
+        let rom = rom_with_banks(64);
+
+        assert!(!Mbc1::new(&rom).multicart);
+    }
+
+    #[test]
+    fn multicart() {
+        let mut rom = rom_with_banks(64);
+        let logo_begin = MULTICART_GAME_BANKS * ROM_BANK_SIZE + LOGO_BEGIN;
+        rom[logo_begin..logo_begin + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+        let mut mbc = Mbc1::new(&rom);
+
+        assert!(mbc.multicart);
+
+        mbc.write_rom(0x2000, 0x12);
+        mbc.write_rom(0x4000, 1);
+
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x12);
+
+        mbc.write_rom(0x6000, 1);
+
+        assert_eq!(mbc.read_rom(&rom, 0x0000), 0x10);
+    }
+}
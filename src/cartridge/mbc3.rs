@@ -0,0 +1,210 @@
+use crate::cartridge::rtc::{Rtc, DAYS_HIGH_REGISTER, SECONDS_REGISTER};
+use crate::cartridge::{RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+#[derive(Clone)]
+pub struct Mbc3 {
+    // Enables both the RAM and the RTC registers.
+    ram_and_rtc_enabled: bool,
+
+    // 7-bit ROM bank number.
+    rom_bank: u8,
+
+    // 0x00-0x03 select a RAM bank, 0x08-0x0C an RTC register.
+    ram_bank_or_rtc_register: u8,
+
+    // The RTC is latched by writing 0x00 and then 0x01.
+    latch_armed: bool,
+
+    rtc: Option<Rtc>,
+}
+
+impl Mbc3 {
+    pub fn new(has_rtc: bool) -> Mbc3 {
+        Mbc3 {
+            ram_and_rtc_enabled: false,
+            rom_bank: 1,
+            ram_bank_or_rtc_register: 0,
+            latch_armed: false,
+            rtc: if has_rtc { Some(Rtc::new()) } else { None },
+        }
+    }
+
+    pub fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        let bank = if address < 0x4000 {
+            0
+        } else {
+            usize::from(self.rom_bank)
+        };
+
+        let offset = bank * ROM_BANK_SIZE + (address as usize % ROM_BANK_SIZE);
+        rom[offset % rom.len()]
+    }
+
+    pub fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_and_rtc_enabled = value & 0x0F == 0x0A,
+            0x2000..=0x3FFF => {
+                self.rom_bank = match value & 0x7F {
+                    0 => 1,
+                    bank => bank,
+                }
+            }
+            0x4000..=0x5FFF => self.ram_bank_or_rtc_register = value,
+            _ => {
+                if self.latch_armed && value == 0x01 {
+                    if let Some(rtc) = &mut self.rtc {
+                        rtc.latch();
+                    }
+                }
+
+                self.latch_armed = value == 0x00;
+            }
+        }
+    }
+
+    pub fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        if !self.ram_and_rtc_enabled {
+            return 0xFF;
+        }
+
+        match (self.ram_bank_or_rtc_register, &self.rtc) {
+            (0x00..=0x03, _) if !ram.is_empty() => ram[self.ram_offset(ram, address)],
+            (SECONDS_REGISTER..=DAYS_HIGH_REGISTER, Some(rtc)) => {
+                rtc.read(self.ram_bank_or_rtc_register)
+            }
+            _ => 0xFF,
+        }
+    }
+
+    /// Returns whether the value was stored in RAM or in the RTC, which it
+    /// is not when they are disabled or missing.
+    pub fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool {
+        if !self.ram_and_rtc_enabled {
+            return false;
+        }
+
+        match (self.ram_bank_or_rtc_register, &mut self.rtc) {
+            (0x00..=0x03, _) if !ram.is_empty() => {
+                let offset = self.ram_offset(ram, address);
+                ram[offset] = value;
+                true
+            }
+            (SECONDS_REGISTER..=DAYS_HIGH_REGISTER, Some(rtc)) => {
+                rtc.write(self.ram_bank_or_rtc_register, value);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn tick(&mut self, m_cycles: u8) {
+        if let Some(rtc) = &mut self.rtc {
+            rtc.tick(m_cycles);
+        }
+    }
+
+    pub fn rtc(&self) -> Option<&Rtc> {
+        self.rtc.as_ref()
+    }
+
+    pub fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        self.rtc.as_mut()
+    }
+
+    fn ram_offset(&self, ram: &[u8], address: u16) -> usize {
+        let bank = usize::from(self.ram_bank_or_rtc_register);
+        (bank * RAM_BANK_SIZE + (address as usize - 0xA000)) % ram.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::rtc::{HOURS_REGISTER, MINUTES_REGISTER};
+
+    #[test]
+    fn switch_rom_bank() {
+        let mut rom = vec![0; 128 * ROM_BANK_SIZE];
+        rom[0x7F * ROM_BANK_SIZE] = 0x42;
+        let mut mbc = Mbc3::new(false);
+
+        mbc.write_rom(0x2000, 0x7F);
+
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x42);
+    }
+
+    #[test]
+    fn rom_bank_0_maps_to_1() {
+        let mut rom = vec![0; 4 * ROM_BANK_SIZE];
+        rom[ROM_BANK_SIZE] = 0x42;
+        let mut mbc = Mbc3::new(false);
+
+        mbc.write_rom(0x2000, 0);
+
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x42);
+    }
+
+    #[test]
+    fn switch_ram_bank() {
+        let mut ram = vec![0; 4 * RAM_BANK_SIZE];
+        let mut mbc = Mbc3::new(false);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 3);
+
+        mbc.write_ram(&mut ram, 0xA000, 0x42);
+
+        assert_eq!(ram[3 * RAM_BANK_SIZE], 0x42);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0x42);
+    }
+
+    #[test]
+    fn rtc_needs_latch_to_be_read() {
+        let ram = vec![0; RAM_BANK_SIZE];
+        let mut mbc = Mbc3::new(true);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, HOURS_REGISTER);
+        mbc.rtc_mut().unwrap().fast_forward(3 * 3600);
+
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0);
+
+        mbc.write_rom(0x6000, 0x00);
+        mbc.write_rom(0x6000, 0x01);
+
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 3);
+    }
+
+    #[test]
+    fn latch_needs_0_before_1() {
+        let ram = vec![0; RAM_BANK_SIZE];
+        let mut mbc = Mbc3::new(true);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, MINUTES_REGISTER);
+        mbc.rtc_mut().unwrap().fast_forward(60);
+
+        mbc.write_rom(0x6000, 0x01);
+
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0);
+    }
+
+    #[test]
+    fn write_rtc_register() {
+        let mut ram = vec![0; RAM_BANK_SIZE];
+        let mut mbc = Mbc3::new(true);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, SECONDS_REGISTER);
+
+        mbc.write_ram(&mut ram, 0xA000, 30);
+
+        assert_eq!(mbc.rtc().unwrap().current(SECONDS_REGISTER), 30);
+        assert_eq!(ram[0], 0);
+    }
+
+    #[test]
+    fn rtc_disabled_reads_0xff() {
+        let ram = vec![0; RAM_BANK_SIZE];
+        let mut mbc = Mbc3::new(true);
+        mbc.write_rom(0x4000, SECONDS_REGISTER);
+
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::VecDeque;
+
+use crate::cartridge::{RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+// On rumble cartridges, this bit of the RAM bank register drives the motor.
+const RUMBLE_BIT: u8 = 0b0000_1000;
+
+// Games switch the motor many times per frame, so only the most recent
+// events are kept if nobody consumes them.
+const MAX_RUMBLE_EVENTS: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RumbleEvent {
+    MotorOn,
+    MotorOff,
+}
+
+#[derive(Clone)]
+pub struct Mbc5 {
+    ram_enabled: bool,
+
+    // 9-bit ROM bank number. Unlike in the MBC1, bank 0 can be mapped at
+    // 0x4000-0x7FFF.
+    rom_bank: u16,
+
+    // 0x00-0x0F. Rumble cartridges only have bits 0-2.
+    ram_bank: u8,
+
+    has_rumble: bool,
+    motor_on: bool,
+
+    // Changes of the motor state that have not been consumed yet.
+    rumble_events: VecDeque<RumbleEvent>,
+}
+
+impl Mbc5 {
+    pub fn new(has_rumble: bool) -> Mbc5 {
+        Mbc5 {
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            has_rumble,
+            motor_on: false,
+            rumble_events: VecDeque::with_capacity(MAX_RUMBLE_EVENTS),
+        }
+    }
+
+    pub fn read_rom(&self, rom: &[u8], address: u16) -> u8 {
+        let bank = if address < 0x4000 {
+            0
+        } else {
+            usize::from(self.rom_bank)
+        };
+
+        let offset = bank * ROM_BANK_SIZE + (address as usize % ROM_BANK_SIZE);
+        rom[offset % rom.len()]
+    }
+
+    pub fn write_rom(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | u16::from(value),
+            0x3000..=0x3FFF => {
+                self.rom_bank = (self.rom_bank & 0xFF) | (u16::from(value & 0x01) << 8)
+            }
+            0x4000..=0x5FFF => {
+                if self.has_rumble {
+                    self.ram_bank = value & 0x07;
+                    self.set_motor(value & RUMBLE_BIT != 0);
+                } else {
+                    self.ram_bank = value & 0x0F;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    pub fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        if !self.ram_enabled || ram.is_empty() {
+            return 0xFF;
+        }
+
+        ram[self.ram_offset(ram, address)]
+    }
+
+    /// Returns whether the value was stored, which it is not when RAM is
+    /// disabled or missing.
+    pub fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) -> bool {
+        if !self.ram_enabled || ram.is_empty() {
+            return false;
+        }
+
+        let offset = self.ram_offset(ram, address);
+        ram[offset] = value;
+        true
+    }
+
+    /// Returns the rumble events since the last call, oldest first. If there
+    /// were too many, the oldest ones are lost.
+    pub fn take_rumble_events(&mut self) -> Vec<RumbleEvent> {
+        self.rumble_events.drain(..).collect()
+    }
+
+    fn set_motor(&mut self, on: bool) {
+        if on == self.motor_on {
+            return;
+        }
+
+        self.motor_on = on;
+
+        if self.rumble_events.len() == MAX_RUMBLE_EVENTS {
+            self.rumble_events.pop_front();
+        }
+
+        self.rumble_events.push_back(if on {
+            RumbleEvent::MotorOn
+        } else {
+            RumbleEvent::MotorOff
+        });
+    }
+
+    fn ram_offset(&self, ram: &[u8], address: u16) -> usize {
+        let bank = usize::from(self.ram_bank);
+        (bank * RAM_BANK_SIZE + (address as usize - 0xA000)) % ram.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rumble_events_are_bounded() {
+        let mut mbc = Mbc5::new(true);
+
+        for _ in 0..1000 {
+            mbc.write_rom(0x4000, RUMBLE_BIT);
+            mbc.write_rom(0x4000, 0x00);
+        }
+
+        let events = mbc.take_rumble_events();
+        assert_eq!(events.len(), MAX_RUMBLE_EVENTS);
+        assert_eq!(events.last(), Some(&RumbleEvent::MotorOff));
+        assert!(mbc.take_rumble_events().is_empty());
+    }
+
+    #[test]
+    fn nine_bit_rom_bank() {
+        let mut rom = vec![0; 512 * ROM_BANK_SIZE];
+        rom[0x1FF * ROM_BANK_SIZE] = 0x42;
+        let mut mbc = Mbc5::new(false);
+
+        mbc.write_rom(0x2000, 0xFF);
+        mbc.write_rom(0x3000, 0x01);
+
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x42);
+    }
+
+    #[test]
+    fn rom_bank_0_can_be_selected() {
+        let mut rom = vec![0; 4 * ROM_BANK_SIZE];
+        rom[0x0000] = 0x42;
+        let mut mbc = Mbc5::new(false);
+
+        mbc.write_rom(0x2000, 0);
+
+        assert_eq!(mbc.read_rom(&rom, 0x4000), 0x42);
+    }
+
+    #[test]
+    fn switch_ram_bank() {
+        let mut ram = vec![0; 16 * RAM_BANK_SIZE];
+        let mut mbc = Mbc5::new(false);
+        mbc.write_rom(0x0000, 0x0A);
+        mbc.write_rom(0x4000, 0x0F);
+
+        mbc.write_ram(&mut ram, 0xA000, 0x42);
+
+        assert_eq!(ram[15 * RAM_BANK_SIZE], 0x42);
+    }
+
+    #[test]
+    fn rumble_events() {
+        let mut mbc = Mbc5::new(true);
+
+        mbc.write_rom(0x4000, RUMBLE_BIT);
+        mbc.write_rom(0x4000, RUMBLE_BIT | 0x01);
+        mbc.write_rom(0x4000, 0x01);
+
+        assert_eq!(
+            mbc.take_rumble_events(),
+            vec![RumbleEvent::MotorOn, RumbleEvent::MotorOff]
+        );
+        assert_eq!(mbc.take_rumble_events(), vec![]);
+        assert_eq!(mbc.ram_bank, 0x01);
+    }
+
+    #[test]
+    fn no_rumble_events_without_motor() {
+        let mut mbc = Mbc5::new(false);
+
+        mbc.write_rom(0x4000, RUMBLE_BIT);
+
+        assert_eq!(mbc.take_rumble_events(), vec![]);
+        assert_eq!(mbc.ram_bank, RUMBLE_BIT);
+    }
+}
@@ -0,0 +1,413 @@
+use std::error::Error;
+use std::fmt;
+
+pub use crate::cartridge::header::{CartridgeHeader, CartridgeType, CgbSupport, Licensee, Mbc};
+pub(crate) use crate::cartridge::header::{
+    CGB_FLAG_ADDR, HEADER_CHECKSUM_ADDR, TITLE_BEGIN, TITLE_END,
+};
+use crate::cartridge::mbc1::Mbc1;
+use crate::cartridge::mbc3::Mbc3;
+use crate::cartridge::mbc5::Mbc5;
+pub use crate::cartridge::mbc5::RumbleEvent;
+pub use crate::cartridge::rtc::Rtc;
+use crate::cartridge::rtc::{SAVE_TRAILER_SIZE, SHORT_SAVE_TRAILER_SIZE};
+
+mod header;
+mod mbc1;
+mod mbc3;
+mod mbc5;
+pub mod rtc;
+
+pub const ROM_BANK_SIZE: usize = 16 * 1024;
+pub const RAM_BANK_SIZE: usize = 8 * 1024;
+
+#[derive(Debug, PartialEq)]
+pub enum CartridgeError {
+    // The image is shorter than what the header says, or too short to even
+    // contain the header.
+    Truncated { expected: usize, actual: usize },
+    HeaderChecksumMismatch { expected: u8, actual: u8 },
+    GlobalChecksumMismatch { expected: u16, actual: u16 },
+    UnknownCartridgeType(u8),
+    InvalidRomSize(u8),
+    InvalidRamSize(u8),
+    UnsupportedMbc(Mbc),
+    InvalidSaveSize { expected: usize, actual: usize },
+}
+
+impl fmt::Display for CartridgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CartridgeError::Truncated { expected, actual } => write!(
+                f,
+                "truncated ROM: expected at least {} bytes, got {}",
+                expected, actual
+            ),
+            CartridgeError::HeaderChecksumMismatch { expected, actual } => write!(
+                f,
+                "wrong header checksum: expected 0x{:02X}, got 0x{:02X}",
+                expected, actual
+            ),
+            CartridgeError::GlobalChecksumMismatch { expected, actual } => write!(
+                f,
+                "wrong global checksum: expected 0x{:04X}, got 0x{:04X}",
+                expected, actual
+            ),
+            CartridgeError::UnknownCartridgeType(code) => {
+                write!(f, "unknown cartridge type: 0x{:02X}", code)
+            }
+            CartridgeError::InvalidRomSize(code) => write!(f, "invalid ROM size: 0x{:02X}", code),
+            CartridgeError::InvalidRamSize(code) => write!(f, "invalid RAM size: 0x{:02X}", code),
+            CartridgeError::UnsupportedMbc(mbc) => write!(f, "unsupported MBC: {:?}", mbc),
+            CartridgeError::InvalidSaveSize { expected, actual } => write!(
+                f,
+                "invalid save file: expected {} bytes, got {}",
+                expected, actual
+            ),
+        }
+    }
+}
+
+impl Error for CartridgeError {}
+
+// The memory bank controller maps the ROM and RAM banks of the cartridge in
+// the address space. Writes to the ROM area go to its registers.
+#[derive(Clone)]
+enum BankController {
+    NoMbc,
+    Mbc1(Mbc1),
+    Mbc3(Mbc3),
+    Mbc5(Mbc5),
+}
+
+#[derive(Clone)]
+pub struct Cartridge {
+    header: CartridgeHeader,
+    rom: Vec<u8>,
+    ram: Vec<u8>,
+    controller: BankController,
+
+    // Set when the RAM is written, so that it is only saved when needed.
+    ram_modified: bool,
+}
+
+impl Cartridge {
+    /// Creates a cartridge from a ROM image, checking that its header is valid
+    /// and that the image is as big as the header says.
+    pub fn new(rom: Vec<u8>) -> Result<Cartridge, CartridgeError> {
+        let header = CartridgeHeader::parse(&rom)?;
+
+        if rom.len() < header.rom_size {
+            return Err(CartridgeError::Truncated {
+                expected: header.rom_size,
+                actual: rom.len(),
+            });
+        }
+
+        let controller = match header.cartridge_type.mbc {
+            Mbc::NoMbc => BankController::NoMbc,
+            Mbc::Mbc1 => BankController::Mbc1(Mbc1::new(&rom)),
+            Mbc::Mbc3 => BankController::Mbc3(Mbc3::new(header.cartridge_type.timer)),
+            Mbc::Mbc5 => BankController::Mbc5(Mbc5::new(header.cartridge_type.rumble)),
+            mbc => return Err(CartridgeError::UnsupportedMbc(mbc)),
+        };
+
+        Ok(Cartridge {
+            ram: vec![0; header.ram_size],
+            header,
+            rom,
+            controller,
+            ram_modified: false,
+        })
+    }
+
+    /// Reads from the ROM area, 0x0000-0x7FFF.
+    pub fn read_rom(&self, address: u16) -> u8 {
+        match &self.controller {
+            BankController::NoMbc => self.rom[address as usize],
+            BankController::Mbc1(mbc) => mbc.read_rom(&self.rom, address),
+            BankController::Mbc3(mbc) => mbc.read_rom(&self.rom, address),
+            BankController::Mbc5(mbc) => mbc.read_rom(&self.rom, address),
+        }
+    }
+
+    /// Writes to the ROM area, 0x0000-0x7FFF. The ROM is not modified, these
+    /// writes set the registers of the bank controller.
+    pub fn write_rom(&mut self, address: u16, value: u8) {
+        match &mut self.controller {
+            BankController::NoMbc => (),
+            BankController::Mbc1(mbc) => mbc.write_rom(address, value),
+            BankController::Mbc3(mbc) => mbc.write_rom(address, value),
+            BankController::Mbc5(mbc) => mbc.write_rom(address, value),
+        }
+    }
+
+    /// Reads from the external RAM area, 0xA000-0xBFFF.
+    pub fn read_ram(&self, address: u16) -> u8 {
+        match &self.controller {
+            BankController::NoMbc => match self.ram.get(address as usize - 0xA000) {
+                Some(&value) => value,
+                None => 0xFF,
+            },
+            BankController::Mbc1(mbc) => mbc.read_ram(&self.ram, address),
+            BankController::Mbc3(mbc) => mbc.read_ram(&self.ram, address),
+            BankController::Mbc5(mbc) => mbc.read_ram(&self.ram, address),
+        }
+    }
+
+    /// Writes to the external RAM area, 0xA000-0xBFFF.
+    pub fn write_ram(&mut self, address: u16, value: u8) {
+        let stored = match &mut self.controller {
+            BankController::NoMbc => match self.ram.get_mut(address as usize - 0xA000) {
+                Some(byte) => {
+                    *byte = value;
+                    true
+                }
+                None => false,
+            },
+            BankController::Mbc1(mbc) => mbc.write_ram(&mut self.ram, address, value),
+            BankController::Mbc3(mbc) => mbc.write_ram(&mut self.ram, address, value),
+            BankController::Mbc5(mbc) => mbc.write_ram(&mut self.ram, address, value),
+        };
+
+        // Writes to disabled RAM do not need to be saved.
+        if stored {
+            self.ram_modified = true;
+        }
+    }
+
+    /// Advances the clock of the cartridge, if it has one, the given number
+    /// of M-cycles.
+    pub fn tick(&mut self, m_cycles: u8) {
+        if let BankController::Mbc3(mbc) = &mut self.controller {
+            mbc.tick(m_cycles);
+        }
+    }
+
+    pub fn rtc(&self) -> Option<&Rtc> {
+        match &self.controller {
+            BankController::Mbc3(mbc) => mbc.rtc(),
+            _ => None,
+        }
+    }
+
+    /// Gives access to the real time clock so that it can be set or moved
+    /// forward, for example from tests.
+    pub fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        match &mut self.controller {
+            BankController::Mbc3(mbc) => mbc.rtc_mut(),
+            _ => None,
+        }
+    }
+
+    /// Returns the changes of the rumble motor since the last call. Only
+    /// MBC5 cartridges with rumble produce them.
+    pub fn take_rumble_events(&mut self) -> Vec<RumbleEvent> {
+        match &mut self.controller {
+            BankController::Mbc5(mbc) => mbc.take_rumble_events(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether the RAM, and the RTC if there is one, are kept by a battery
+    /// and should be saved.
+    pub fn has_battery(&self) -> bool {
+        self.header.cartridge_type.battery
+    }
+
+    /// Whether the RAM may have changed since it was last saved or loaded.
+    pub fn ram_modified(&self) -> bool {
+        self.ram_modified
+    }
+
+    /// Returns the contents of a save file: the raw RAM followed, for MBC3
+    /// cartridges with a timer, by the RTC trailer with the given UNIX
+    /// timestamp.
+    pub fn save_data(&mut self, timestamp: u64) -> Vec<u8> {
+        self.ram_modified = false;
+
+        let mut data = self.ram.clone();
+
+        if let Some(rtc) = self.rtc() {
+            data.extend(rtc.save_trailer(timestamp));
+        }
+
+        data
+    }
+
+    /// Loads the contents of a save file. Returns the UNIX timestamp of the
+    /// RTC trailer, if the file has one.
+    pub fn load_save_data(&mut self, data: &[u8]) -> Result<Option<u64>, CartridgeError> {
+        let ram_size = self.ram.len();
+        let trailer_size = data.len().saturating_sub(ram_size);
+
+        let valid_trailer = match self.rtc() {
+            Some(_) => [0, SAVE_TRAILER_SIZE, SHORT_SAVE_TRAILER_SIZE].contains(&trailer_size),
+            None => trailer_size == 0,
+        };
+
+        if data.len() < ram_size || !valid_trailer {
+            return Err(CartridgeError::InvalidSaveSize {
+                expected: ram_size + self.rtc().map_or(0, |_| SAVE_TRAILER_SIZE),
+                actual: data.len(),
+            });
+        }
+
+        self.ram.copy_from_slice(&data[..ram_size]);
+        self.ram_modified = false;
+
+        match self.rtc_mut() {
+            Some(rtc) if trailer_size > 0 => Ok(Some(rtc.load_trailer(&data[ram_size..]))),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    pub fn verify_global_checksum(&self) -> Result<(), CartridgeError> {
+        self.header.verify_global_checksum(&self.rom)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    pub use crate::cartridge::header::tests::rom_with_header;
+
+    #[test]
+    fn new_cartridge() {
+        let rom = rom_with_header("TETRIS", 0x00, 0x00, 0x00);
+
+        let cartridge = Cartridge::new(rom).unwrap();
+
+        assert_eq!(cartridge.header().title, "TETRIS");
+        assert_eq!(cartridge.verify_global_checksum(), Ok(()));
+    }
+
+    #[test]
+    fn new_cartridge_with_unsupported_mbc() {
+        let rom = rom_with_header("TETRIS", 0x05, 0x00, 0x00);
+
+        match Cartridge::new(rom) {
+            Err(CartridgeError::UnsupportedMbc(Mbc::Mbc2)) => (),
+            _ => panic!("MBC2 should not be supported"),
+        }
+    }
+
+    #[test]
+    fn rumble_cartridge_reports_motor_events() {
+        // MBC5+RUMBLE
+        let rom = rom_with_header("RUMBLE", 0x1C, 0x01, 0x00);
+        let mut cartridge = Cartridge::new(rom).unwrap();
+
+        cartridge.write_rom(0x4000, 0x08);
+        cartridge.write_rom(0x4000, 0x00);
+
+        assert_eq!(
+            cartridge.take_rumble_events(),
+            vec![RumbleEvent::MotorOn, RumbleEvent::MotorOff]
+        );
+    }
+
+    #[test]
+    fn save_data_is_the_raw_ram() {
+        // MBC1+RAM+BATTERY
+        let rom = rom_with_header("ZELDA", 0x03, 0x01, 0x02);
+        let mut cartridge = Cartridge::new(rom).unwrap();
+        cartridge.write_rom(0x0000, 0x0A);
+        cartridge.write_ram(0xA001, 0x42);
+
+        assert!(cartridge.ram_modified());
+
+        let data = cartridge.save_data(0);
+
+        assert_eq!(data.len(), RAM_BANK_SIZE);
+        assert_eq!(data[1], 0x42);
+        assert!(!cartridge.ram_modified());
+    }
+
+    #[test]
+    fn writes_to_disabled_ram_do_not_modify_it() {
+        // MBC1+RAM+BATTERY
+        let rom = rom_with_header("ZELDA", 0x03, 0x01, 0x02);
+        let mut cartridge = Cartridge::new(rom).unwrap();
+
+        cartridge.write_ram(0xA001, 0x42);
+
+        assert!(!cartridge.ram_modified());
+    }
+
+    #[test]
+    fn save_data_with_rtc() {
+        // MBC3+TIMER+RAM+BATTERY
+        let rom = rom_with_header("POKEMON", 0x10, 0x01, 0x02);
+        let mut cartridge = Cartridge::new(rom.clone()).unwrap();
+        cartridge.rtc_mut().unwrap().fast_forward(90);
+
+        let data = cartridge.save_data(1234);
+        let mut loaded = Cartridge::new(rom).unwrap();
+
+        assert_eq!(data.len(), RAM_BANK_SIZE + SAVE_TRAILER_SIZE);
+        assert_eq!(loaded.load_save_data(&data), Ok(Some(1234)));
+        assert_eq!(loaded.rtc(), cartridge.rtc());
+    }
+
+    #[test]
+    fn load_save_data_without_rtc_trailer() {
+        // MBC3+TIMER+RAM+BATTERY
+        let rom = rom_with_header("POKEMON", 0x10, 0x01, 0x02);
+        let mut cartridge = Cartridge::new(rom).unwrap();
+        let mut data = vec![0; RAM_BANK_SIZE];
+        data[0] = 0x42;
+
+        assert_eq!(cartridge.load_save_data(&data), Ok(None));
+
+        cartridge.write_rom(0x0000, 0x0A);
+        assert_eq!(cartridge.read_ram(0xA000), 0x42);
+    }
+
+    #[test]
+    fn load_save_data_with_wrong_size() {
+        // MBC1+RAM+BATTERY
+        let rom = rom_with_header("ZELDA", 0x03, 0x01, 0x02);
+        let mut cartridge = Cartridge::new(rom).unwrap();
+
+        assert_eq!(
+            cartridge.load_save_data(&[0; 100]),
+            Err(CartridgeError::InvalidSaveSize {
+                expected: RAM_BANK_SIZE,
+                actual: 100
+            })
+        );
+    }
+
+    #[test]
+    fn write_rom_does_not_modify_it() {
+        let rom = rom_with_header("TETRIS", 0x01, 0x01, 0x00);
+        let mut cartridge = Cartridge::new(rom).unwrap();
+
+        cartridge.write_rom(0x2000, 0x42);
+
+        assert_eq!(cartridge.read_rom(0x2000), 0);
+    }
+
+    #[test]
+    fn new_cartridge_smaller_than_header_says() {
+        let mut rom = rom_with_header("TETRIS", 0x01, 0x01, 0x00);
+        rom.truncate(0x4000);
+
+        match Cartridge::new(rom) {
+            Err(CartridgeError::Truncated { expected, actual }) => {
+                assert_eq!(expected, 0x10000);
+                assert_eq!(actual, 0x4000);
+            }
+            _ => panic!("The cartridge should be truncated"),
+        }
+    }
+}
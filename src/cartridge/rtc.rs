@@ -0,0 +1,326 @@
+// The RTC runs from its own 32768 Hz crystal, but we advance it with the
+// emulated time so that it is deterministic: one second is 2^20 M-cycles.
+const M_CYCLES_PER_SECOND: u32 = 1_048_576;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+// Save files store the RTC after the RAM as 5 current registers and 5 latched
+// registers, 4 little endian bytes each, followed by a 64-bit UNIX timestamp.
+// Some emulators write a 32-bit timestamp instead.
+pub const SAVE_TRAILER_SIZE: usize = 48;
+pub const SHORT_SAVE_TRAILER_SIZE: usize = 44;
+
+pub const SECONDS_REGISTER: u8 = 0x08;
+pub const MINUTES_REGISTER: u8 = 0x09;
+pub const HOURS_REGISTER: u8 = 0x0A;
+pub const DAYS_LOW_REGISTER: u8 = 0x0B;
+pub const DAYS_HIGH_REGISTER: u8 = 0x0C;
+
+// Bits of the days high register.
+const DAY_BIT_8: u8 = 0b0000_0001;
+const HALT_BIT: u8 = 0b0100_0000;
+const DAY_CARRY_BIT: u8 = 0b1000_0000;
+
+/// Real time clock of the MBC3. The registers are 0x08 (seconds), 0x09
+/// (minutes), 0x0A (hours), 0x0B (lower 8 bits of the day counter) and 0x0C
+/// (bit 0: bit 8 of the day counter, bit 6: halt, bit 7: day counter carry).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Rtc {
+    seconds: u8,
+    minutes: u8,
+    hours: u8,
+    days: u16,
+    halted: bool,
+    day_carry: bool,
+
+    // M-cycles since the last second.
+    cycles: u32,
+
+    // Reads return the values copied here by the last latch, indexed by
+    // register number - 0x08.
+    latched: [u8; 5],
+}
+
+impl Default for Rtc {
+    fn default() -> Rtc {
+        Rtc::new()
+    }
+}
+
+impl Rtc {
+    pub fn new() -> Rtc {
+        Rtc {
+            seconds: 0,
+            minutes: 0,
+            hours: 0,
+            days: 0,
+            halted: false,
+            day_carry: false,
+            cycles: 0,
+            latched: [0; 5],
+        }
+    }
+
+    pub fn tick(&mut self, m_cycles: u8) {
+        if self.halted {
+            return;
+        }
+
+        self.cycles += u32::from(m_cycles);
+
+        if self.cycles >= M_CYCLES_PER_SECOND {
+            self.cycles -= M_CYCLES_PER_SECOND;
+            self.advance_one_second();
+        }
+    }
+
+    /// Moves the clock forward the given number of seconds, as if that much
+    /// emulated time had passed. It does nothing if the clock is halted.
+    pub fn fast_forward(&mut self, seconds: u64) {
+        if self.halted {
+            return;
+        }
+
+        let mut seconds = seconds;
+
+        while seconds > 0 {
+            // A whole day only changes the day counter, as long as the other
+            // registers contain valid values.
+            if seconds >= SECONDS_PER_DAY && self.has_valid_time() {
+                self.advance_one_day();
+                seconds -= SECONDS_PER_DAY;
+            } else {
+                self.advance_one_second();
+                seconds -= 1;
+            }
+        }
+    }
+
+    /// Copies the current time to the registers that reads return.
+    pub fn latch(&mut self) {
+        for register in SECONDS_REGISTER..=DAYS_HIGH_REGISTER {
+            self.latched[usize::from(register - SECONDS_REGISTER)] = self.current(register);
+        }
+    }
+
+    /// Returns the latched value of the given register.
+    pub fn read(&self, register: u8) -> u8 {
+        self.latched[usize::from(register - SECONDS_REGISTER)]
+    }
+
+    /// Writes the given register of the running clock.
+    pub fn write(&mut self, register: u8, value: u8) {
+        match register {
+            SECONDS_REGISTER => {
+                self.seconds = value & 0x3F;
+                self.cycles = 0;
+            }
+            MINUTES_REGISTER => self.minutes = value & 0x3F,
+            HOURS_REGISTER => self.hours = value & 0x1F,
+            DAYS_LOW_REGISTER => self.days = (self.days & 0x100) | u16::from(value),
+            DAYS_HIGH_REGISTER => {
+                self.days = (self.days & 0xFF) | (u16::from(value & DAY_BIT_8) << 8);
+                self.halted = value & HALT_BIT != 0;
+                self.day_carry = value & DAY_CARRY_BIT != 0;
+            }
+            _ => panic!("Invalid RTC register: {:X}", register),
+        }
+    }
+
+    /// Returns the value of the given register in the running clock.
+    pub fn current(&self, register: u8) -> u8 {
+        match register {
+            SECONDS_REGISTER => self.seconds,
+            MINUTES_REGISTER => self.minutes,
+            HOURS_REGISTER => self.hours,
+            DAYS_LOW_REGISTER => (self.days & 0xFF) as u8,
+            DAYS_HIGH_REGISTER => {
+                let mut value = (self.days >> 8) as u8;
+
+                if self.halted {
+                    value |= HALT_BIT;
+                }
+
+                if self.day_carry {
+                    value |= DAY_CARRY_BIT;
+                }
+
+                value
+            }
+            _ => panic!("Invalid RTC register: {:X}", register),
+        }
+    }
+
+    /// Encodes the clock in the trailer format appended to save files.
+    pub fn save_trailer(&self, timestamp: u64) -> Vec<u8> {
+        let mut trailer = Vec::with_capacity(SAVE_TRAILER_SIZE);
+
+        for register in SECONDS_REGISTER..=DAYS_HIGH_REGISTER {
+            trailer.extend_from_slice(&u32::from(self.current(register)).to_le_bytes());
+        }
+
+        for &value in self.latched.iter() {
+            trailer.extend_from_slice(&u32::from(value).to_le_bytes());
+        }
+
+        trailer.extend_from_slice(&timestamp.to_le_bytes());
+
+        trailer
+    }
+
+    /// Restores the clock from a save file trailer of 48 or 44 bytes and
+    /// returns the timestamp of when it was saved.
+    pub fn load_trailer(&mut self, trailer: &[u8]) -> u64 {
+        let field = |i: usize| trailer[i * 4];
+
+        for register in SECONDS_REGISTER..=DAYS_HIGH_REGISTER {
+            let i = usize::from(register - SECONDS_REGISTER);
+            self.write(register, field(i));
+            self.latched[i] = field(i + 5);
+        }
+
+        let mut timestamp = [0; 8];
+        let timestamp_bytes = &trailer[40..];
+        timestamp[..timestamp_bytes.len()].copy_from_slice(timestamp_bytes);
+
+        u64::from_le_bytes(timestamp)
+    }
+
+    fn has_valid_time(&self) -> bool {
+        self.seconds < 60 && self.minutes < 60 && self.hours < 24
+    }
+
+    // Registers with out of range values keep counting until they overflow
+    // their bits, without increasing the next one.
+    fn advance_one_second(&mut self) {
+        self.seconds = (self.seconds + 1) & 0x3F;
+        if self.seconds != 60 {
+            return;
+        }
+        self.seconds = 0;
+
+        self.minutes = (self.minutes + 1) & 0x3F;
+        if self.minutes != 60 {
+            return;
+        }
+        self.minutes = 0;
+
+        self.hours = (self.hours + 1) & 0x1F;
+        if self.hours != 24 {
+            return;
+        }
+        self.hours = 0;
+
+        self.advance_one_day();
+    }
+
+    // The day counter has 9 bits. When it overflows, the carry bit is set and
+    // stays set until the game clears it.
+    fn advance_one_day(&mut self) {
+        self.days += 1;
+
+        if self.days > 0x1FF {
+            self.days = 0;
+            self.day_carry = true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advances_with_emulated_time() {
+        let mut rtc = Rtc::new();
+
+        for _ in 0..(M_CYCLES_PER_SECOND / 4) {
+            rtc.tick(4);
+        }
+
+        assert_eq!(rtc.current(SECONDS_REGISTER), 1);
+    }
+
+    #[test]
+    fn halted_rtc_does_not_advance() {
+        let mut rtc = Rtc::new();
+        rtc.write(DAYS_HIGH_REGISTER, HALT_BIT);
+
+        rtc.fast_forward(100);
+
+        assert_eq!(rtc.current(SECONDS_REGISTER), 0);
+    }
+
+    #[test]
+    fn fast_forward_carries_between_registers() {
+        let mut rtc = Rtc::new();
+
+        rtc.fast_forward(3 * SECONDS_PER_DAY + 2 * 3600 + 5 * 60 + 7);
+
+        assert_eq!(rtc.current(SECONDS_REGISTER), 7);
+        assert_eq!(rtc.current(MINUTES_REGISTER), 5);
+        assert_eq!(rtc.current(HOURS_REGISTER), 2);
+        assert_eq!(rtc.current(DAYS_LOW_REGISTER), 3);
+    }
+
+    #[test]
+    fn day_counter_overflow_sets_carry() {
+        let mut rtc = Rtc::new();
+        rtc.write(DAYS_LOW_REGISTER, 0xFF);
+        rtc.write(DAYS_HIGH_REGISTER, DAY_BIT_8);
+
+        rtc.fast_forward(SECONDS_PER_DAY);
+
+        assert_eq!(rtc.current(DAYS_LOW_REGISTER), 0);
+        assert_eq!(rtc.current(DAYS_HIGH_REGISTER), DAY_CARRY_BIT);
+    }
+
+    #[test]
+    fn invalid_seconds_wrap_without_carry() {
+        let mut rtc = Rtc::new();
+        rtc.write(SECONDS_REGISTER, 62);
+
+        rtc.fast_forward(2);
+
+        assert_eq!(rtc.current(SECONDS_REGISTER), 0);
+        assert_eq!(rtc.current(MINUTES_REGISTER), 0);
+    }
+
+    #[test]
+    fn save_trailer_round_trip() {
+        let mut rtc = Rtc::new();
+        rtc.fast_forward(300 * SECONDS_PER_DAY + 3661);
+        rtc.latch();
+        rtc.fast_forward(5);
+
+        let trailer = rtc.save_trailer(1_600_000_000);
+        let mut loaded = Rtc::new();
+        let timestamp = loaded.load_trailer(&trailer);
+
+        assert_eq!(trailer.len(), SAVE_TRAILER_SIZE);
+        assert_eq!(&trailer[16..20], &[1, 0, 0, 0]);
+        assert_eq!(timestamp, 1_600_000_000);
+        assert_eq!(loaded, rtc);
+    }
+
+    #[test]
+    fn load_short_trailer() {
+        let mut trailer = Rtc::new().save_trailer(0x1234_5678);
+        trailer.truncate(SHORT_SAVE_TRAILER_SIZE);
+
+        assert_eq!(Rtc::new().load_trailer(&trailer), 0x1234_5678);
+    }
+
+    #[test]
+    fn reads_return_latched_values() {
+        let mut rtc = Rtc::new();
+        rtc.fast_forward(10);
+
+        assert_eq!(rtc.read(SECONDS_REGISTER), 0);
+
+        rtc.latch();
+        rtc.fast_forward(10);
+
+        assert_eq!(rtc.read(SECONDS_REGISTER), 10);
+    }
+}
@@ -1,48 +1,60 @@
 use crate::cpu::registers::*;
 use crate::cpu::CPU;
 
-pub fn bit(cpu: &mut CPU, bit: u8, register: Register8bits) {
+pub fn bit(cpu: &mut CPU, bit: u8, register: Register8bits) -> u8 {
     let bit_value = cpu.registers.read(&register) & (0x1 << bit);
-    write_flags_for_bit(cpu, bit_value)
+    write_flags_for_bit(cpu, bit_value);
+
+    2
 }
 
-pub fn bit_hl(cpu: &mut CPU, bit: u8) {
+pub fn bit_hl(cpu: &mut CPU, bit: u8) -> u8 {
     let bit_value = cpu.value_in_addr(&Register16bits::HL) & (0x1 << bit);
-    write_flags_for_bit(cpu, bit_value)
+    write_flags_for_bit(cpu, bit_value);
+
+    3
 }
 
-pub fn res(cpu: &mut CPU, bit: u8, register: Register8bits) {
+pub fn res(cpu: &mut CPU, bit: u8, register: Register8bits) -> u8 {
     let mask = (0x1 << bit) ^ (0xFF);
     let initial_val = cpu.registers.read(&register);
     let new_val = initial_val & mask;
 
     cpu.registers.write(&register, new_val);
+
+    2
 }
 
-pub fn res_hl(cpu: &mut CPU, bit: u8) {
+pub fn res_hl(cpu: &mut CPU, bit: u8) -> u8 {
     let mem_address = cpu.registers.read_16b(&Register16bits::HL);
     let initial_val = cpu.memory.read_byte(mem_address);
     let mask = (0x1 << bit) ^ (0xFF);
     let new_val = initial_val & mask;
 
     cpu.memory.write_byte(mem_address, new_val);
+
+    4
 }
 
-pub fn set(cpu: &mut CPU, bit: u8, register: Register8bits) {
+pub fn set(cpu: &mut CPU, bit: u8, register: Register8bits) -> u8 {
     let mask = 0x1 << bit;
     let initial_val = cpu.registers.read(&register);
     let new_val = initial_val | mask;
 
     cpu.registers.write(&register, new_val);
+
+    2
 }
 
-pub fn set_hl(cpu: &mut CPU, bit: u8) {
+pub fn set_hl(cpu: &mut CPU, bit: u8) -> u8 {
     let mem_address = cpu.registers.read_16b(&Register16bits::HL);
     let initial_val = cpu.memory.read_byte(mem_address);
     let mask = 0x1 << bit;
     let new_val = initial_val | mask;
 
     cpu.memory.write_byte(mem_address, new_val);
+
+    4
 }
 
 fn write_flags_for_bit(cpu: &mut CPU, bit_value: u8) {
@@ -1,39 +1,75 @@
 use crate::cpu::CPU;
+use crate::timer::DIV_ADDR;
 
-pub fn ccf(cpu: &mut CPU) {
+pub fn ccf(cpu: &mut CPU) -> u8 {
     cpu.registers.write_s_flag(false);
     cpu.registers.write_hc_flag(false);
     cpu.registers.write_c_flag(!cpu.registers.read_c_flag());
+
+    1
 }
 
-pub fn scf(cpu: &mut CPU) {
+pub fn scf(cpu: &mut CPU) -> u8 {
     cpu.registers.write_s_flag(false);
     cpu.registers.write_hc_flag(false);
     cpu.registers.write_c_flag(true);
+
+    1
 }
 
-pub fn nop() {}
+pub fn nop() -> u8 {
+    1
+}
+
+pub fn halt(cpu: &mut CPU) -> u8 {
+    let interrupt_pending = cpu.memory.interrupts.any_pending();
+
+    if cpu.interrupts_enable_scheduled && interrupt_pending {
+        // When HALT comes right after EI and there is a pending interrupt, the
+        // interrupt is attended, and it returns to the HALT, which runs again.
+        cpu.registers.decrease_pc(1);
+    } else if !cpu.interrupts_enabled && interrupt_pending {
+        cpu.halt_bug = true;
+    } else {
+        cpu.halted = true;
+    }
 
-pub fn halt() {
-    // TODO
+    1
 }
 
-pub fn stop() {
-    // TODO
+pub fn stop(cpu: &mut CPU) -> u8 {
+    // STOP is followed by a byte that is ignored.
+    cpu.fetch_byte();
+
+    cpu.memory.write_byte(DIV_ADDR, 0);
+
+    // In CGB mode, STOP switches the speed if it was prepared with KEY1. The
+    // CPU pauses for a while during the switch, which is not emulated.
+    if !cpu.memory.switch_speed() {
+        cpu.stopped = true;
+    }
+
+    1
 }
 
-pub fn di(cpu: &mut CPU) {
+pub fn di(cpu: &mut CPU) -> u8 {
     cpu.interrupts_enabled = false;
+    cpu.interrupts_enable_scheduled = false;
+
+    1
 }
 
-pub fn ei(cpu: &mut CPU) {
-    cpu.interrupts_enabled = true;
+pub fn ei(cpu: &mut CPU) -> u8 {
+    cpu.interrupts_enable_scheduled = true;
+
+    1
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::memory::Memory;
+    use crate::model::Model;
 
     #[test]
     fn ccf_with_carry_set() {
@@ -76,4 +112,145 @@ mod tests {
 
         assert_eq!(cpu.registers.read_c_flag(), true);
     }
+
+    #[test]
+    fn halt_with_ime_enabled() {
+        let mut mem: Memory = Memory::new();
+        mem.interrupts.enable_or_disable_interrupts(0b0000_0001);
+        mem.interrupts.add_interrupts(0b0000_0001);
+        let mut cpu: CPU = CPU::new(&mut mem);
+
+        halt(&mut cpu);
+
+        assert!(cpu.halted);
+        assert!(!cpu.halt_bug);
+    }
+
+    #[test]
+    fn halt_with_ime_disabled_and_no_pending_interrupts() {
+        let mut mem: Memory = Memory::new();
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.interrupts_enabled = false;
+
+        halt(&mut cpu);
+
+        assert!(cpu.halted);
+        assert!(!cpu.halt_bug);
+    }
+
+    #[test]
+    fn halt_with_ime_disabled_and_pending_interrupts() {
+        let mut mem: Memory = Memory::new();
+        mem.interrupts.enable_or_disable_interrupts(0b0000_0001);
+        mem.interrupts.add_interrupts(0b0000_0001);
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.interrupts_enabled = false;
+
+        halt(&mut cpu);
+
+        assert!(!cpu.halted);
+        assert!(cpu.halt_bug);
+    }
+
+    #[test]
+    fn stop_resets_div() {
+        let mut mem: Memory = Memory::new();
+        mem.tick(200);
+        let mut cpu: CPU = CPU::new(&mut mem);
+
+        stop(&mut cpu);
+
+        assert!(cpu.stopped);
+        assert_eq!(cpu.memory.read_byte(DIV_ADDR), 0);
+    }
+
+    #[test]
+    fn stop_switches_speed_when_prepared() {
+        let mut mem: Memory = Memory::new();
+        mem.write_byte(0x0143, 0x80);
+        mem.set_model(Model::Cgb);
+        mem.write_byte(0xFF4D, 0x01);
+        let mut cpu: CPU = CPU::new(&mut mem);
+
+        stop(&mut cpu);
+
+        assert!(!cpu.stopped);
+        assert!(cpu.memory.double_speed());
+        assert_eq!(cpu.memory.read_byte(0xFF4D), 0xFE);
+    }
+
+    #[test]
+    fn ei_does_not_enable_interrupts_immediately() {
+        let mut mem: Memory = Memory::new();
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.interrupts_enabled = false;
+
+        ei(&mut cpu);
+
+        assert!(!cpu.interrupts_enabled);
+    }
+
+    #[test]
+    fn ei_enables_interrupts_after_next_instruction() {
+        let mut mem: Memory = Memory::new();
+        let initial_pc = 0x200;
+        mem.write_byte(initial_pc, 0xFB); // EI
+        mem.write_byte(initial_pc + 1, 0x00); // NOP
+        mem.interrupts.enable_or_disable_interrupts(0b0000_0001);
+        mem.interrupts.add_interrupts(0b0000_0001);
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.registers.write_pc(initial_pc);
+        cpu.registers.write_sp(0x8080);
+        cpu.interrupts_enabled = false;
+
+        cpu.run_next_instruction();
+        assert!(!cpu.interrupts_enabled);
+
+        cpu.run_next_instruction();
+        assert!(cpu.interrupts_enabled);
+        assert_eq!(cpu.registers.pc(), initial_pc + 2);
+
+        cpu.run_next_instruction();
+        assert_eq!(cpu.registers.pc(), 0x40);
+        assert_eq!(cpu.memory.read_word(cpu.registers.sp()), initial_pc + 2);
+    }
+
+    #[test]
+    fn di_cancels_scheduled_ei() {
+        let mut mem: Memory = Memory::new();
+        let initial_pc = 0x200;
+        mem.write_byte(initial_pc, 0xFB); // EI
+        mem.write_byte(initial_pc + 1, 0xF3); // DI
+        mem.write_byte(initial_pc + 2, 0x00); // NOP
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.registers.write_pc(initial_pc);
+        cpu.interrupts_enabled = false;
+
+        cpu.run_next_instruction();
+        cpu.run_next_instruction();
+        cpu.run_next_instruction();
+
+        assert!(!cpu.interrupts_enabled);
+    }
+
+    #[test]
+    fn ei_followed_by_halt_with_pending_interrupt() {
+        let mut mem: Memory = Memory::new();
+        let initial_pc = 0x200;
+        mem.write_byte(initial_pc, 0xFB); // EI
+        mem.write_byte(initial_pc + 1, 0x76); // HALT
+        mem.interrupts.enable_or_disable_interrupts(0b0000_0001);
+        mem.interrupts.add_interrupts(0b0000_0001);
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.registers.write_pc(initial_pc);
+        cpu.registers.write_sp(0x8080);
+        cpu.interrupts_enabled = false;
+
+        cpu.run_next_instruction();
+        cpu.run_next_instruction();
+        cpu.run_next_instruction();
+
+        assert_eq!(cpu.registers.pc(), 0x40);
+        assert_eq!(cpu.memory.read_word(cpu.registers.sp()), initial_pc + 1);
+    }
 }
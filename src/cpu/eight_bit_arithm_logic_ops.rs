@@ -1,165 +1,221 @@
 use crate::cpu::registers::*;
 use crate::cpu::CPU;
 
-pub fn add(cpu: &mut CPU, register: Register8bits) {
+pub fn add(cpu: &mut CPU, register: Register8bits) -> u8 {
     let value = cpu.registers.read(&register);
     add_value_in_register_a(cpu, value);
+
+    1
 }
 
-pub fn add_hl(cpu: &mut CPU) {
+pub fn add_hl(cpu: &mut CPU) -> u8 {
     let value = cpu.value_in_addr(&Register16bits::HL);
     add_value_in_register_a(cpu, value);
+
+    2
 }
 
-pub fn add_d8(cpu: &mut CPU) {
+pub fn add_d8(cpu: &mut CPU) -> u8 {
     let value = cpu.read_d8();
     add_value_in_register_a(cpu, value);
+
+    2
 }
 
-pub fn adc(cpu: &mut CPU, register: Register8bits) {
+pub fn adc(cpu: &mut CPU, register: Register8bits) -> u8 {
     let value = cpu.registers.read(&register);
     adc_value_in_register_a(cpu, value);
+
+    1
 }
 
-pub fn adc_hl(cpu: &mut CPU) {
+pub fn adc_hl(cpu: &mut CPU) -> u8 {
     let value = cpu.value_in_addr(&Register16bits::HL);
     adc_value_in_register_a(cpu, value);
+
+    2
 }
 
-pub fn adc_d8(cpu: &mut CPU) {
+pub fn adc_d8(cpu: &mut CPU) -> u8 {
     let value = cpu.read_d8();
     adc_value_in_register_a(cpu, value);
+
+    2
 }
 
-pub fn sub(cpu: &mut CPU, register: Register8bits) {
+pub fn sub(cpu: &mut CPU, register: Register8bits) -> u8 {
     let value = cpu.registers.read(&register);
     sub_value_in_register_a(cpu, value);
+
+    1
 }
 
-pub fn sub_hl(cpu: &mut CPU) {
+pub fn sub_hl(cpu: &mut CPU) -> u8 {
     let value = cpu.value_in_addr(&Register16bits::HL);
     sub_value_in_register_a(cpu, value);
+
+    2
 }
 
-pub fn sub_d8(cpu: &mut CPU) {
+pub fn sub_d8(cpu: &mut CPU) -> u8 {
     let value = cpu.read_d8();
     sub_value_in_register_a(cpu, value);
+
+    2
 }
 
-pub fn and(cpu: &mut CPU, register: Register8bits) {
+pub fn and(cpu: &mut CPU, register: Register8bits) -> u8 {
     let value = cpu.registers.read(&register);
     and_value_in_register_a(cpu, value);
+
+    1
 }
 
-pub fn and_hl(cpu: &mut CPU) {
+pub fn and_hl(cpu: &mut CPU) -> u8 {
     let value = cpu.value_in_addr(&Register16bits::HL);
     and_value_in_register_a(cpu, value);
+
+    2
 }
 
-pub fn and_d8(cpu: &mut CPU) {
+pub fn and_d8(cpu: &mut CPU) -> u8 {
     let value = cpu.read_d8();
     and_value_in_register_a(cpu, value);
+
+    2
 }
 
-pub fn or(cpu: &mut CPU, register: Register8bits) {
+pub fn or(cpu: &mut CPU, register: Register8bits) -> u8 {
     let value = cpu.registers.read(&register);
     or_value_in_register_a(cpu, value);
+
+    1
 }
 
-pub fn or_hl(cpu: &mut CPU) {
+pub fn or_hl(cpu: &mut CPU) -> u8 {
     let value = cpu.value_in_addr(&Register16bits::HL);
     or_value_in_register_a(cpu, value);
+
+    2
 }
 
-pub fn or_d8(cpu: &mut CPU) {
+pub fn or_d8(cpu: &mut CPU) -> u8 {
     let value = cpu.read_d8();
     or_value_in_register_a(cpu, value);
+
+    2
 }
 
-pub fn xor(cpu: &mut CPU, register: Register8bits) {
+pub fn xor(cpu: &mut CPU, register: Register8bits) -> u8 {
     let value = cpu.registers.read(&register);
     xor_value_in_register_a(cpu, value);
+
+    1
 }
 
-pub fn xor_hl(cpu: &mut CPU) {
+pub fn xor_hl(cpu: &mut CPU) -> u8 {
     let value = cpu.value_in_addr(&Register16bits::HL);
     xor_value_in_register_a(cpu, value);
+
+    2
 }
 
-pub fn xor_d8(cpu: &mut CPU) {
+pub fn xor_d8(cpu: &mut CPU) -> u8 {
     let value = cpu.read_d8();
     xor_value_in_register_a(cpu, value);
+
+    2
 }
 
-pub fn cp(cpu: &mut CPU, register: Register8bits) {
+pub fn cp(cpu: &mut CPU, register: Register8bits) -> u8 {
     let value = cpu.registers.read(&register);
     cp_value_in_register_a(cpu, value);
+
+    1
 }
 
-pub fn cp_hl(cpu: &mut CPU) {
+pub fn cp_hl(cpu: &mut CPU) -> u8 {
     let value = cpu.value_in_addr(&Register16bits::HL);
     cp_value_in_register_a(cpu, value);
+
+    2
 }
 
-pub fn cp_d8(cpu: &mut CPU) {
+pub fn cp_d8(cpu: &mut CPU) -> u8 {
     let value = cpu.read_d8();
     cp_value_in_register_a(cpu, value);
+
+    2
 }
 
-pub fn sbc(cpu: &mut CPU, register: Register8bits) {
+pub fn sbc(cpu: &mut CPU, register: Register8bits) -> u8 {
     let value = cpu.registers.read(&register);
     sbc_value_in_register_a(cpu, value);
+
+    1
 }
 
-pub fn sbc_hl(cpu: &mut CPU) {
+pub fn sbc_hl(cpu: &mut CPU) -> u8 {
     let value = cpu.value_in_addr(&Register16bits::HL);
     sbc_value_in_register_a(cpu, value);
+
+    2
 }
 
-pub fn sbc_d8(cpu: &mut CPU) {
+pub fn sbc_d8(cpu: &mut CPU) -> u8 {
     let value = cpu.read_d8();
     sbc_value_in_register_a(cpu, value);
+
+    2
 }
 
-pub fn inc(cpu: &mut CPU, register: Register8bits) {
+pub fn inc(cpu: &mut CPU, register: Register8bits) -> u8 {
     let initial_val = cpu.registers.read(&register);
     let (new_val, _overflow) = initial_val.overflowing_add(1);
 
     cpu.registers.write(&register, new_val);
 
-    write_flags_for_inc(cpu, initial_val, new_val)
+    write_flags_for_inc(cpu, initial_val, new_val);
+
+    1
 }
 
-pub fn inc_hl(cpu: &mut CPU) {
+pub fn inc_hl(cpu: &mut CPU) -> u8 {
     let mem_address = cpu.registers.read_16b(&Register16bits::HL);
     let initial_val = cpu.memory.read_byte(mem_address);
     let (new_val, _overflow) = initial_val.overflowing_add(1);
 
     cpu.memory.write_byte(mem_address, new_val);
 
-    write_flags_for_inc(cpu, initial_val, new_val)
+    write_flags_for_inc(cpu, initial_val, new_val);
+
+    3
 }
 
-pub fn dec(cpu: &mut CPU, register: Register8bits) {
+pub fn dec(cpu: &mut CPU, register: Register8bits) -> u8 {
     let initial_val = cpu.registers.read(&register);
     let (new_val, _overflow) = initial_val.overflowing_sub(1);
 
     cpu.registers.write(&register, new_val);
 
-    write_flags_for_dec(cpu, initial_val, new_val)
+    write_flags_for_dec(cpu, initial_val, new_val);
+
+    1
 }
 
-pub fn dec_hl(cpu: &mut CPU) {
+pub fn dec_hl(cpu: &mut CPU) -> u8 {
     let mem_address = cpu.registers.read_16b(&Register16bits::HL);
     let initial_val = cpu.memory.read_byte(mem_address);
     let (new_val, _overflow) = initial_val.overflowing_sub(1);
 
     cpu.memory.write_byte(mem_address, new_val);
 
-    write_flags_for_dec(cpu, initial_val, new_val)
+    write_flags_for_dec(cpu, initial_val, new_val);
+
+    3
 }
 
-pub fn cpl(cpu: &mut CPU) {
+pub fn cpl(cpu: &mut CPU) -> u8 {
     let initial_val = cpu.registers.read(&Register8bits::A);
     let new_val = initial_val ^ 0xFF;
 
@@ -167,9 +223,11 @@ pub fn cpl(cpu: &mut CPU) {
 
     cpu.registers.write_s_flag(true);
     cpu.registers.write_hc_flag(true);
+
+    1
 }
 
-pub fn daa(cpu: &mut CPU) {
+pub fn daa(cpu: &mut CPU) -> u8 {
     // Ref: https://ehaskins.com/2018-01-30%20Z80%20DAA/
 
     let value = cpu.registers.read(&Register8bits::A);
@@ -203,6 +261,8 @@ pub fn daa(cpu: &mut CPU) {
 
     cpu.registers
         .write_flags(new_val == 0, cpu.registers.read_s_flag(), false, c);
+
+    1
 }
 
 fn add_value_in_register_a(cpu: &mut CPU, value: u8) {
@@ -3,40 +3,50 @@ use crate::cpu::CPU;
 
 use crate::memory::IO_PORTS_BEGIN;
 
-pub fn ld_r8_r8(cpu: &mut CPU, r1: Register8bits, r2: Register8bits) {
+pub fn ld_r8_r8(cpu: &mut CPU, r1: Register8bits, r2: Register8bits) -> u8 {
     let val_src = cpu.registers.read(&r2);
 
     cpu.registers.write(&r1, val_src);
+
+    1
 }
 
-pub fn ld_r8_d8(cpu: &mut CPU, register: Register8bits) {
+pub fn ld_r8_d8(cpu: &mut CPU, register: Register8bits) -> u8 {
     let data = cpu.read_d8();
 
     cpu.registers.write(&register, data);
+
+    2
 }
 
-pub fn ld_hl_d8(cpu: &mut CPU) {
+pub fn ld_hl_d8(cpu: &mut CPU) -> u8 {
     let data = cpu.read_d8();
     let mem_address = cpu.registers.read_16b(&Register16bits::HL);
 
     cpu.memory.write_byte(mem_address, data);
+
+    3
 }
 
-pub fn ld_r8_addr(cpu: &mut CPU, r1: Register8bits, r2: Register8bits) {
+pub fn ld_r8_addr(cpu: &mut CPU, r1: Register8bits, r2: Register8bits) -> u8 {
     let mem_address = IO_PORTS_BEGIN as u16 + (u16::from(cpu.registers.read(&r2)));
     let data = cpu.memory.read_byte(mem_address);
 
     cpu.registers.write(&r1, data);
+
+    2
 }
 
-pub fn ld_addr_r8(cpu: &mut CPU, r1: Register8bits, r2: Register8bits) {
+pub fn ld_addr_r8(cpu: &mut CPU, r1: Register8bits, r2: Register8bits) -> u8 {
     let mem_address = IO_PORTS_BEGIN as u16 + (u16::from(cpu.registers.read(&r1)));
     let data = cpu.registers.read(&r2);
 
     cpu.memory.write_byte(mem_address, data);
+
+    2
 }
 
-pub fn ld_a_hli(cpu: &mut CPU) {
+pub fn ld_a_hli(cpu: &mut CPU) -> u8 {
     let data = cpu.value_in_addr(&Register16bits::HL);
 
     cpu.registers.write(&Register8bits::A, data);
@@ -45,9 +55,11 @@ pub fn ld_a_hli(cpu: &mut CPU) {
     let (new_val, _overflow) = initial_val.overflowing_add(1);
 
     cpu.registers.write_16b(&Register16bits::HL, new_val);
+
+    2
 }
 
-pub fn ld_a_hld(cpu: &mut CPU) {
+pub fn ld_a_hld(cpu: &mut CPU) -> u8 {
     let data = cpu.value_in_addr(&Register16bits::HL);
 
     cpu.registers.write(&Register8bits::A, data);
@@ -56,9 +68,11 @@ pub fn ld_a_hld(cpu: &mut CPU) {
     let (new_val, _overflow) = initial_val.overflowing_sub(1);
 
     cpu.registers.write_16b(&Register16bits::HL, new_val);
+
+    2
 }
 
-pub fn ld_hli_a(cpu: &mut CPU) {
+pub fn ld_hli_a(cpu: &mut CPU) -> u8 {
     let mem_address = cpu.registers.read_16b(&Register16bits::HL);
     let data = cpu.registers.read(&Register8bits::A);
 
@@ -68,9 +82,11 @@ pub fn ld_hli_a(cpu: &mut CPU) {
     let (new_val, _overflow) = initial_val.overflowing_add(1);
 
     cpu.registers.write_16b(&Register16bits::HL, new_val);
+
+    2
 }
 
-pub fn ld_hld_a(cpu: &mut CPU) {
+pub fn ld_hld_a(cpu: &mut CPU) -> u8 {
     let mem_address = cpu.registers.read_16b(&Register16bits::HL);
     let data = cpu.registers.read(&Register8bits::A);
 
@@ -80,58 +96,76 @@ pub fn ld_hld_a(cpu: &mut CPU) {
     let (new_val, _overflow) = initial_val.overflowing_sub(1);
 
     cpu.registers.write_16b(&Register16bits::HL, new_val);
+
+    2
 }
 
-pub fn ld_hl_r8(cpu: &mut CPU, register: Register8bits) {
+pub fn ld_hl_r8(cpu: &mut CPU, register: Register8bits) -> u8 {
     let mem_address = cpu.registers.read_16b(&Register16bits::HL);
     let data = cpu.registers.read(&register);
 
     cpu.memory.write_byte(mem_address, data);
+
+    2
 }
 
-pub fn ld_r8_hl(cpu: &mut CPU, register: Register8bits) {
+pub fn ld_r8_hl(cpu: &mut CPU, register: Register8bits) -> u8 {
     let data = cpu.value_in_addr(&Register16bits::HL);
 
     cpu.registers.write(&register, data);
+
+    2
 }
 
-pub fn ld_r16_r8(cpu: &mut CPU, r16: Register16bits, r8: Register8bits) {
+pub fn ld_r16_r8(cpu: &mut CPU, r16: Register16bits, r8: Register8bits) -> u8 {
     let mem_address = cpu.registers.read_16b(&r16);
     let data = cpu.registers.read(&r8);
 
     cpu.memory.write_byte(mem_address, data);
+
+    2
 }
 
-pub fn ld_r8_r16(cpu: &mut CPU, r8: Register8bits, r16: Register16bits) {
+pub fn ld_r8_r16(cpu: &mut CPU, r8: Register8bits, r16: Register16bits) -> u8 {
     cpu.registers.write(&r8, cpu.value_in_addr(&r16));
+
+    2
 }
 
-pub fn ld_a8_a(cpu: &mut CPU) {
+pub fn ld_a8_a(cpu: &mut CPU) -> u8 {
     let mem_address = (IO_PORTS_BEGIN as u16) + u16::from(cpu.fetch_byte());
     let data = cpu.registers.read(&Register8bits::A);
 
     cpu.memory.write_byte(mem_address, data);
+
+    3
 }
 
-pub fn ld_a_a8(cpu: &mut CPU) {
+pub fn ld_a_a8(cpu: &mut CPU) -> u8 {
     let mem_address = (IO_PORTS_BEGIN as u16) + u16::from(cpu.fetch_byte());
     let data = cpu.memory.read_byte(mem_address);
 
     cpu.registers.write(&Register8bits::A, data);
+
+    3
 }
 
-pub fn ld_a16_a(cpu: &mut CPU) {
+pub fn ld_a16_a(cpu: &mut CPU) -> u8 {
     let a16 = cpu.read_a16();
     let data = cpu.registers.read(&Register8bits::A);
 
     cpu.memory.write_byte(a16, data);
+
+    4
 }
 
-pub fn ld_a_a16(cpu: &mut CPU) {
+pub fn ld_a_a16(cpu: &mut CPU) -> u8 {
     let a16 = cpu.read_a16();
     let data = cpu.memory.read_byte(a16);
 
     cpu.registers.write(&Register8bits::A, data);
+
+    4
 }
 
 #[cfg(test)]
@@ -192,10 +226,10 @@ mod tests {
     fn ld_r8_addr_op() {
         let mut mem = Memory::new();
         let initial_pc = 0x200;
-        mem.write_byte(IO_PORTS_BEGIN as u16 + 2, 4);
+        mem.write_byte(IO_PORTS_BEGIN as u16 + 0x80, 4);
         let mut cpu: CPU = CPU::new(&mut mem);
         cpu.registers.write_pc(initial_pc);
-        cpu.registers.write(&Register8bits::C, 2);
+        cpu.registers.write(&Register8bits::C, 0x80);
 
         ld_r8_addr(&mut cpu, Register8bits::A, Register8bits::C);
 
@@ -206,12 +240,12 @@ mod tests {
     fn ld_addr_r8_op() {
         let mut mem = Memory::new();
         let mut cpu: CPU = CPU::new(&mut mem);
-        cpu.registers.write(&Register8bits::C, 2);
+        cpu.registers.write(&Register8bits::C, 0x80);
         cpu.registers.write(&Register8bits::A, 4);
 
         ld_addr_r8(&mut cpu, Register8bits::C, Register8bits::A);
 
-        assert_eq!(mem.read_byte(IO_PORTS_BEGIN as u16 + 2), 4);
+        assert_eq!(mem.read_byte(IO_PORTS_BEGIN as u16 + 0x80), 4);
     }
 
     #[test]
@@ -334,8 +368,8 @@ mod tests {
     fn ld_a8_a_op() {
         let mut mem = Memory::new();
         let initial_pc = 0x200;
-        mem.write_byte(initial_pc, 4); // a8
-        let addr = IO_PORTS_BEGIN as u16 + 4;
+        mem.write_byte(initial_pc, 0x80); // a8
+        let addr = IO_PORTS_BEGIN as u16 + 0x80;
         let mut cpu: CPU = CPU::new(&mut mem);
         cpu.registers.write_pc(initial_pc);
         cpu.registers.write(&Register8bits::A, 2);
@@ -349,8 +383,8 @@ mod tests {
     fn ld_a_a8_op() {
         let mut mem = Memory::new();
         let initial_pc = 0x200;
-        mem.write_byte(initial_pc, 4); // a8
-        let addr = IO_PORTS_BEGIN as u16 + 4;
+        mem.write_byte(initial_pc, 0x80); // a8
+        let addr = IO_PORTS_BEGIN as u16 + 0x80;
         mem.write_byte(addr, 2);
         let mut cpu: CPU = CPU::new(&mut mem);
         cpu.registers.write_pc(initial_pc);
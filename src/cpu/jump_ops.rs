@@ -2,21 +2,26 @@ use crate::cpu::instructions::JumpCondition;
 use crate::cpu::registers::*;
 use crate::cpu::CPU;
 
-pub fn jp(cpu: &mut CPU, condition: JumpCondition) {
+pub fn jp(cpu: &mut CPU, condition: JumpCondition) -> u8 {
     let d16 = cpu.read_d16();
 
     if condition_is_true(cpu, condition) {
         cpu.registers.write_pc(d16);
+        4
+    } else {
+        3
     }
 }
 
-pub fn jp_hl(cpu: &mut CPU) {
+pub fn jp_hl(cpu: &mut CPU) -> u8 {
     let hl_val = cpu.registers.read_16b(&Register16bits::HL);
 
     cpu.registers.write_pc(hl_val);
+
+    1
 }
 
-pub fn jr(cpu: &mut CPU, condition: JumpCondition) {
+pub fn jr(cpu: &mut CPU, condition: JumpCondition) -> u8 {
     let jmp = cpu.fetch_byte() as i8;
 
     if condition_is_true(cpu, condition) {
@@ -27,35 +32,56 @@ pub fn jr(cpu: &mut CPU, condition: JumpCondition) {
         } else {
             cpu.registers.decrease_pc(abs_jmp as u16);
         }
+
+        3
+    } else {
+        2
     }
 }
 
-pub fn rst(cpu: &mut CPU, offset: u8) {
+pub fn rst(cpu: &mut CPU, offset: u8) -> u8 {
     cpu.push_to_stack(cpu.registers.pc());
     cpu.registers.write_pc(u16::from(offset));
+
+    4
 }
 
-pub fn ret(cpu: &mut CPU, condition: JumpCondition) {
+pub fn ret(cpu: &mut CPU, condition: JumpCondition) -> u8 {
+    // Unlike the other jumps, a conditional "ret" needs an extra cycle to
+    // check the condition, so it is slower than the unconditional one.
+    let check_cycles = match condition {
+        JumpCondition::Always => 0,
+        _ => 1,
+    };
+
     if condition_is_true(cpu, condition) {
         let jp_addr = cpu.pop_from_stack();
         cpu.registers.write_pc(jp_addr);
+        4 + check_cycles
+    } else {
+        2
     }
 }
 
-pub fn reti(cpu: &mut CPU) {
+pub fn reti(cpu: &mut CPU) -> u8 {
     let jp_addr = cpu.pop_from_stack();
 
     cpu.registers.write_pc(jp_addr);
 
     cpu.interrupts_enabled = true;
+
+    4
 }
 
-pub fn call(cpu: &mut CPU, condition: JumpCondition) {
+pub fn call(cpu: &mut CPU, condition: JumpCondition) -> u8 {
     let a16 = cpu.read_a16();
 
     if condition_is_true(cpu, condition) {
         cpu.push_to_stack(cpu.registers.pc());
         cpu.registers.write_pc(a16);
+        6
+    } else {
+        3
     }
 }
 
@@ -517,13 +543,13 @@ mod tests {
         mem.write_byte(initial_sp, jp_addr);
         let mut cpu: CPU = CPU::new(&mut mem);
         cpu.registers.write_sp(initial_sp);
+        cpu.interrupts_enabled = false;
 
         reti(&mut cpu);
 
         assert_eq!(cpu.registers.sp(), initial_sp + 2);
         assert_eq!(cpu.registers.pc(), jp_addr as u16);
-
-        // TODO: test enable interrupts
+        assert!(cpu.interrupts_enabled);
     }
 
     #[test]
@@ -695,4 +721,50 @@ mod tests {
         assert_eq!(cpu.registers.pc(), initial_pc + 2);
         assert_eq!(cpu.registers.sp(), initial_sp);
     }
+
+    #[test]
+    fn jp_cycles() {
+        let mut mem = Memory::new();
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.registers.write_flags(true, false, false, false);
+
+        assert_eq!(jp(&mut cpu, JumpCondition::Z), 4);
+        assert_eq!(jp(&mut cpu, JumpCondition::NZ), 3);
+        assert_eq!(jp(&mut cpu, JumpCondition::Always), 4);
+    }
+
+    #[test]
+    fn jr_cycles() {
+        let mut mem = Memory::new();
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.registers.write_flags(false, false, false, true);
+
+        assert_eq!(jr(&mut cpu, JumpCondition::C), 3);
+        assert_eq!(jr(&mut cpu, JumpCondition::NC), 2);
+        assert_eq!(jr(&mut cpu, JumpCondition::Always), 3);
+    }
+
+    #[test]
+    fn ret_cycles() {
+        let mut mem = Memory::new();
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.registers.write_sp(0x8080);
+        cpu.registers.write_flags(true, false, false, false);
+
+        assert_eq!(ret(&mut cpu, JumpCondition::Z), 5);
+        assert_eq!(ret(&mut cpu, JumpCondition::NZ), 2);
+        assert_eq!(ret(&mut cpu, JumpCondition::Always), 4);
+    }
+
+    #[test]
+    fn call_cycles() {
+        let mut mem = Memory::new();
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.registers.write_sp(0x8000);
+        cpu.registers.write_flags(false, false, false, false);
+
+        assert_eq!(call(&mut cpu, JumpCondition::NZ), 6);
+        assert_eq!(call(&mut cpu, JumpCondition::Z), 3);
+        assert_eq!(call(&mut cpu, JumpCondition::Always), 6);
+    }
 }
@@ -8,7 +8,9 @@ use crate::cpu::registers::*;
 use crate::cpu::rotate_ops::*;
 use crate::cpu::sixteen_bit_arithm_logic_ops::*;
 use crate::cpu::sixteen_bit_load_ops::*;
+use crate::interrupts::InterruptKind;
 use crate::memory::Memory;
+use crate::model::Model;
 
 mod bit_ops;
 mod control_ops;
@@ -22,10 +24,29 @@ mod sixteen_bit_load_ops;
 mod instructions;
 mod registers;
 
+// Dispatching an interrupt takes 5 M-cycles: 2 wait states, 2 to push PC to
+// the stack and 1 to jump to the ISR.
+const INTERRUPT_DISPATCH_CYCLES: u8 = 5;
+
 pub struct CPU<'memory> {
     registers: Registers,
     memory: &'memory mut Memory,
     interrupts_enabled: bool,
+
+    // EI enables interrupts after the instruction that follows it.
+    interrupts_enable_scheduled: bool,
+
+    // Set by HALT. The CPU does not run instructions until an interrupt is
+    // pending.
+    halted: bool,
+
+    // Set by STOP. Everything stops until a button is pressed.
+    stopped: bool,
+
+    // When HALT is run with IME disabled and an interrupt already pending, the
+    // CPU does not halt, and it fails to increase PC after fetching the next
+    // byte, so that byte is read twice.
+    halt_bug: bool,
 }
 
 impl<'memory> CPU<'memory> {
@@ -34,40 +55,104 @@ impl<'memory> CPU<'memory> {
             registers: Registers::new(),
             memory,
             interrupts_enabled: true,
+            interrupts_enable_scheduled: false,
+            halted: false,
+            stopped: false,
+            halt_bug: false,
+        }
+    }
+
+    /// Creates a CPU as it is when the Game Boy is turned on, to run the boot
+    /// ROM loaded in memory from 0x0000. The boot ROM sets up the hardware
+    /// and jumps to the cartridge at 0x100.
+    pub fn new_at_0x0000(memory: &'memory mut Memory) -> Self {
+        CPU {
+            registers: Registers::new(),
+            memory,
+            interrupts_enabled: false,
+            interrupts_enable_scheduled: false,
+            halted: false,
+            stopped: false,
+            halt_bug: false,
         }
     }
 
-    /// ROMs start running at PC = 0x100 after the bootloader.
-    /// This method creates a new CPU setting the registers and memory with the
-    /// values when PC = 0x100 I observed when debugging test ROMs using the
-    /// BGB emulator.
-    pub fn new_at_0x100(memory: &'memory mut Memory) -> Self {
+    /// ROMs start running at PC = 0x100 after the boot ROM. This method
+    /// creates a new CPU with the registers and memory set to the values that
+    /// the boot ROM of the given model leaves.
+    pub fn new_at_0x100(memory: &'memory mut Memory, model: Model) -> Self {
+        let post_boot = model.post_boot_registers(memory);
         let mut registers = Registers::new();
 
         registers.write_pc(0x100);
         registers.write_sp(0xFFFE);
 
-        registers.write_16b(&Register16bits::AF, 0x1180);
-        registers.write_16b(&Register16bits::BC, 0x0000);
-        registers.write_16b(&Register16bits::DE, 0xFF56);
-        registers.write_16b(&Register16bits::HL, 0x000D);
+        registers.write_16b(&Register16bits::AF, post_boot.af);
+        registers.write_16b(&Register16bits::BC, post_boot.bc);
+        registers.write_16b(&Register16bits::DE, post_boot.de);
+        registers.write_16b(&Register16bits::HL, post_boot.hl);
 
-        registers.write_flags(true, false, false, false);
-
-        memory.write_byte(0xFF0F, 0xE1);
+        memory.skip_boot_rom(model);
 
         CPU {
             registers,
             memory,
             interrupts_enabled: false,
+            interrupts_enable_scheduled: false,
+            halted: false,
+            stopped: false,
+            halt_bug: false,
         }
     }
 
-    pub fn run_next_instruction(&mut self) {
-        if self.attend_pending_interrupt() {
-            return;
+    pub fn memory(&mut self) -> &mut Memory {
+        self.memory
+    }
+
+    /// Runs the next instruction, or dispatches a pending interrupt, and
+    /// returns the number of machine cycles (M-cycles) it took. Each M-cycle
+    /// is 4 clock cycles.
+    pub fn run_next_instruction(&mut self) -> u8 {
+        if self.stopped {
+            // The clock is stopped, so the timer and the rest of components do
+            // not advance either.
+            if self.memory.interrupts.is_pending(&InterruptKind::JOYPAD) {
+                self.stopped = false;
+            }
+
+            return 1;
+        }
+
+        if self.halted {
+            // The CPU wakes up when there is a pending interrupt even if IME
+            // is disabled. In that case, the interrupt is not attended and
+            // execution continues after the HALT.
+            if self.memory.interrupts.any_pending() {
+                self.halted = false;
+            } else {
+                self.memory.tick(1);
+                return 1;
+            }
         }
 
+        // The CPU is stopped while VRAM DMA copies a block.
+        if self.memory.vram_dma_pending() {
+            return self.memory.run_vram_dma_block();
+        }
+
+        let cycles = match self.attend_pending_interrupt() {
+            Some(cycles) => cycles,
+            None => self.fetch_and_execute(),
+        };
+
+        self.memory.tick(cycles);
+
+        cycles
+    }
+
+    fn fetch_and_execute(&mut self) -> u8 {
+        let enable_scheduled_before = self.interrupts_enable_scheduled;
+
         let mut instruction_byte = self.fetch_byte();
 
         let prefixed = instruction_byte == instructions::PREFIX_INSTR_CODE;
@@ -79,10 +164,20 @@ impl<'memory> CPU<'memory> {
             Instruction::decode(instruction_byte)
         };
 
-        self.execute(instruction);
+        let cycles = self.execute(instruction);
+
+        // If the instruction was a DI, the scheduled enable is cancelled.
+        if enable_scheduled_before && self.interrupts_enable_scheduled {
+            self.interrupts_enable_scheduled = false;
+            self.interrupts_enabled = true;
+        }
+
+        cycles
     }
 
-    pub fn execute(&mut self, instruction: Instruction) {
+    /// Executes the given instruction and returns the M-cycles it took. For
+    /// prefixed instructions, the cycle spent fetching the prefix is included.
+    pub fn execute(&mut self, instruction: Instruction) -> u8 {
         match instruction {
             //  8-bit arithmetic and logic
             Instruction::ADD(register) => add(self, register),
@@ -187,11 +282,11 @@ impl<'memory> CPU<'memory> {
             Instruction::CCF => ccf(self),
             Instruction::NOP => nop(),
             Instruction::SCF => scf(self),
-            Instruction::HALT => halt(),
-            Instruction::STOP => stop(),
+            Instruction::HALT => halt(self),
+            Instruction::STOP => stop(self),
             Instruction::DI => di(self),
             Instruction::EI => ei(self),
-            Instruction::PREFIX => (),
+            Instruction::PREFIX => 1,
 
             // Jumps
             Instruction::JP(condition) => jp(self, condition),
@@ -212,7 +307,13 @@ impl<'memory> CPU<'memory> {
 
     fn fetch_byte(&mut self) -> u8 {
         let byte = self.memory.read_byte(self.registers.pc());
-        self.registers.increase_pc(1);
+
+        if self.halt_bug {
+            self.halt_bug = false;
+        } else {
+            self.registers.increase_pc(1);
+        }
+
         byte
     }
 
@@ -252,7 +353,9 @@ impl<'memory> CPU<'memory> {
         self.memory.read_byte(register_val)
     }
 
-    fn attend_pending_interrupt(&mut self) -> bool {
+    // Returns the M-cycles spent dispatching the interrupt, if there was one
+    // to attend.
+    fn attend_pending_interrupt(&mut self) -> Option<u8> {
         if self.interrupts_enabled {
             let isr_addr = self.memory.interrupts.isr_of_first_pending();
 
@@ -261,12 +364,197 @@ impl<'memory> CPU<'memory> {
                     self.interrupts_enabled = false;
                     self.push_to_stack(self.registers.pc());
                     self.registers.write_pc(addr);
-                    return true;
+                    return Some(INTERRUPT_DISPATCH_CYCLES);
                 }
-                None => return false,
+                None => return None,
             }
         }
 
-        false
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::BootRom;
+
+    #[test]
+    fn boot_rom_hands_over_to_the_cartridge() {
+        let mut mem = Memory::new();
+        let mut boot_rom = vec![0; 0x100];
+        boot_rom[0x00] = 0x3E; // LD A, 0x01
+        boot_rom[0x01] = 0x01;
+        boot_rom[0xFE] = 0xE0; // LDH (0x50), A
+        boot_rom[0xFF] = 0x50;
+        mem.write_byte(0x0000, 0x76);
+        mem.write_byte(0x0100, 0x3C); // INC A
+        mem.load_boot_rom(BootRom::new(boot_rom).unwrap());
+        let mut cpu = CPU::new_at_0x0000(&mut mem);
+
+        // LD A, the NOPs and LDH.
+        for _ in 0..(1 + 0xFC + 1) {
+            cpu.run_next_instruction();
+        }
+        assert_eq!(cpu.registers.pc(), 0x0100);
+        assert!(!cpu.memory.boot_rom_mapped());
+
+        cpu.run_next_instruction();
+        assert_eq!(cpu.registers.read(&Register8bits::A), 0x02);
+        assert_eq!(cpu.memory.read_byte(0x0000), 0x76);
+    }
+
+    #[test]
+    fn run_next_instruction_returns_cycles() {
+        let mut mem = Memory::new();
+        let initial_pc = 0x200;
+        mem.write_byte(initial_pc, 0x00); // NOP
+        mem.write_byte(initial_pc + 1, 0x36); // LD (HL), d8
+        mem.write_byte(initial_pc + 2, 0x01);
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.registers.write_pc(initial_pc);
+        cpu.registers.write_16b(&Register16bits::HL, 0x8000);
+
+        assert_eq!(cpu.run_next_instruction(), 1);
+        assert_eq!(cpu.run_next_instruction(), 3);
+    }
+
+    #[test]
+    fn vram_dma_stops_the_cpu() {
+        let mut mem = Memory::new();
+        mem.write_byte(0x0143, 0x80);
+        mem.set_model(Model::Cgb);
+        let initial_pc = 0x200;
+        mem.write_byte(initial_pc, 0xE0); // LDH (0x55), A
+        mem.write_byte(initial_pc + 1, 0x55);
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.registers.write_pc(initial_pc);
+        cpu.registers.write(&Register8bits::A, 0x01);
+
+        cpu.run_next_instruction();
+
+        // Two blocks of 8 M-cycles.
+        assert_eq!(cpu.run_next_instruction(), 8);
+        assert_eq!(cpu.run_next_instruction(), 8);
+        assert_eq!(cpu.registers.pc(), initial_pc + 2);
+        assert_eq!(cpu.memory.read_byte(0xFF55), 0xFF);
+    }
+
+    #[test]
+    fn run_next_instruction_includes_prefix_cycles() {
+        let mut mem = Memory::new();
+        let initial_pc = 0x200;
+        mem.write_byte(initial_pc, instructions::PREFIX_INSTR_CODE);
+        mem.write_byte(initial_pc + 1, 0x00); // RLC B
+        mem.write_byte(initial_pc + 2, instructions::PREFIX_INSTR_CODE);
+        mem.write_byte(initial_pc + 3, 0xC6); // SET 0, (HL)
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.registers.write_pc(initial_pc);
+        cpu.registers.write_16b(&Register16bits::HL, 0x8000);
+
+        assert_eq!(cpu.run_next_instruction(), 2);
+        assert_eq!(cpu.run_next_instruction(), 4);
+    }
+
+    #[test]
+    fn interrupt_dispatch_cycles() {
+        let mut mem = Memory::new();
+        mem.interrupts.enable_or_disable_interrupts(0b0000_0001);
+        mem.interrupts.add_interrupts(0b0000_0001);
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.registers.write_sp(0x8080);
+
+        assert_eq!(cpu.run_next_instruction(), INTERRUPT_DISPATCH_CYCLES);
+        assert_eq!(cpu.registers.pc(), 0x40);
+    }
+
+    #[test]
+    fn halted_cpu_burns_cycles_until_interrupt_pending() {
+        let mut mem = Memory::new();
+        let initial_pc = 0x200;
+        mem.write_byte(initial_pc, 0x76); // HALT
+        mem.write_byte(initial_pc + 1, 0x00); // NOP
+        mem.interrupts.enable_or_disable_interrupts(0b0000_0100);
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.registers.write_pc(initial_pc);
+        cpu.registers.write_sp(0x8080);
+
+        cpu.run_next_instruction();
+        cpu.run_next_instruction();
+
+        assert!(cpu.halted);
+        assert_eq!(cpu.registers.pc(), initial_pc + 1);
+
+        cpu.memory.interrupts.request(&InterruptKind::TIMER);
+        cpu.run_next_instruction();
+
+        assert!(!cpu.halted);
+        assert_eq!(cpu.registers.pc(), 0x50);
+    }
+
+    #[test]
+    fn halted_cpu_wakes_up_without_ime() {
+        let mut mem = Memory::new();
+        let initial_pc = 0x200;
+        mem.write_byte(initial_pc, 0x76); // HALT
+        mem.write_byte(initial_pc + 1, 0x00); // NOP
+        mem.interrupts.enable_or_disable_interrupts(0b0000_0100);
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.registers.write_pc(initial_pc);
+        cpu.interrupts_enabled = false;
+
+        cpu.run_next_instruction();
+        cpu.memory.interrupts.request(&InterruptKind::TIMER);
+        cpu.run_next_instruction();
+
+        assert!(!cpu.halted);
+        assert_eq!(cpu.registers.pc(), initial_pc + 2);
+        assert!(cpu.memory.interrupts.is_pending(&InterruptKind::TIMER));
+    }
+
+    #[test]
+    fn halt_bug_reads_next_byte_twice() {
+        let mut mem = Memory::new();
+        let initial_pc = 0x200;
+        mem.write_byte(initial_pc, 0x76); // HALT
+        mem.write_byte(initial_pc + 1, 0x3C); // INC A
+        mem.interrupts.enable_or_disable_interrupts(0b0000_0100);
+        mem.interrupts.request(&InterruptKind::TIMER);
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.registers.write_pc(initial_pc);
+        cpu.interrupts_enabled = false;
+
+        cpu.run_next_instruction();
+        cpu.run_next_instruction();
+        cpu.run_next_instruction();
+
+        assert!(!cpu.halted);
+        assert_eq!(cpu.registers.read(&Register8bits::A), 2);
+        assert_eq!(cpu.registers.pc(), initial_pc + 2);
+    }
+
+    #[test]
+    fn stopped_cpu_waits_for_joypad() {
+        let mut mem = Memory::new();
+        let initial_pc = 0x200;
+        mem.write_byte(initial_pc, 0x10); // STOP
+        mem.write_byte(initial_pc + 1, 0x00);
+        mem.write_byte(initial_pc + 2, 0x00); // NOP
+        let mut cpu: CPU = CPU::new(&mut mem);
+        cpu.registers.write_pc(initial_pc);
+        cpu.interrupts_enabled = false;
+
+        cpu.run_next_instruction();
+        cpu.run_next_instruction();
+
+        assert!(cpu.stopped);
+        assert_eq!(cpu.registers.pc(), initial_pc + 2);
+
+        cpu.memory.interrupts.request(&InterruptKind::JOYPAD);
+        cpu.run_next_instruction();
+        cpu.run_next_instruction();
+
+        assert!(!cpu.stopped);
+        assert_eq!(cpu.registers.pc(), initial_pc + 3);
     }
 }
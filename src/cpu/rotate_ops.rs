@@ -1,7 +1,7 @@
 use crate::cpu::registers::*;
 use crate::cpu::CPU;
 
-pub fn rl(cpu: &mut CPU, register: Register8bits) {
+pub fn rl(cpu: &mut CPU, register: Register8bits) -> u8 {
     let initial_val = cpu.registers.read(&register);
     let initial_bit_7 = initial_val >> 7;
     let initial_c = cpu.registers.read_c_flag();
@@ -12,9 +12,11 @@ pub fn rl(cpu: &mut CPU, register: Register8bits) {
 
     cpu.registers
         .write_flags(new_val == 0, false, false, initial_bit_7 == 1);
+
+    2
 }
 
-pub fn rla(cpu: &mut CPU) {
+pub fn rla(cpu: &mut CPU) -> u8 {
     let initial_val = cpu.registers.read(&Register8bits::A);
     let initial_bit_7 = initial_val >> 7;
     let initial_c = cpu.registers.read_c_flag();
@@ -25,9 +27,11 @@ pub fn rla(cpu: &mut CPU) {
 
     cpu.registers
         .write_flags(false, false, false, initial_bit_7 == 1);
+
+    1
 }
 
-pub fn rl_hl(cpu: &mut CPU) {
+pub fn rl_hl(cpu: &mut CPU) -> u8 {
     let mem_address = cpu.registers.read_16b(&Register16bits::HL);
     let initial_val = cpu.memory.read_byte(mem_address);
     let initial_bit_7 = initial_val >> 7;
@@ -39,9 +43,11 @@ pub fn rl_hl(cpu: &mut CPU) {
 
     cpu.registers
         .write_flags(new_val == 0, false, false, initial_bit_7 == 1);
+
+    4
 }
 
-pub fn rlc(cpu: &mut CPU, register: Register8bits) {
+pub fn rlc(cpu: &mut CPU, register: Register8bits) -> u8 {
     let initial_val = cpu.registers.read(&register);
     let new_val = initial_val.rotate_left(1);
 
@@ -49,9 +55,11 @@ pub fn rlc(cpu: &mut CPU, register: Register8bits) {
 
     cpu.registers
         .write_flags(new_val == 0, false, false, new_val & 1 == 1);
+
+    2
 }
 
-pub fn rlca(cpu: &mut CPU) {
+pub fn rlca(cpu: &mut CPU) -> u8 {
     let initial_val = cpu.registers.read(&Register8bits::A);
     let new_val = initial_val.rotate_left(1);
 
@@ -59,9 +67,11 @@ pub fn rlca(cpu: &mut CPU) {
 
     cpu.registers
         .write_flags(false, false, false, new_val & 1 == 1);
+
+    1
 }
 
-pub fn rlc_hl(cpu: &mut CPU) {
+pub fn rlc_hl(cpu: &mut CPU) -> u8 {
     let mem_address = cpu.registers.read_16b(&Register16bits::HL);
     let initial_val = cpu.memory.read_byte(mem_address);
     let new_val = initial_val.rotate_left(1);
@@ -70,9 +80,11 @@ pub fn rlc_hl(cpu: &mut CPU) {
 
     cpu.registers
         .write_flags(new_val == 0, false, false, new_val & 1 == 1);
+
+    4
 }
 
-pub fn rr(cpu: &mut CPU, register: Register8bits) {
+pub fn rr(cpu: &mut CPU, register: Register8bits) -> u8 {
     let initial_val = cpu.registers.read(&register);
     let initial_bit_0 = initial_val & 1;
     let initial_c = cpu.registers.read_c_flag();
@@ -83,9 +95,11 @@ pub fn rr(cpu: &mut CPU, register: Register8bits) {
 
     cpu.registers
         .write_flags(new_val == 0, false, false, initial_bit_0 == 1);
+
+    2
 }
 
-pub fn rra(cpu: &mut CPU) {
+pub fn rra(cpu: &mut CPU) -> u8 {
     let initial_val = cpu.registers.read(&Register8bits::A);
     let initial_bit_0 = initial_val & 1;
     let initial_c = cpu.registers.read_c_flag();
@@ -96,9 +110,11 @@ pub fn rra(cpu: &mut CPU) {
 
     cpu.registers
         .write_flags(false, false, false, initial_bit_0 == 1);
+
+    1
 }
 
-pub fn rr_hl(cpu: &mut CPU) {
+pub fn rr_hl(cpu: &mut CPU) -> u8 {
     let mem_address = cpu.registers.read_16b(&Register16bits::HL);
     let initial_val = cpu.memory.read_byte(mem_address);
     let initial_bit_0 = initial_val & 1;
@@ -110,9 +126,11 @@ pub fn rr_hl(cpu: &mut CPU) {
 
     cpu.registers
         .write_flags(new_val == 0, false, false, initial_bit_0 == 1);
+
+    4
 }
 
-pub fn rrc(cpu: &mut CPU, register: Register8bits) {
+pub fn rrc(cpu: &mut CPU, register: Register8bits) -> u8 {
     let initial_val = cpu.registers.read(&register);
     let initial_bit_0 = initial_val & 1;
     let new_val = initial_val.rotate_right(1);
@@ -121,9 +139,11 @@ pub fn rrc(cpu: &mut CPU, register: Register8bits) {
 
     cpu.registers
         .write_flags(new_val == 0, false, false, initial_bit_0 == 1);
+
+    2
 }
 
-pub fn rrca(cpu: &mut CPU) {
+pub fn rrca(cpu: &mut CPU) -> u8 {
     let initial_val = cpu.registers.read(&Register8bits::A);
     let initial_bit_0 = initial_val & 1;
     let new_val = initial_val.rotate_right(1);
@@ -132,9 +152,11 @@ pub fn rrca(cpu: &mut CPU) {
 
     cpu.registers
         .write_flags(false, false, false, initial_bit_0 == 1);
+
+    1
 }
 
-pub fn rrc_hl(cpu: &mut CPU) {
+pub fn rrc_hl(cpu: &mut CPU) -> u8 {
     let mem_address = cpu.registers.read_16b(&Register16bits::HL);
     let initial_val = cpu.memory.read_byte(mem_address);
     let initial_bit_0 = initial_val & 1;
@@ -144,9 +166,11 @@ pub fn rrc_hl(cpu: &mut CPU) {
 
     cpu.registers
         .write_flags(new_val == 0, false, false, initial_bit_0 == 1);
+
+    4
 }
 
-pub fn sla(cpu: &mut CPU, register: Register8bits) {
+pub fn sla(cpu: &mut CPU, register: Register8bits) -> u8 {
     let initial_val = cpu.registers.read(&register);
     let initial_bit_7 = initial_val >> 7;
     let new_val = initial_val << 1;
@@ -155,9 +179,11 @@ pub fn sla(cpu: &mut CPU, register: Register8bits) {
 
     cpu.registers
         .write_flags(new_val == 0, false, false, initial_bit_7 == 1);
+
+    2
 }
 
-pub fn sla_hl(cpu: &mut CPU) {
+pub fn sla_hl(cpu: &mut CPU) -> u8 {
     let mem_address = cpu.registers.read_16b(&Register16bits::HL);
     let initial_val = cpu.memory.read_byte(mem_address);
     let initial_bit_7 = initial_val >> 7;
@@ -167,9 +193,11 @@ pub fn sla_hl(cpu: &mut CPU) {
 
     cpu.registers
         .write_flags(new_val == 0, false, false, initial_bit_7 == 1);
+
+    4
 }
 
-pub fn sra(cpu: &mut CPU, register: Register8bits) {
+pub fn sra(cpu: &mut CPU, register: Register8bits) -> u8 {
     let initial_val = cpu.registers.read(&register);
     let initial_bit_7 = initial_val >> 7;
     let initial_bit_0 = initial_val & 1;
@@ -179,9 +207,11 @@ pub fn sra(cpu: &mut CPU, register: Register8bits) {
 
     cpu.registers
         .write_flags(new_val == 0, false, false, initial_bit_0 == 1);
+
+    2
 }
 
-pub fn sra_hl(cpu: &mut CPU) {
+pub fn sra_hl(cpu: &mut CPU) -> u8 {
     let mem_address = cpu.registers.read_16b(&Register16bits::HL);
     let initial_val = cpu.memory.read_byte(mem_address);
 
@@ -193,9 +223,11 @@ pub fn sra_hl(cpu: &mut CPU) {
 
     cpu.registers
         .write_flags(new_val == 0, false, false, initial_bit_0 == 1);
+
+    4
 }
 
-pub fn srl(cpu: &mut CPU, register: Register8bits) {
+pub fn srl(cpu: &mut CPU, register: Register8bits) -> u8 {
     let initial_val = cpu.registers.read(&register);
     let initial_bit_0 = initial_val & 1;
     let new_val = initial_val >> 1;
@@ -204,9 +236,11 @@ pub fn srl(cpu: &mut CPU, register: Register8bits) {
 
     cpu.registers
         .write_flags(new_val == 0, false, false, initial_bit_0 == 1);
+
+    2
 }
 
-pub fn srl_hl(cpu: &mut CPU) {
+pub fn srl_hl(cpu: &mut CPU) -> u8 {
     let mem_address = cpu.registers.read_16b(&Register16bits::HL);
     let initial_val = cpu.memory.read_byte(mem_address);
     let initial_bit_0 = initial_val & 1;
@@ -216,9 +250,11 @@ pub fn srl_hl(cpu: &mut CPU) {
 
     cpu.registers
         .write_flags(new_val == 0, false, false, initial_bit_0 == 1);
+
+    4
 }
 
-pub fn swap(cpu: &mut CPU, register: Register8bits) {
+pub fn swap(cpu: &mut CPU, register: Register8bits) -> u8 {
     let initial_val = cpu.registers.read(&register);
     let high = initial_val & 0xF0;
     let low = initial_val & 0x0F;
@@ -227,9 +263,11 @@ pub fn swap(cpu: &mut CPU, register: Register8bits) {
     cpu.registers.write(&register, new_val);
 
     cpu.registers.write_flags(new_val == 0, false, false, false);
+
+    2
 }
 
-pub fn swap_hl(cpu: &mut CPU) {
+pub fn swap_hl(cpu: &mut CPU) -> u8 {
     let mem_address = cpu.registers.read_16b(&Register16bits::HL);
     let initial_val = cpu.memory.read_byte(mem_address);
     let high = initial_val & 0xF0;
@@ -239,6 +277,8 @@ pub fn swap_hl(cpu: &mut CPU) {
     cpu.memory.write_byte(mem_address, new_val);
 
     cpu.registers.write_flags(new_val == 0, false, false, false);
+
+    4
 }
 
 #[cfg(test)]
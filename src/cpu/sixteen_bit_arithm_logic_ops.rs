@@ -1,7 +1,7 @@
 use crate::cpu::registers::*;
 use crate::cpu::CPU;
 
-pub fn add16(cpu: &mut CPU, register: Register16bits) {
+pub fn add16(cpu: &mut CPU, register: Register16bits) -> u8 {
     let register_val = cpu.registers.read_16b(&register);
     let register_a_val = cpu.registers.read_16b(&Register16bits::HL);
     let (new_val, overflow) = register_a_val.overflowing_add(register_val);
@@ -12,28 +12,38 @@ pub fn add16(cpu: &mut CPU, register: Register16bits) {
     cpu.registers
         .write_hc_flag(CPU::half_carry_in_add_16(register_a_val, register_val));
     cpu.registers.write_c_flag(overflow);
+
+    2
 }
 
-pub fn dec16(cpu: &mut CPU, register: Register16bits) {
+pub fn dec16(cpu: &mut CPU, register: Register16bits) -> u8 {
     let initial_val = cpu.registers.read_16b(&register);
     let (new_val, _overflow) = initial_val.overflowing_sub(1);
 
     cpu.registers.write_16b(&register, new_val);
+
+    2
 }
 
-pub fn dec_sp(cpu: &mut CPU) {
+pub fn dec_sp(cpu: &mut CPU) -> u8 {
     cpu.registers.decrease_sp(1);
+
+    2
 }
 
-pub fn inc16(cpu: &mut CPU, register: Register16bits) {
+pub fn inc16(cpu: &mut CPU, register: Register16bits) -> u8 {
     let initial_val = cpu.registers.read_16b(&register);
     let (new_val, _overflow) = initial_val.overflowing_add(1);
 
     cpu.registers.write_16b(&register, new_val);
+
+    2
 }
 
-pub fn inc_sp(cpu: &mut CPU) {
+pub fn inc_sp(cpu: &mut CPU) -> u8 {
     cpu.registers.increase_sp(1);
+
+    2
 }
 
 #[cfg(test)]
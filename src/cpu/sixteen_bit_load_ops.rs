@@ -1,25 +1,31 @@
 use crate::cpu::registers::*;
 use crate::cpu::CPU;
 
-pub fn ld_r16_d16(cpu: &mut CPU, register: Register16bits) {
+pub fn ld_r16_d16(cpu: &mut CPU, register: Register16bits) -> u8 {
     let d16 = cpu.read_d16();
 
     cpu.registers.write_16b(&register, d16);
+
+    3
 }
 
-pub fn ld_sp_d16(cpu: &mut CPU) {
+pub fn ld_sp_d16(cpu: &mut CPU) -> u8 {
     let d16 = cpu.read_d16();
 
     cpu.registers.write_sp(d16);
+
+    3
 }
 
-pub fn ld_a16_sp(cpu: &mut CPU) {
+pub fn ld_a16_sp(cpu: &mut CPU) -> u8 {
     let a16 = cpu.read_a16();
 
     cpu.memory.write_word(a16, cpu.registers.sp());
+
+    5
 }
 
-pub fn add_hl_sp(cpu: &mut CPU) {
+pub fn add_hl_sp(cpu: &mut CPU) -> u8 {
     let sp = cpu.registers.sp();
     let register_hl_val = cpu.registers.read_16b(&Register16bits::HL);
     let (new_val, overflow) = register_hl_val.overflowing_add(sp);
@@ -30,15 +36,19 @@ pub fn add_hl_sp(cpu: &mut CPU) {
     cpu.registers
         .write_hc_flag(CPU::half_carry_in_add_16(register_hl_val, sp));
     cpu.registers.write_c_flag(overflow);
+
+    2
 }
 
-pub fn ld_sp_hl(cpu: &mut CPU) {
+pub fn ld_sp_hl(cpu: &mut CPU) -> u8 {
     let register_hl_val = cpu.registers.read_16b(&Register16bits::HL);
 
     cpu.registers.write_sp(register_hl_val);
+
+    2
 }
 
-pub fn add_sp_r8(cpu: &mut CPU) {
+pub fn add_sp_r8(cpu: &mut CPU) -> u8 {
     let sp_val = i32::from(cpu.registers.sp());
     let r8 = i32::from(cpu.fetch_byte() as i8);
     let new_val = sp_val.wrapping_add(r8);
@@ -51,9 +61,11 @@ pub fn add_sp_r8(cpu: &mut CPU) {
         (sp_val ^ r8 ^ new_val) & 0x10 != 0,
         (sp_val ^ r8 ^ new_val) & 0x100 != 0,
     );
+
+    4
 }
 
-pub fn ld_hl_sp_r8(cpu: &mut CPU) {
+pub fn ld_hl_sp_r8(cpu: &mut CPU) -> u8 {
     let sp_val = i32::from(cpu.registers.sp());
     let r8 = i32::from(cpu.fetch_byte() as i8);
     let new_val = sp_val.wrapping_add(r8);
@@ -66,18 +78,24 @@ pub fn ld_hl_sp_r8(cpu: &mut CPU) {
         (sp_val ^ r8 ^ new_val) & 0x10 != 0,
         (sp_val ^ r8 ^ new_val) & 0x100 != 0,
     );
+
+    3
 }
 
-pub fn push(cpu: &mut CPU, register: Register16bits) {
+pub fn push(cpu: &mut CPU, register: Register16bits) -> u8 {
     let data = cpu.registers.read_16b(&register);
 
     cpu.push_to_stack(data);
+
+    4
 }
 
-pub fn pop(cpu: &mut CPU, register: Register16bits) {
+pub fn pop(cpu: &mut CPU, register: Register16bits) -> u8 {
     let data = cpu.pop_from_stack();
 
     cpu.registers.write_16b(&register, data);
+
+    3
 }
 
 #[cfg(test)]
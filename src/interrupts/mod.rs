@@ -27,6 +27,12 @@ pub struct InterruptRegister {
     values: HashMap<InterruptKind, bool>,
 }
 
+impl Default for InterruptRegister {
+    fn default() -> InterruptRegister {
+        InterruptRegister::new()
+    }
+}
+
 impl InterruptRegister {
     pub fn new() -> InterruptRegister {
         let mut values: HashMap<InterruptKind, bool> = HashMap::new();
@@ -98,6 +104,12 @@ pub struct Interrupts {
     pending: InterruptRegister,
 }
 
+impl Default for Interrupts {
+    fn default() -> Interrupts {
+        Interrupts::new()
+    }
+}
+
 impl Interrupts {
     pub fn new() -> Interrupts {
         Interrupts {
@@ -132,6 +144,10 @@ impl Interrupts {
         self.pending = InterruptRegister::from(value);
     }
 
+    pub fn request(&mut self, kind: &InterruptKind) {
+        self.pending.set(kind, true);
+    }
+
     pub fn isr_of_first_pending(&mut self) -> Option<u16> {
         for kind in SORTED_INTERRUPT_KINDS.iter() {
             if self.is_enabled(kind) && self.is_pending(kind) {
@@ -143,7 +159,15 @@ impl Interrupts {
         None
     }
 
-    fn is_pending(&self, kind: &InterruptKind) -> bool {
+    /// Returns whether there is an interrupt that is both requested and
+    /// enabled, regardless of whether the CPU is going to attend it.
+    pub fn any_pending(&self) -> bool {
+        SORTED_INTERRUPT_KINDS
+            .iter()
+            .any(|kind| self.is_enabled(kind) && self.is_pending(kind))
+    }
+
+    pub fn is_pending(&self, kind: &InterruptKind) -> bool {
         self.pending.get(&kind)
     }
 
@@ -176,6 +200,16 @@ mod tests {
         assert_eq!(interrupts.if_value(), value)
     }
 
+    #[test]
+    fn request_interrupt() {
+        let mut interrupts = Interrupts::new();
+        interrupts.add_interrupts(0b00000001);
+
+        interrupts.request(&InterruptKind::TIMER);
+
+        assert_eq!(interrupts.if_value(), 0b00000101)
+    }
+
     #[test]
     fn isr_of_first_pending() {
         // We are going to add interrupts for "lcdstat" and "serial",
@@ -192,6 +226,18 @@ mod tests {
         assert_eq!(isr, 0x58)
     }
 
+    #[test]
+    fn any_pending_only_considers_enabled_interrupts() {
+        let mut interrupts = Interrupts::new();
+        interrupts.add_interrupts(0b00000010);
+
+        assert!(!interrupts.any_pending());
+
+        interrupts.enable_or_disable_interrupts(0b00000010);
+
+        assert!(interrupts.any_pending());
+    }
+
     #[test]
     fn is_of_first_pending_when_no_pending() {
         let mut interrupts = Interrupts::new();
@@ -0,0 +1,185 @@
+pub const P1_ADDR: u16 = 0xFF00;
+
+// P1 bits. A 0 in a select bit selects that group of buttons, and a 0 in the
+// lower 4 bits means that the button is pressed.
+const SELECT_DIRECTIONS: u8 = 0b0001_0000;
+const SELECT_BUTTONS: u8 = 0b0010_0000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Button {
+    Right,
+    Left,
+    Up,
+    Down,
+    A,
+    B,
+    Select,
+    Start,
+}
+
+impl Button {
+    // Bit in the lower 4 bits of P1, and whether the button is in the
+    // directions group.
+    fn bit(self) -> (u8, bool) {
+        match self {
+            Button::Right => (0b0001, true),
+            Button::Left => (0b0010, true),
+            Button::Up => (0b0100, true),
+            Button::Down => (0b1000, true),
+            Button::A => (0b0001, false),
+            Button::B => (0b0010, false),
+            Button::Select => (0b0100, false),
+            Button::Start => (0b1000, false),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Joypad {
+    select: u8,
+
+    // Pressed buttons, 1 meaning pressed.
+    directions: u8,
+    buttons: u8,
+}
+
+impl Default for Joypad {
+    fn default() -> Joypad {
+        Joypad::new()
+    }
+}
+
+impl Joypad {
+    pub fn new() -> Joypad {
+        Joypad {
+            select: SELECT_DIRECTIONS | SELECT_BUTTONS,
+            directions: 0,
+            buttons: 0,
+        }
+    }
+
+    /// Bits 6 and 7 are unused and read as 1.
+    pub fn read(&self) -> u8 {
+        0xC0 | self.select | self.input_lines()
+    }
+
+    /// Only the select bits can be written. Returns whether the joypad
+    /// interrupt must be requested.
+    pub fn write(&mut self, value: u8) -> bool {
+        self.update(|joypad| joypad.select = value & (SELECT_DIRECTIONS | SELECT_BUTTONS))
+    }
+
+    /// Returns whether the joypad interrupt must be requested.
+    pub fn press(&mut self, button: Button) -> bool {
+        let (bit, direction) = button.bit();
+
+        self.update(|joypad| {
+            if direction {
+                joypad.directions |= bit;
+            } else {
+                joypad.buttons |= bit;
+            }
+        })
+    }
+
+    pub fn release(&mut self, button: Button) {
+        let (bit, direction) = button.bit();
+
+        if direction {
+            self.directions &= !bit;
+        } else {
+            self.buttons &= !bit;
+        }
+    }
+
+    // The interrupt is requested when any of the input lines goes from high
+    // to low.
+    fn update<F: FnOnce(&mut Joypad)>(&mut self, change: F) -> bool {
+        let before = self.input_lines();
+        change(self);
+        let after = self.input_lines();
+
+        before & !after != 0
+    }
+
+    // Lower 4 bits of P1. When both groups are selected, a line is low if a
+    // button of either group is pressed.
+    fn input_lines(&self) -> u8 {
+        let mut pressed = 0;
+
+        if self.select & SELECT_DIRECTIONS == 0 {
+            pressed |= self.directions;
+        }
+
+        if self.select & SELECT_BUTTONS == 0 {
+            pressed |= self.buttons;
+        }
+
+        !pressed & 0x0F
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nothing_selected_reads_0xff() {
+        let mut joypad = Joypad::new();
+
+        joypad.press(Button::A);
+
+        assert_eq!(joypad.read(), 0xFF);
+    }
+
+    #[test]
+    fn read_directions() {
+        let mut joypad = Joypad::new();
+        joypad.write(SELECT_BUTTONS);
+
+        joypad.press(Button::Up);
+        joypad.press(Button::A);
+
+        assert_eq!(joypad.read(), 0xEB);
+    }
+
+    #[test]
+    fn read_buttons() {
+        let mut joypad = Joypad::new();
+        joypad.write(SELECT_DIRECTIONS);
+
+        joypad.press(Button::Start);
+        joypad.press(Button::Left);
+
+        assert_eq!(joypad.read(), 0xD7);
+    }
+
+    #[test]
+    fn release() {
+        let mut joypad = Joypad::new();
+        joypad.write(SELECT_DIRECTIONS);
+        joypad.press(Button::B);
+
+        joypad.release(Button::B);
+
+        assert_eq!(joypad.read(), 0xDF);
+    }
+
+    #[test]
+    fn interrupt_on_press_of_selected_button() {
+        let mut joypad = Joypad::new();
+        joypad.write(SELECT_DIRECTIONS);
+
+        assert!(!joypad.press(Button::Down));
+        assert!(joypad.press(Button::A));
+        assert!(!joypad.press(Button::A));
+    }
+
+    #[test]
+    fn interrupt_on_select_with_button_pressed() {
+        let mut joypad = Joypad::new();
+        joypad.press(Button::Right);
+
+        assert!(joypad.write(SELECT_BUTTONS));
+    }
+}
@@ -0,0 +1,11 @@
+pub mod apu;
+pub mod cartridge;
+pub mod cpu;
+pub mod interrupts;
+pub mod joypad;
+pub mod memory;
+pub mod model;
+pub mod ppu;
+pub mod printer;
+pub mod serial;
+pub mod timer;
@@ -1,27 +1,285 @@
-mod cpu;
-mod interrupts;
-mod memory;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use std::fs::File;
-use std::io::prelude::*;
+use gebers::cartridge::{Cartridge, CgbSupport};
+use gebers::cpu::CPU;
+use gebers::memory::{BootRom, Memory};
+use gebers::model::{ManualPalette, Model};
+use gebers::ppu::Renderer;
+use gebers::printer::Printer;
+use gebers::serial::{SocketLink, StdoutSink};
+
+// How often, in M-cycles, the battery-backed RAM is written to disk if it
+// changed. This is about one second of emulated time.
+const SAVE_INTERVAL: u32 = 1_048_576;
+
+const USAGE: &str =
+    "Usage: gebers [--pixel-fifo] [--model <model>] [--boot-rom <file>] [--dmg-palette <palette>] [--link-listen <address> | --link-connect <address> | --printer <directory>] <rom>
+
+Link cable addresses are host:port for TCP, or a path for a Unix socket.
+Printouts of the Game Boy Printer are saved as PNG files in the directory.
+The model is one of dmg0, dmg, mgb, sgb, sgb2, cgb or agb. By default, it is
+the one the boot ROM is for, or cgb for CGB games and dmg for the rest.
+The DMG palette colours DMG games on a CGB, like holding the buttons at boot:
+up, down, left or right, optionally followed by +a or +b.";
 
 fn main() {
-    let test_rom = "some_path"; // TODO
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    // The pixel FIFO renderer is slower, but needed by games and demos that
+    // change the PPU registers in the middle of a line.
+    let renderer = if take_flag(&mut args, "--pixel-fifo") {
+        Renderer::PixelFifo
+    } else {
+        Renderer::Scanline
+    };
+
+    let link_listen = take_option(&mut args, "--link-listen");
+    let link_connect = take_option(&mut args, "--link-connect");
+    let printer_dir = take_option(&mut args, "--printer");
+    let boot_rom_path = take_option(&mut args, "--boot-rom");
+
+    let model_name = take_option(&mut args, "--model");
+    let dmg_palette_name = take_option(&mut args, "--dmg-palette");
+
+    let rom_path = match args.first() {
+        Some(path) => path.clone(),
+        None => {
+            eprintln!("{}", USAGE);
+            process::exit(1);
+        }
+    };
+
+    let rom = fs::read(&rom_path).unwrap_or_else(|err| {
+        eprintln!("Could not read {}: {}", rom_path, err);
+        process::exit(1);
+    });
+
+    let mut cartridge = Cartridge::new(rom).unwrap_or_else(|err| {
+        eprintln!("Invalid ROM {}: {}", rom_path, err);
+        process::exit(1);
+    });
+
+    eprint!("{}", cartridge.header());
+
+    if let Err(err) = cartridge.verify_global_checksum() {
+        eprintln!("Warning: {}", err);
+    }
+
+    let boot_rom = boot_rom_path.map(|path| load_boot_rom(&path));
+
+    // The boot ROM runs on the model it was made for. Without one, CGB games
+    // run on a CGB unless another model is chosen.
+    let model = match (model_name, &boot_rom) {
+        (Some(name), _) => name.parse().unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        }),
+        (None, Some(boot_rom)) if boot_rom.is_cgb() => Model::Cgb,
+        (None, Some(_)) => Model::default(),
+        (None, None) if cartridge.header().cgb_support != CgbSupport::DmgOnly => Model::Cgb,
+        (None, None) => Model::default(),
+    };
+
+    if let Some(boot_rom) = &boot_rom {
+        if boot_rom.is_cgb() != model.is_cgb() {
+            let kind = if boot_rom.is_cgb() { "CGB" } else { "DMG" };
+            eprintln!("A {} boot ROM can not run on the {:?} model", kind, model);
+            process::exit(1);
+        }
+    }
+
+    let dmg_palette = dmg_palette_name.map(|name| {
+        name.parse::<ManualPalette>().unwrap_or_else(|err| {
+            eprintln!("{}", err);
+            process::exit(1);
+        })
+    });
 
-    let f = File::open(test_rom).unwrap();
-    let mut rom = Vec::new();
-    for byte in f.bytes() {
-        rom.push(byte.unwrap());
+    // The save file goes next to the ROM, like in other emulators.
+    let save_path = Path::new(&rom_path).with_extension("sav");
+
+    if cartridge.has_battery() {
+        load_save_file(&mut cartridge, &save_path);
     }
 
-    let mut memory = memory::Memory::new();
-    for (i, code) in rom.iter().enumerate() {
-        memory.write_byte(i as u16, *code);
+    let mut memory = Memory::with_cartridge_and_renderer(cartridge, renderer);
+
+    // Blargg's test ROMs send everything that is printed on the screen to
+    // the serial port.
+    let link = match (link_listen, link_connect) {
+        (Some(address), _) => Some(link_cable(&address, true)),
+        (None, Some(address)) => Some(link_cable(&address, false)),
+        (None, None) => None,
+    };
+
+    match (link, printer_dir) {
+        (Some(link), _) => memory.connect_serial(Box::new(link)),
+        (None, Some(dir)) => memory.connect_serial(Box::new(Printer::with_output_dir(dir))),
+        (None, None) => memory.connect_serial(Box::new(StdoutSink)),
     }
 
-    let mut cpu = cpu::CPU::new_at_0x100(&mut memory);
+    let mut cpu = match boot_rom {
+        Some(boot_rom) => {
+            memory.load_boot_rom(boot_rom);
+            memory.set_model(model);
+            CPU::new_at_0x0000(&mut memory)
+        }
+        None => CPU::new_at_0x100(&mut memory, model),
+    };
+
+    // Replaces the palettes chosen from the title, which the boot ROM does
+    // when buttons are held.
+    if let Some(dmg_palette) = dmg_palette {
+        let memory = cpu.memory();
+
+        if memory.model().is_cgb() && !memory.cgb_mode() && !memory.boot_rom_mapped() {
+            memory.ppu.set_dmg_palettes(&dmg_palette.palettes());
+        } else {
+            eprintln!(
+                "Warning: --dmg-palette only applies to DMG games on a CGB without a boot ROM"
+            );
+        }
+    }
 
-    loop {
-        cpu.run_next_instruction();
+    // Ctrl-C and termination signals stop the emulation, so that the last
+    // changes to the save file are not lost.
+    let running = Arc::new(AtomicBool::new(true));
+    let handler_running = Arc::clone(&running);
+    if let Err(err) = ctrlc::set_handler(move || handler_running.store(false, Ordering::Relaxed)) {
+        eprintln!("Warning: could not set the signal handler: {}", err);
     }
+
+    let mut cycles_since_save = 0;
+
+    while running.load(Ordering::Relaxed) {
+        cycles_since_save += u32::from(cpu.run_next_instruction());
+
+        if cycles_since_save >= SAVE_INTERVAL {
+            cycles_since_save = 0;
+            save(cpu.memory(), &save_path);
+
+            // There is no force feedback output yet, so the motor changes
+            // are thrown away.
+            if let Some(cartridge) = cpu.memory().cartridge_mut() {
+                cartridge.take_rumble_events();
+            }
+        }
+    }
+
+    save(cpu.memory(), &save_path);
+}
+
+fn save(memory: &mut Memory, save_path: &Path) {
+    if let Some(cartridge) = memory.cartridge_mut() {
+        if cartridge.has_battery() && cartridge.ram_modified() {
+            write_save_file(cartridge, save_path);
+        }
+    }
+}
+
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    match args.iter().position(|arg| arg == name) {
+        Some(index) => {
+            args.remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let index = args.iter().position(|arg| arg == name)?;
+
+    if index + 1 >= args.len() {
+        eprintln!("{}", USAGE);
+        process::exit(1);
+    }
+
+    args.remove(index);
+    Some(args.remove(index))
+}
+
+// Addresses with a slash are paths of Unix sockets.
+fn link_cable(address: &str, listen: bool) -> SocketLink {
+    if listen {
+        eprintln!(
+            "Waiting for the other side of the link cable on {}",
+            address
+        );
+    }
+
+    let link = match (address.contains('/'), listen) {
+        #[cfg(unix)]
+        (true, true) => SocketLink::listen_unix(address),
+        #[cfg(unix)]
+        (true, false) => SocketLink::connect_unix(address),
+        (_, true) => SocketLink::listen_tcp(address),
+        (_, false) => SocketLink::connect_tcp(address),
+    };
+
+    link.unwrap_or_else(|err| {
+        eprintln!("Could not connect the link cable to {}: {}", address, err);
+        process::exit(1);
+    })
+}
+
+fn load_boot_rom(path: &str) -> BootRom {
+    let data = fs::read(path).unwrap_or_else(|err| {
+        eprintln!("Could not read {}: {}", path, err);
+        process::exit(1);
+    });
+
+    BootRom::new(data).unwrap_or_else(|err| {
+        eprintln!("Invalid boot ROM {}: {}", path, err);
+        process::exit(1);
+    })
+}
+
+fn load_save_file(cartridge: &mut Cartridge, save_path: &Path) {
+    let data = match fs::read(save_path) {
+        Ok(data) => data,
+        Err(ref err) if err.kind() == ErrorKind::NotFound => return,
+        Err(err) => {
+            eprintln!("Could not read {}: {}", save_path.display(), err);
+            process::exit(1);
+        }
+    };
+
+    match cartridge.load_save_data(&data) {
+        // The clock keeps running while the game is not being played.
+        Ok(Some(timestamp)) => {
+            let elapsed = unix_timestamp().saturating_sub(timestamp);
+
+            if let Some(rtc) = cartridge.rtc_mut() {
+                rtc.fast_forward(elapsed);
+            }
+        }
+        Ok(None) => (),
+        Err(err) => {
+            eprintln!("Invalid save file {}: {}", save_path.display(), err);
+            process::exit(1);
+        }
+    }
+}
+
+fn write_save_file(cartridge: &mut Cartridge, save_path: &Path) {
+    let data = cartridge.save_data(unix_timestamp());
+
+    if let Err(err) = fs::write(save_path, data) {
+        eprintln!("Could not write {}: {}", save_path.display(), err);
+    }
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
 }
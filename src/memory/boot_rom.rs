@@ -0,0 +1,101 @@
+use std::error::Error;
+use std::fmt;
+
+pub const BOOT_ROM_DISABLE_ADDR: u16 = 0xFF50;
+
+pub const DMG_BOOT_ROM_SIZE: usize = 0x100;
+pub const CGB_BOOT_ROM_SIZE: usize = 0x900;
+
+// The CGB boot ROM has a hole where the cartridge header is, so that it can
+// read it.
+const CGB_HEADER_BEGIN: u16 = 0x100;
+const CGB_HEADER_END: u16 = 0x1FF;
+
+#[derive(Debug, PartialEq)]
+pub enum BootRomError {
+    InvalidSize(usize),
+}
+
+impl fmt::Display for BootRomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BootRomError::InvalidSize(size) => write!(
+                f,
+                "invalid boot ROM size: expected {} or {} bytes, got {}",
+                DMG_BOOT_ROM_SIZE, CGB_BOOT_ROM_SIZE, size
+            ),
+        }
+    }
+}
+
+impl Error for BootRomError {}
+
+/// The program that runs when the Game Boy is turned on. It is mapped over
+/// the beginning of the cartridge ROM until it writes to 0xFF50.
+#[derive(Clone)]
+pub struct BootRom {
+    data: Vec<u8>,
+}
+
+impl BootRom {
+    /// Creates a boot ROM from a DMG (256 bytes) or CGB (2304 bytes) image.
+    pub fn new(data: Vec<u8>) -> Result<BootRom, BootRomError> {
+        match data.len() {
+            DMG_BOOT_ROM_SIZE | CGB_BOOT_ROM_SIZE => Ok(BootRom { data }),
+            size => Err(BootRomError::InvalidSize(size)),
+        }
+    }
+
+    pub fn is_cgb(&self) -> bool {
+        self.data.len() == CGB_BOOT_ROM_SIZE
+    }
+
+    /// Whether the address is mapped to the boot ROM instead of the
+    /// cartridge.
+    pub fn maps(&self, address: u16) -> bool {
+        let in_header = self.is_cgb() && (CGB_HEADER_BEGIN..=CGB_HEADER_END).contains(&address);
+
+        usize::from(address) < self.data.len() && !in_header
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        self.data[usize::from(address)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_size() {
+        assert_eq!(
+            BootRom::new(vec![0; 0x200]).err(),
+            Some(BootRomError::InvalidSize(0x200))
+        );
+    }
+
+    #[test]
+    fn dmg_mapping() {
+        let boot_rom = BootRom::new(vec![0; DMG_BOOT_ROM_SIZE]).unwrap();
+
+        assert!(!boot_rom.is_cgb());
+        assert!(boot_rom.maps(0x0000));
+        assert!(boot_rom.maps(0x00FF));
+        assert!(!boot_rom.maps(0x0100));
+        assert!(!boot_rom.maps(0x0200));
+    }
+
+    #[test]
+    fn cgb_mapping_skips_the_header() {
+        let boot_rom = BootRom::new(vec![0; CGB_BOOT_ROM_SIZE]).unwrap();
+
+        assert!(boot_rom.is_cgb());
+        assert!(boot_rom.maps(0x00FF));
+        assert!(!boot_rom.maps(0x0100));
+        assert!(!boot_rom.maps(0x01FF));
+        assert!(boot_rom.maps(0x0200));
+        assert!(boot_rom.maps(0x08FF));
+        assert!(!boot_rom.maps(0x0900));
+    }
+}
@@ -0,0 +1,140 @@
+pub const DMA_ADDR: u16 = 0xFF46;
+
+const OAM_SIZE: usize = 0xA0;
+
+// Sources from 0xE000 read the work RAM, like echo RAM.
+const ECHO_RAM_BEGIN: u16 = 0xE000;
+const ECHO_RAM_OFFSET: u16 = 0x2000;
+
+/// OAM DMA copies 160 bytes to OAM, one per M-cycle, starting one M-cycle
+/// after 0xFF46 is written. While it runs, the CPU can only use HRAM and the
+/// I/O registers.
+#[derive(Clone)]
+pub struct OamDma {
+    // The last value written to 0xFF46.
+    register: u8,
+
+    source: u16,
+    index: usize,
+    active: bool,
+
+    // Source of a transfer that starts in the next M-cycle. If a transfer is
+    // running, it continues until then, so OAM stays blocked when DMA is
+    // restarted.
+    pending: Option<u16>,
+}
+
+impl Default for OamDma {
+    fn default() -> OamDma {
+        OamDma::new()
+    }
+}
+
+impl OamDma {
+    pub fn new() -> OamDma {
+        OamDma {
+            register: 0xFF,
+            source: 0,
+            index: 0,
+            active: false,
+            pending: None,
+        }
+    }
+
+    pub fn read(&self) -> u8 {
+        self.register
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.register = value;
+
+        let source = u16::from(value) << 8;
+        self.pending = Some(if source >= ECHO_RAM_BEGIN {
+            source - ECHO_RAM_OFFSET
+        } else {
+            source
+        });
+    }
+
+    /// Whether a transfer is running, so the CPU can not use the bus.
+    pub fn active(&self) -> bool {
+        self.active
+    }
+
+    /// Advances one M-cycle. Returns the address to read and the OAM index to
+    /// write it to, if a byte is copied in this cycle.
+    pub fn step(&mut self) -> Option<(u16, usize)> {
+        let transfer = if self.active {
+            let index = self.index;
+            self.index += 1;
+            self.active = self.index < OAM_SIZE;
+
+            Some((self.source + index as u16, index))
+        } else {
+            None
+        };
+
+        if let Some(source) = self.pending.take() {
+            self.source = source;
+            self.index = 0;
+            self.active = true;
+        }
+
+        transfer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_one_cycle_after_the_write() {
+        let mut dma = OamDma::new();
+
+        dma.write(0xC1);
+
+        assert_eq!(dma.step(), None);
+        assert!(dma.active());
+        assert_eq!(dma.step(), Some((0xC100, 0)));
+        assert_eq!(dma.read(), 0xC1);
+    }
+
+    #[test]
+    fn copies_160_bytes() {
+        let mut dma = OamDma::new();
+        dma.write(0xC0);
+        dma.step();
+
+        for index in 0..OAM_SIZE {
+            assert_eq!(dma.step(), Some((0xC000 + index as u16, index)));
+        }
+
+        assert!(!dma.active());
+        assert_eq!(dma.step(), None);
+    }
+
+    #[test]
+    fn high_sources_read_work_ram() {
+        let mut dma = OamDma::new();
+        dma.write(0xFE);
+        dma.step();
+
+        assert_eq!(dma.step(), Some((0xDE00, 0)));
+    }
+
+    #[test]
+    fn restart_continues_old_transfer_for_a_cycle() {
+        let mut dma = OamDma::new();
+        dma.write(0xC0);
+        for _ in 0..11 {
+            dma.step();
+        }
+
+        dma.write(0xD0);
+
+        assert_eq!(dma.step(), Some((0xC00A, 10)));
+        assert!(dma.active());
+        assert_eq!(dma.step(), Some((0xD000, 0)));
+    }
+}
@@ -0,0 +1,210 @@
+pub const HDMA1_ADDR: u16 = 0xFF51;
+pub const HDMA2_ADDR: u16 = 0xFF52;
+pub const HDMA3_ADDR: u16 = 0xFF53;
+pub const HDMA4_ADDR: u16 = 0xFF54;
+pub const HDMA5_ADDR: u16 = 0xFF55;
+
+pub const BLOCK_SIZE: u16 = 0x10;
+
+// HDMA5 bits
+const HBLANK_MODE: u8 = 0b1000_0000;
+const LENGTH_MASK: u8 = 0b0111_1111;
+
+const VRAM_BEGIN: u16 = 0x8000;
+const DESTINATION_MASK: u16 = 0x1FF0;
+
+/// CGB VRAM DMA copies blocks of 16 bytes to VRAM. A general purpose
+/// transfer copies every block at once, while an HBlank transfer copies one
+/// block at the beginning of each HBlank. The CPU is stopped while a block
+/// is copied.
+#[derive(Clone)]
+pub struct VramDma {
+    source: u16,
+    // Offset in VRAM.
+    destination: u16,
+
+    // Blocks left minus one, as read from HDMA5. It wraps to 0x7F when the
+    // transfer finishes.
+    remaining: u8,
+    active: bool,
+    hblank_mode: bool,
+
+    // Whether an HBlank transfer can copy a block in the current HBlank.
+    block_pending: bool,
+}
+
+impl Default for VramDma {
+    fn default() -> VramDma {
+        VramDma::new()
+    }
+}
+
+impl VramDma {
+    pub fn new() -> VramDma {
+        VramDma {
+            source: 0,
+            destination: 0,
+            remaining: LENGTH_MASK,
+            active: false,
+            hblank_mode: false,
+            block_pending: false,
+        }
+    }
+
+    // The source and the destination can not be read back.
+    pub fn read(&self, address: u16) -> u8 {
+        if address == HDMA5_ADDR {
+            let inactive = if self.active { 0 } else { HBLANK_MODE };
+            inactive | self.remaining
+        } else {
+            0xFF
+        }
+    }
+
+    /// `in_hblank` tells whether the PPU is in HBlank, so that an HBlank
+    /// transfer started then copies its first block right away.
+    pub fn write(&mut self, address: u16, value: u8, in_hblank: bool) {
+        match address {
+            HDMA1_ADDR => self.source = (self.source & 0x00FF) | (u16::from(value) << 8),
+            HDMA2_ADDR => self.source = (self.source & 0xFF00) | u16::from(value & 0xF0),
+            HDMA3_ADDR => {
+                self.destination =
+                    ((self.destination & 0x00FF) | (u16::from(value) << 8)) & DESTINATION_MASK
+            }
+            HDMA4_ADDR => self.destination = (self.destination & 0xFF00) | u16::from(value & 0xF0),
+            _ => self.write_control(value, in_hblank),
+        }
+    }
+
+    fn write_control(&mut self, value: u8, in_hblank: bool) {
+        // Clearing bit 7 during an HBlank transfer cancels it. The remaining
+        // length can still be read.
+        if self.active && self.hblank_mode && value & HBLANK_MODE == 0 {
+            self.active = false;
+            return;
+        }
+
+        self.remaining = value & LENGTH_MASK;
+        self.active = true;
+        self.hblank_mode = value & HBLANK_MODE != 0;
+        self.block_pending = in_hblank;
+    }
+
+    /// Called when the PPU enters HBlank.
+    pub fn hblank(&mut self) {
+        self.block_pending = true;
+    }
+
+    /// Whether a block has to be copied now, stopping the CPU.
+    pub fn pending(&self) -> bool {
+        self.active && (!self.hblank_mode || self.block_pending)
+    }
+
+    /// Returns the source and destination addresses of the next block, and
+    /// advances the transfer.
+    pub fn next_block(&mut self) -> (u16, u16) {
+        let block = (self.source, VRAM_BEGIN + self.destination);
+
+        self.source = self.source.wrapping_add(BLOCK_SIZE);
+        self.destination = (self.destination + BLOCK_SIZE) & DESTINATION_MASK;
+        self.remaining = self.remaining.wrapping_sub(1) & LENGTH_MASK;
+        self.block_pending = false;
+
+        // The transfer also stops if the destination passes the end of VRAM.
+        if self.remaining == LENGTH_MASK || self.destination == 0 {
+            self.remaining = LENGTH_MASK;
+            self.active = false;
+        }
+
+        block
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start(dma: &mut VramDma, source: u16, destination: u16, control: u8) {
+        dma.write(HDMA1_ADDR, (source >> 8) as u8, false);
+        dma.write(HDMA2_ADDR, source as u8, false);
+        dma.write(HDMA3_ADDR, (destination >> 8) as u8, false);
+        dma.write(HDMA4_ADDR, destination as u8, false);
+        dma.write(HDMA5_ADDR, control, false);
+    }
+
+    #[test]
+    fn general_purpose_transfer() {
+        let mut dma = VramDma::new();
+
+        start(&mut dma, 0xC123, 0x8456, 0x01);
+
+        assert!(dma.pending());
+        assert_eq!(dma.read(HDMA5_ADDR), 0x01);
+        assert_eq!(dma.next_block(), (0xC120, 0x8450));
+        assert_eq!(dma.read(HDMA5_ADDR), 0x00);
+        assert_eq!(dma.next_block(), (0xC130, 0x8460));
+        assert!(!dma.pending());
+        assert_eq!(dma.read(HDMA5_ADDR), 0xFF);
+    }
+
+    #[test]
+    fn destination_is_always_in_vram() {
+        let mut dma = VramDma::new();
+
+        start(&mut dma, 0x4000, 0xFFF0, 0x00);
+
+        assert_eq!(dma.next_block(), (0x4000, 0x9FF0));
+    }
+
+    #[test]
+    fn hblank_transfer_waits_for_hblank() {
+        let mut dma = VramDma::new();
+
+        start(&mut dma, 0xC000, 0x8000, 0x81);
+
+        assert!(!dma.pending());
+        dma.hblank();
+        assert!(dma.pending());
+        dma.next_block();
+        assert!(!dma.pending());
+        assert_eq!(dma.read(HDMA5_ADDR), 0x00);
+    }
+
+    #[test]
+    fn hblank_transfer_started_in_hblank() {
+        let mut dma = VramDma::new();
+        start(&mut dma, 0xC000, 0x8000, 0x00);
+        dma.next_block();
+
+        dma.write(HDMA5_ADDR, 0x82, true);
+
+        assert!(dma.pending());
+    }
+
+    #[test]
+    fn cancel_hblank_transfer() {
+        let mut dma = VramDma::new();
+        start(&mut dma, 0xC000, 0x8000, 0x83);
+        dma.hblank();
+        dma.next_block();
+
+        dma.write(HDMA5_ADDR, 0x00, false);
+
+        assert!(!dma.pending());
+        assert_eq!(dma.read(HDMA5_ADDR), 0x82);
+        dma.hblank();
+        assert!(!dma.pending());
+    }
+
+    #[test]
+    fn stops_at_the_end_of_vram() {
+        let mut dma = VramDma::new();
+
+        start(&mut dma, 0xC000, 0x9FE0, 0x7F);
+        dma.next_block();
+        dma.next_block();
+
+        assert!(!dma.pending());
+        assert_eq!(dma.read(HDMA5_ADDR), 0xFF);
+    }
+}
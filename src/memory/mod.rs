@@ -1,51 +1,462 @@
-use crate::interrupts::Interrupts;
+use crate::apu::Apu;
+use crate::cartridge::{Cartridge, CGB_FLAG_ADDR};
+use crate::interrupts::{InterruptKind, Interrupts};
 use crate::interrupts::{ENABLED_INTERRUPTS_ADDR, PENDING_INTERRUPTS_ADDR};
+use crate::joypad::{Button, Joypad, P1_ADDR};
+use crate::model::{dmg_palettes, Model};
+use crate::ppu::{Mode, Ppu, Renderer, OAM_BEGIN, OAM_END, VRAM_BEGIN, VRAM_END};
+use crate::serial::{Serial, SerialDevice, SB_ADDR, SC_ADDR};
+use crate::timer::{Timer, DIV_ADDR, TAC_ADDR};
 
+pub use crate::memory::boot_rom::{BootRom, BootRomError, BOOT_ROM_DISABLE_ADDR};
+use crate::memory::dma::{OamDma, DMA_ADDR};
+use crate::memory::hdma::{VramDma, BLOCK_SIZE, HDMA1_ADDR, HDMA5_ADDR};
+
+mod boot_rom;
+mod dma;
+mod hdma;
+
+const ROM_END: u16 = 0x7FFF;
+const EXTERNAL_RAM_BEGIN: u16 = 0xA000;
+const EXTERNAL_RAM_END: u16 = 0xBFFF;
+const WRAM_BEGIN: u16 = 0xC000;
+const WRAM_END: u16 = 0xDFFF;
+const WRAM_BANK_BEGIN: u16 = 0xD000;
+const ECHO_RAM_BEGIN: u16 = 0xE000;
+const ECHO_RAM_END: u16 = 0xFDFF;
+const UNUSABLE_BEGIN: u16 = 0xFEA0;
+const UNUSABLE_END: u16 = 0xFEFF;
 pub const IO_PORTS_BEGIN: usize = 0xFF00;
-const SERIAL_TRANSFER_DATA: usize = 0xFF01;
-const SERIAL_TRANSFER_CONTROL: usize = 0xFF02;
+const HRAM_BEGIN: u16 = 0xFF80;
+const HRAM_END: u16 = 0xFFFE;
+const IE_ADDR: u16 = ENABLED_INTERRUPTS_ADDR as u16;
+
+// CGB registers
+const KEY0_ADDR: u16 = 0xFF4C;
+const KEY1_ADDR: u16 = 0xFF4D;
+const SVBK_ADDR: u16 = 0xFF70;
+
+// KEY1 bits
+const DOUBLE_SPEED: u8 = 0b1000_0000;
+const PREPARE_SPEED_SWITCH: u8 = 0b0000_0001;
 
-const MEMORY_SIZE: usize = 65_536;
+// Written to KEY0 by the CGB boot ROM for DMG games.
+const KEY0_DMG_MODE: u8 = 0b0000_0100;
+
+// There are 8 banks of 4 KiB. Bank 0 is always at 0xC000, and 0xD000 has the
+// bank selected with SVBK in CGB mode, or bank 1 otherwise.
+const WRAM_BANK_SIZE: usize = 0x1000;
+const WRAM_BANKS: usize = 8;
+const HRAM_SIZE: usize = 0x7F;
+
+// The upper 3 bits of IF are not used and read as 1.
+const IF_UNUSED_BITS: u8 = 0xE0;
 
-#[derive(Clone)]
 pub struct Memory {
-    mem: [u8; MEMORY_SIZE],
+    wram: [u8; WRAM_BANK_SIZE * WRAM_BANKS],
+    wram_bank: u8,
+    hram: [u8; HRAM_SIZE],
+
+    // Without a cartridge, the ROM and external RAM areas are backed by plain
+    // RAM, so code can be written directly into memory. Tests rely on this.
+    cartridge: Option<Cartridge>,
+    no_cartridge_ram: Vec<u8>,
+
+    // Mapped over the cartridge until 0xFF50 is written.
+    boot_rom: Option<BootRom>,
+
+    dma: OamDma,
+    hdma: VramDma,
+
+    model: Model,
+    cgb_mode: bool,
+
+    // In double speed mode, the CPU, the timer, the serial port and OAM DMA
+    // run twice as fast as the rest.
+    double_speed: bool,
+    speed_switch_prepared: bool,
+    odd_double_speed_cycle: bool,
+
     pub interrupts: Interrupts,
+    timer: Timer,
+    pub ppu: Ppu,
+    pub apu: Apu,
+    joypad: Joypad,
+    serial: Serial,
+}
+
+impl Default for Memory {
+    fn default() -> Memory {
+        Memory::new()
+    }
 }
 
 impl Memory {
     pub fn new() -> Memory {
+        Memory::with_renderer(Renderer::Scanline)
+    }
+
+    pub fn with_renderer(renderer: Renderer) -> Memory {
         Memory {
-            mem: [0; MEMORY_SIZE],
+            wram: [0; WRAM_BANK_SIZE * WRAM_BANKS],
+            wram_bank: 0,
+            hram: [0; HRAM_SIZE],
+            cartridge: None,
+            no_cartridge_ram: vec![0; usize::from(EXTERNAL_RAM_END) + 1],
+            boot_rom: None,
+            dma: OamDma::new(),
+            hdma: VramDma::new(),
+            model: Model::default(),
+            cgb_mode: false,
+            double_speed: false,
+            speed_switch_prepared: false,
+            odd_double_speed_cycle: false,
             interrupts: Interrupts::new(),
+            timer: Timer::new(),
+            ppu: Ppu::with_renderer(renderer),
+            apu: Apu::new(),
+            joypad: Joypad::new(),
+            serial: Serial::new(),
         }
     }
 
+    pub fn with_cartridge(cartridge: Cartridge) -> Memory {
+        Memory::with_cartridge_and_renderer(cartridge, Renderer::Scanline)
+    }
+
+    pub fn with_cartridge_and_renderer(cartridge: Cartridge, renderer: Renderer) -> Memory {
+        let mut memory = Memory::with_renderer(renderer);
+        memory.cartridge = Some(cartridge);
+        memory
+    }
+
+    /// Maps a boot ROM over the beginning of the address space. The CPU
+    /// should then start running at 0x0000.
+    pub fn load_boot_rom(&mut self, boot_rom: BootRom) {
+        self.boot_rom = Some(boot_rom);
+    }
+
+    /// Sets the I/O registers to the values that the boot ROM of the model
+    /// leaves, for when the cartridge is run without it.
+    pub fn skip_boot_rom(&mut self, model: Model) {
+        self.set_model(model);
+
+        // The CGB boot ROM colours DMG games.
+        if model.is_cgb() && !self.cgb_mode {
+            let palettes = dmg_palettes(self);
+            self.ppu.set_dmg_palettes(&palettes);
+        }
+
+        for (address, value) in model.post_boot_io_registers() {
+            self.write_byte(address, value);
+        }
+
+        self.timer.set_counter(model.post_boot_div_counter());
+    }
+
+    /// Sets the model of the Game Boy. CGB mode is enabled on the CGB and
+    /// AGB for cartridges that support it. When there is a boot ROM, it
+    /// starts in CGB mode and switches to DMG mode itself.
+    pub fn set_model(&mut self, model: Model) {
+        let cgb_cartridge = self.read_bus(CGB_FLAG_ADDR as u16) & 0x80 != 0;
+
+        self.model = model;
+        self.set_cgb_mode(model.is_cgb() && (cgb_cartridge || self.boot_rom_mapped()));
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    pub fn cgb_mode(&self) -> bool {
+        self.cgb_mode
+    }
+
+    fn set_cgb_mode(&mut self, cgb_mode: bool) {
+        self.cgb_mode = cgb_mode;
+        self.ppu.set_cgb_mode(cgb_mode);
+        self.ppu
+            .set_dmg_compatibility(self.model.is_cgb() && !cgb_mode);
+
+        if !cgb_mode {
+            self.wram_bank = 0;
+        }
+    }
+
+    pub fn double_speed(&self) -> bool {
+        self.double_speed
+    }
+
+    /// Called by STOP. Switches between normal and double speed if it was
+    /// prepared by writing KEY1, and returns whether it did.
+    pub fn switch_speed(&mut self) -> bool {
+        if !self.cgb_mode || !self.speed_switch_prepared {
+            return false;
+        }
+
+        self.double_speed = !self.double_speed;
+        self.speed_switch_prepared = false;
+        true
+    }
+
+    pub fn boot_rom_mapped(&self) -> bool {
+        self.boot_rom.is_some()
+    }
+
     pub fn read_byte(&self, address: u16) -> u8 {
-        if address == PENDING_INTERRUPTS_ADDR as u16 {
-            self.interrupts.if_value()
-        } else if address == ENABLED_INTERRUPTS_ADDR as u16 {
-            self.interrupts.ie_value()
-        } else {
-            self.mem[address as usize]
+        // During OAM DMA, the CPU can only read HRAM and the registers.
+        if self.dma.active() && address < IO_PORTS_BEGIN as u16 {
+            return 0xFF;
         }
+
+        self.read_bus(address)
     }
 
     pub fn write_byte(&mut self, address: u16, value: u8) {
-        self.mem[address as usize] = value;
+        if self.dma.active() && address < IO_PORTS_BEGIN as u16 {
+            return;
+        }
+
+        self.write_bus(address, value);
+    }
+
+    fn read_bus(&self, address: u16) -> u8 {
+        match &self.boot_rom {
+            Some(boot_rom) if boot_rom.maps(address) => return boot_rom.read(address),
+            _ => (),
+        }
+
+        match address {
+            0x0000..=ROM_END => match &self.cartridge {
+                Some(cartridge) => cartridge.read_rom(address),
+                None => self.no_cartridge_ram[usize::from(address)],
+            },
+            VRAM_BEGIN..=VRAM_END | OAM_BEGIN..=OAM_END => self.ppu.read(address),
+            EXTERNAL_RAM_BEGIN..=EXTERNAL_RAM_END => match &self.cartridge {
+                Some(cartridge) => cartridge.read_ram(address),
+                None => self.no_cartridge_ram[usize::from(address)],
+            },
+            WRAM_BEGIN..=WRAM_END => self.wram[self.wram_index(address)],
+            ECHO_RAM_BEGIN..=ECHO_RAM_END => {
+                self.wram[self.wram_index(address - (ECHO_RAM_BEGIN - WRAM_BEGIN))]
+            }
+            // The unusable area reads as 0, except when OAM is blocked.
+            UNUSABLE_BEGIN..=UNUSABLE_END => match self.ppu.mode() {
+                Mode::OamScan | Mode::Drawing => 0xFF,
+                Mode::HBlank | Mode::VBlank => 0x00,
+            },
+            HRAM_BEGIN..=HRAM_END => self.hram[usize::from(address - HRAM_BEGIN)],
+            IE_ADDR => self.interrupts.ie_value(),
+            _ => self.read_io(address),
+        }
+    }
 
-        if address == PENDING_INTERRUPTS_ADDR as u16 {
+    fn write_bus(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=ROM_END => match &mut self.cartridge {
+                Some(cartridge) => cartridge.write_rom(address, value),
+                None => self.no_cartridge_ram[usize::from(address)] = value,
+            },
+            VRAM_BEGIN..=VRAM_END | OAM_BEGIN..=OAM_END => self.ppu.write(address, value),
+            EXTERNAL_RAM_BEGIN..=EXTERNAL_RAM_END => match &mut self.cartridge {
+                Some(cartridge) => cartridge.write_ram(address, value),
+                None => self.no_cartridge_ram[usize::from(address)] = value,
+            },
+            WRAM_BEGIN..=WRAM_END => {
+                let index = self.wram_index(address);
+                self.wram[index] = value;
+            }
+            ECHO_RAM_BEGIN..=ECHO_RAM_END => {
+                let index = self.wram_index(address - (ECHO_RAM_BEGIN - WRAM_BEGIN));
+                self.wram[index] = value;
+            }
+            UNUSABLE_BEGIN..=UNUSABLE_END => (),
+            HRAM_BEGIN..=HRAM_END => self.hram[usize::from(address - HRAM_BEGIN)] = value,
+            IE_ADDR => self.interrupts.enable_or_disable_interrupts(value),
+            _ => self.write_io(address, value),
+        }
+    }
+
+    // Registers from 0xFF00 to 0xFF7F. Unused ones read as 0xFF and ignore
+    // writes.
+    fn read_io(&self, address: u16) -> u8 {
+        if address == P1_ADDR {
+            self.joypad.read()
+        } else if address == PENDING_INTERRUPTS_ADDR as u16 {
+            IF_UNUSED_BITS | self.interrupts.if_value()
+        } else if address == SB_ADDR || address == SC_ADDR {
+            self.serial.read(address)
+        } else if (DIV_ADDR..=TAC_ADDR).contains(&address) {
+            self.timer.read(address)
+        } else if address == DMA_ADDR {
+            self.dma.read()
+        } else if Ppu::handles(address) {
+            self.ppu.read(address)
+        } else if Apu::handles(address) {
+            self.apu.read(address)
+        } else if (HDMA1_ADDR..=HDMA5_ADDR).contains(&address) && self.cgb_mode {
+            self.hdma.read(address)
+        } else if address == KEY1_ADDR && self.cgb_mode {
+            let speed = if self.double_speed { DOUBLE_SPEED } else { 0 };
+            let prepared = if self.speed_switch_prepared {
+                PREPARE_SPEED_SWITCH
+            } else {
+                0
+            };
+
+            0x7E | speed | prepared
+        } else if address == SVBK_ADDR && self.cgb_mode {
+            0xF8 | self.wram_bank
+        } else {
+            0xFF
+        }
+    }
+
+    fn write_io(&mut self, address: u16, value: u8) {
+        if address == P1_ADDR {
+            if self.joypad.write(value) {
+                self.interrupts.request(&InterruptKind::JOYPAD);
+            }
+        } else if address == PENDING_INTERRUPTS_ADDR as u16 {
             self.interrupts.add_interrupts(value);
-        } else if address == ENABLED_INTERRUPTS_ADDR as u16 {
-            self.interrupts.enable_or_disable_interrupts(value);
-        } else if address == SERIAL_TRANSFER_CONTROL as u16 {
-            // Blargg's test roms sent everything that is printed on the screen
-            // to the game link port. That allows us to see the result of the
-            // tests without implementing the graphics part.
-            print!("{}", self.mem[SERIAL_TRANSFER_DATA] as char)
+        } else if (DIV_ADDR..=TAC_ADDR).contains(&address) {
+            self.timer.write(address, value);
+        } else if address == DMA_ADDR {
+            self.dma.write(value);
+        } else if Ppu::handles(address) {
+            self.ppu.write(address, value);
+        } else if Apu::handles(address) {
+            self.apu.write(address, value);
+        } else if address == SB_ADDR || address == SC_ADDR {
+            self.serial.write(address, value);
+        } else if (HDMA1_ADDR..=HDMA5_ADDR).contains(&address) && self.cgb_mode {
+            let in_hblank = self.ppu.mode() == Mode::HBlank;
+            self.hdma.write(address, value, in_hblank);
+        } else if address == KEY1_ADDR && self.cgb_mode {
+            self.speed_switch_prepared = value & PREPARE_SPEED_SWITCH != 0;
+        } else if address == SVBK_ADDR && self.cgb_mode {
+            self.wram_bank = value & 0x07;
+        } else if address == KEY0_ADDR && self.boot_rom_mapped() {
+            // Only the boot ROM can switch to DMG mode.
+            if value & KEY0_DMG_MODE != 0 {
+                self.set_cgb_mode(false);
+            }
+        } else if address == BOOT_ROM_DISABLE_ADDR && value != 0 {
+            // The boot ROM can not be mapped again until the next reset.
+            self.boot_rom = None;
+        }
+    }
+
+    /// Advances the components that are driven by the clock the given number
+    /// of M-cycles.
+    pub fn tick(&mut self, m_cycles: u8) {
+        for _ in 0..m_cycles {
+            if let Some((source, index)) = self.dma.step() {
+                let value = self.read_bus(source);
+                self.ppu.write_oam(index, value);
+            }
+        }
+
+        if self.timer.tick(m_cycles) {
+            self.interrupts.request(&InterruptKind::TIMER);
+        }
+
+        if self.serial.tick(m_cycles) {
+            self.interrupts.request(&InterruptKind::SERIAL);
+        }
+
+        let m_cycles = self.normal_speed_cycles(m_cycles);
+
+        let mode = self.ppu.mode();
+        let ppu_interrupts = self.ppu.tick(m_cycles);
+
+        if mode != Mode::HBlank && self.ppu.mode() == Mode::HBlank {
+            self.hdma.hblank();
+        }
+
+        if ppu_interrupts.vblank {
+            self.interrupts.request(&InterruptKind::VBLANK);
+        }
+
+        if ppu_interrupts.stat {
+            self.interrupts.request(&InterruptKind::LCDSTAT);
+        }
+
+        self.apu.tick(m_cycles);
+
+        if let Some(cartridge) = &mut self.cartridge {
+            cartridge.tick(m_cycles);
         }
     }
 
+    /// Whether VRAM DMA has to copy a block, so the CPU can not run.
+    pub fn vram_dma_pending(&self) -> bool {
+        self.hdma.pending()
+    }
+
+    /// Copies the next block of VRAM DMA and advances the rest of components
+    /// while the CPU is stopped. Returns the M-cycles it took.
+    pub fn run_vram_dma_block(&mut self) -> u8 {
+        let (source, destination) = self.hdma.next_block();
+
+        for offset in 0..BLOCK_SIZE {
+            let value = self.read_bus(source.wrapping_add(offset));
+            self.ppu.write(destination + offset, value);
+        }
+
+        // A block takes 8 M-cycles at normal speed, which are twice as many
+        // CPU M-cycles in double speed mode.
+        let m_cycles = if self.double_speed { 16 } else { 8 };
+        self.tick(m_cycles);
+
+        m_cycles
+    }
+
+    // Converts CPU M-cycles to the M-cycles of the components that do not
+    // run faster in double speed mode.
+    fn normal_speed_cycles(&mut self, m_cycles: u8) -> u8 {
+        if !self.double_speed {
+            return m_cycles;
+        }
+
+        let total = m_cycles + self.odd_double_speed_cycle as u8;
+        self.odd_double_speed_cycle = total & 1 != 0;
+        total / 2
+    }
+
+    // Index in the WRAM banks of an address from 0xC000 to 0xDFFF.
+    fn wram_index(&self, address: u16) -> usize {
+        if address < WRAM_BANK_BEGIN {
+            usize::from(address - WRAM_BEGIN)
+        } else {
+            let bank = usize::from(self.wram_bank.max(1));
+            bank * WRAM_BANK_SIZE + usize::from(address - WRAM_BANK_BEGIN)
+        }
+    }
+
+    /// Connects a device to the serial port, replacing the previous one.
+    pub fn connect_serial(&mut self, device: Box<dyn SerialDevice>) {
+        self.serial.connect(device);
+    }
+
+    pub fn press(&mut self, button: Button) {
+        if self.joypad.press(button) {
+            self.interrupts.request(&InterruptKind::JOYPAD);
+        }
+    }
+
+    pub fn release(&mut self, button: Button) {
+        self.joypad.release(button);
+    }
+
+    pub fn cartridge(&self) -> Option<&Cartridge> {
+        self.cartridge.as_ref()
+    }
+
+    pub fn cartridge_mut(&mut self) -> Option<&mut Cartridge> {
+        self.cartridge.as_mut()
+    }
+
     pub fn read_word(&self, address: u16) -> u16 {
         let low = u16::from(self.read_byte(address));
         let high = u16::from(self.read_byte(address + 1));
@@ -61,3 +472,396 @@ impl Memory {
         self.write_byte(address + 1, high);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::tests::rom_with_header;
+    use crate::cartridge::CartridgeHeader;
+    use crate::serial::BufferSink;
+
+    #[test]
+    fn renderer_is_chosen_at_construction() {
+        let rom = rom_with_header("TETRIS", 0x00, 0x00, 0x00);
+        let cartridge = Cartridge::new(rom).unwrap();
+
+        let mem = Memory::with_cartridge_and_renderer(cartridge, Renderer::PixelFifo);
+
+        assert_eq!(mem.ppu.renderer(), Renderer::PixelFifo);
+    }
+
+    #[test]
+    fn rom_writes_go_to_the_mbc() {
+        let mut rom = rom_with_header("TETRIS", 0x01, 0x01, 0x00);
+        rom[0x4000 * 2] = 0x42;
+        let mut mem = Memory::with_cartridge(Cartridge::new(rom).unwrap());
+
+        mem.write_byte(0x2000, 2);
+
+        assert_eq!(mem.read_byte(0x2000), 0);
+        assert_eq!(mem.read_byte(0x4000), 0x42);
+    }
+
+    #[test]
+    fn external_ram_goes_to_the_cartridge() {
+        let rom = rom_with_header("TETRIS", 0x03, 0x01, 0x02);
+        let mut mem = Memory::with_cartridge(Cartridge::new(rom).unwrap());
+
+        mem.write_byte(0xA000, 0x42);
+        assert_eq!(mem.read_byte(0xA000), 0xFF);
+
+        mem.write_byte(0x0000, 0x0A);
+        mem.write_byte(0xA000, 0x42);
+        assert_eq!(mem.read_byte(0xA000), 0x42);
+    }
+
+    #[test]
+    fn echo_ram_mirrors_wram() {
+        let mut mem = Memory::new();
+
+        mem.write_byte(0xC123, 0x42);
+        mem.write_byte(0xFDFF, 0x24);
+
+        assert_eq!(mem.read_byte(0xE123), 0x42);
+        assert_eq!(mem.read_byte(0xDDFF), 0x24);
+    }
+
+    #[test]
+    fn unusable_area_ignores_writes() {
+        let mut mem = Memory::new();
+
+        mem.write_byte(0xFEA0, 0x42);
+
+        assert_eq!(mem.read_byte(0xFEA0), 0x00);
+    }
+
+    #[test]
+    fn unused_io_registers_read_fixed_bits() {
+        let mut mem = Memory::new();
+
+        mem.write_byte(0xFF03, 0x42);
+        mem.write_byte(0xFF7F, 0x42);
+        mem.write_byte(0xFF0F, 0x01);
+
+        assert_eq!(mem.read_byte(0xFF03), 0xFF);
+        assert_eq!(mem.read_byte(0xFF7F), 0xFF);
+        assert_eq!(mem.read_byte(0xFF0F), 0xE1);
+    }
+
+    #[test]
+    fn hram_and_ie() {
+        let mut mem = Memory::new();
+
+        mem.write_byte(0xFF80, 0x42);
+        mem.write_byte(0xFFFE, 0x24);
+        mem.write_byte(0xFFFF, 0x1F);
+
+        assert_eq!(mem.read_byte(0xFF80), 0x42);
+        assert_eq!(mem.read_byte(0xFFFE), 0x24);
+        assert_eq!(mem.read_byte(0xFFFF), 0x1F);
+    }
+
+    #[test]
+    fn oam_dma_copies_to_oam() {
+        let mut mem = Memory::new();
+        for i in 0..0xA0 {
+            mem.write_byte(0xC100 + i, i as u8);
+        }
+
+        mem.write_byte(0xFF46, 0xC1);
+        mem.tick(1 + 0xA0);
+
+        assert_eq!(mem.read_byte(0xFE00), 0x00);
+        assert_eq!(mem.read_byte(0xFE9F), 0x9F);
+    }
+
+    #[test]
+    fn oam_dma_blocks_everything_but_hram_and_registers() {
+        let mut mem = Memory::new();
+        mem.write_byte(0xC000, 0x42);
+        mem.write_byte(0xFF80, 0x24);
+
+        mem.write_byte(0xFF46, 0xC0);
+        mem.tick(1);
+
+        assert_eq!(mem.read_byte(0xC000), 0xFF);
+        assert_eq!(mem.read_byte(0xFF80), 0x24);
+        assert_eq!(mem.read_byte(0xFF46), 0xC0);
+
+        mem.tick(0xA0);
+
+        assert_eq!(mem.read_byte(0xC000), 0x42);
+    }
+
+    fn cgb_memory() -> Memory {
+        let mut mem = Memory::new();
+        mem.write_byte(0x0143, 0xC0);
+        mem.set_model(Model::Cgb);
+        mem
+    }
+
+    #[test]
+    fn cgb_mode_from_header() {
+        let mut cgb_game = rom_with_header("TETRIS", 0x00, 0x00, 0x00);
+        cgb_game[0x0143] = 0x80;
+        cgb_game[0x014D] = CartridgeHeader::compute_header_checksum(&cgb_game);
+        let dmg_game = rom_with_header("TETRIS", 0x00, 0x00, 0x00);
+
+        let mut on_cgb = Memory::with_cartridge(Cartridge::new(cgb_game.clone()).unwrap());
+        let mut on_dmg = Memory::with_cartridge(Cartridge::new(cgb_game).unwrap());
+        let mut dmg_on_cgb = Memory::with_cartridge(Cartridge::new(dmg_game).unwrap());
+        on_cgb.set_model(Model::Cgb);
+        on_dmg.set_model(Model::Dmg);
+        dmg_on_cgb.set_model(Model::Cgb);
+
+        assert!(on_cgb.cgb_mode());
+        assert!(!on_dmg.cgb_mode());
+        assert!(!dmg_on_cgb.cgb_mode());
+    }
+
+    #[test]
+    fn boot_rom_switches_to_dmg_mode() {
+        let mut mem = Memory::new();
+        mem.load_boot_rom(BootRom::new(vec![0; 0x900]).unwrap());
+        mem.set_model(Model::Cgb);
+        assert!(mem.cgb_mode());
+
+        mem.write_byte(0xFF4C, 0x04);
+        mem.write_byte(0xFF50, 0x11);
+
+        assert!(!mem.cgb_mode());
+    }
+
+    #[test]
+    fn wram_banks() {
+        let mut mem = cgb_memory();
+
+        mem.write_byte(0xD000, 0x11);
+        mem.write_byte(0xFF70, 0x03);
+        mem.write_byte(0xD000, 0x33);
+
+        assert_eq!(mem.read_byte(0xFF70), 0xFB);
+        assert_eq!(mem.read_byte(0xD000), 0x33);
+        assert_eq!(mem.read_byte(0xF000), 0x33);
+
+        // Bank 0 selects bank 1.
+        mem.write_byte(0xFF70, 0x00);
+        assert_eq!(mem.read_byte(0xD000), 0x11);
+    }
+
+    #[test]
+    fn no_wram_banks_in_dmg_mode() {
+        let mut mem = Memory::new();
+
+        mem.write_byte(0xD000, 0x11);
+        mem.write_byte(0xFF70, 0x03);
+
+        assert_eq!(mem.read_byte(0xFF70), 0xFF);
+        assert_eq!(mem.read_byte(0xD000), 0x11);
+        assert_eq!(mem.read_byte(0xFF4D), 0xFF);
+    }
+
+    #[test]
+    fn double_speed_halves_ppu_cycles() {
+        let mut mem = cgb_memory();
+        mem.write_byte(0xFF4D, 0x01);
+        assert!(mem.switch_speed());
+        mem.write_byte(0xFF40, 0x80);
+
+        // A line takes 114 M-cycles at normal speed.
+        for _ in 0..(2 * 114 - 1) {
+            mem.tick(1);
+        }
+        assert_eq!(mem.read_byte(0xFF44), 0);
+
+        mem.tick(1);
+        assert_eq!(mem.read_byte(0xFF44), 1);
+        assert_eq!(mem.read_byte(0xFF4D), 0xFE);
+    }
+
+    fn start_vram_dma(mem: &mut Memory, source: u16, destination: u16, control: u8) {
+        mem.write_byte(0xFF51, (source >> 8) as u8);
+        mem.write_byte(0xFF52, source as u8);
+        mem.write_byte(0xFF53, (destination >> 8) as u8);
+        mem.write_byte(0xFF54, destination as u8);
+        mem.write_byte(0xFF55, control);
+    }
+
+    #[test]
+    fn general_purpose_vram_dma() {
+        let mut mem = cgb_memory();
+        for i in 0..0x20 {
+            mem.write_byte(0xC000 + i, i as u8);
+        }
+
+        start_vram_dma(&mut mem, 0xC000, 0x8100, 0x01);
+
+        assert!(mem.vram_dma_pending());
+        assert_eq!(mem.run_vram_dma_block(), 8);
+        assert_eq!(mem.run_vram_dma_block(), 8);
+        assert!(!mem.vram_dma_pending());
+        assert_eq!(mem.read_byte(0x8100), 0x00);
+        assert_eq!(mem.read_byte(0x811F), 0x1F);
+        assert_eq!(mem.read_byte(0xFF55), 0xFF);
+    }
+
+    #[test]
+    fn vram_dma_takes_twice_as_long_in_double_speed() {
+        let mut mem = cgb_memory();
+        mem.write_byte(0xFF4D, 0x01);
+        mem.switch_speed();
+
+        start_vram_dma(&mut mem, 0xC000, 0x8000, 0x00);
+
+        assert_eq!(mem.run_vram_dma_block(), 16);
+    }
+
+    #[test]
+    fn hblank_vram_dma_copies_a_block_per_line() {
+        let mut mem = cgb_memory();
+        mem.write_byte(0xC010, 0x42);
+        mem.write_byte(0xFF40, 0x80);
+        mem.tick(1);
+
+        start_vram_dma(&mut mem, 0xC000, 0x8000, 0x82);
+        assert!(!mem.vram_dma_pending());
+
+        while mem.ppu.mode() != Mode::HBlank {
+            mem.tick(1);
+        }
+        assert!(mem.vram_dma_pending());
+        mem.run_vram_dma_block();
+        assert!(!mem.vram_dma_pending());
+        assert_eq!(mem.read_byte(0xFF55), 0x01);
+
+        // Cancelled after the second block.
+        while !mem.vram_dma_pending() {
+            mem.tick(1);
+        }
+        mem.run_vram_dma_block();
+        mem.write_byte(0xFF55, 0x00);
+
+        assert_eq!(mem.read_byte(0xFF55), 0x80);
+        assert_eq!(mem.read_byte(0x8010), 0x42);
+    }
+
+    #[test]
+    fn no_vram_dma_in_dmg_mode() {
+        let mut mem = Memory::new();
+
+        start_vram_dma(&mut mem, 0xC000, 0x8000, 0x00);
+
+        assert!(!mem.vram_dma_pending());
+        assert_eq!(mem.read_byte(0xFF55), 0xFF);
+    }
+
+    #[test]
+    fn ppu_requests_vblank() {
+        let mut mem = Memory::new();
+        mem.write_byte(0xFF40, 0x80);
+
+        for _ in 0..144 {
+            mem.tick(114);
+        }
+
+        assert!(mem.interrupts.is_pending(&InterruptKind::VBLANK));
+    }
+
+    #[test]
+    fn joypad_interrupt() {
+        let mut mem = Memory::new();
+        mem.write_byte(0xFF00, 0x10);
+
+        mem.press(Button::Start);
+
+        assert_eq!(mem.read_byte(0xFF00), 0xD7);
+        assert!(mem.interrupts.is_pending(&InterruptKind::JOYPAD));
+    }
+
+    #[test]
+    fn serial_transfer_requests_interrupt() {
+        let sink = BufferSink::new();
+        let output = sink.output();
+        let mut mem = Memory::new();
+        mem.connect_serial(Box::new(sink));
+
+        mem.write_byte(0xFF01, b'A');
+        mem.write_byte(0xFF02, 0x81);
+        for _ in 0..8 {
+            mem.tick(128);
+        }
+
+        assert_eq!(*output.borrow(), vec![b'A']);
+        assert!(mem.interrupts.is_pending(&InterruptKind::SERIAL));
+    }
+
+    #[test]
+    fn boot_rom_is_unmapped_by_writing_0xff50() {
+        let mut rom = rom_with_header("TETRIS", 0x00, 0x00, 0x00);
+        rom[0x0000] = 0x42;
+        let mut mem = Memory::with_cartridge(Cartridge::new(rom).unwrap());
+        let mut boot_rom = vec![0; 0x100];
+        boot_rom[0x0000] = 0x31;
+        mem.load_boot_rom(BootRom::new(boot_rom).unwrap());
+
+        assert_eq!(mem.read_byte(0x0000), 0x31);
+        assert_eq!(mem.read_byte(0x0134), b'T');
+
+        mem.write_byte(0xFF50, 0x00);
+        assert!(mem.boot_rom_mapped());
+
+        mem.write_byte(0xFF50, 0x01);
+        assert!(!mem.boot_rom_mapped());
+        assert_eq!(mem.read_byte(0x0000), 0x42);
+    }
+
+    #[test]
+    fn skip_boot_rom_sets_io_registers() {
+        let mut dmg = Memory::new();
+        let mut sgb = Memory::new();
+
+        dmg.skip_boot_rom(Model::Dmg);
+        sgb.skip_boot_rom(Model::Sgb);
+
+        assert_eq!(dmg.read_byte(0xFF04), 0xAB);
+        assert_eq!(dmg.read_byte(0xFF26), 0xF1);
+        assert_eq!(dmg.read_byte(0xFF40), 0x91);
+        assert_eq!(sgb.read_byte(0xFF26), 0xF0);
+    }
+
+    #[test]
+    fn dmg_game_on_cgb_gets_palettes_from_title() {
+        let rom = rom_with_header("POKEMON BLUE", 0x00, 0x00, 0x00);
+        let mut mem = Memory::with_cartridge(Cartridge::new(rom).unwrap());
+
+        mem.skip_boot_rom(Model::Cgb);
+
+        assert!(!mem.cgb_mode());
+
+        // The empty tiles are drawn with shade 1, which is light blue.
+        mem.write_byte(0xFF47, 0x01);
+        let frames = mem.ppu.frames();
+        while mem.ppu.frames() < frames + 2 {
+            mem.tick(1);
+        }
+
+        assert_eq!(mem.ppu.frame_rgb555()[0], 0x7E8C);
+    }
+
+    #[test]
+    fn rtc_advances_with_ticks() {
+        // MBC3+TIMER+RAM+BATTERY
+        let rom = rom_with_header("POKEMON", 0x10, 0x01, 0x02);
+        let mut mem = Memory::with_cartridge(Cartridge::new(rom).unwrap());
+        mem.write_byte(0x0000, 0x0A);
+        mem.write_byte(0x4000, 0x08);
+
+        for _ in 0..(1 << 18) {
+            mem.tick(4);
+        }
+        mem.write_byte(0x6000, 0x00);
+        mem.write_byte(0x6000, 0x01);
+
+        assert_eq!(mem.read_byte(0xA000), 1);
+    }
+}
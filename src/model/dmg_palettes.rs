@@ -0,0 +1,302 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::memory::Memory;
+use crate::model::title_checksum;
+use crate::ppu::DmgPalettes;
+
+// The letter of the title that tells apart games with the same checksum.
+const FOURTH_LETTER_ADDR: u16 = 0x137;
+
+// Games from this index on have the checksum of another game in the table,
+// and also have to match the fourth letter of the title.
+const FIRST_DUPLICATE: usize = 65;
+
+// The tables of the CGB boot ROM. The title checksums of known DMG games
+// select an entry of PALETTE_PER_CHECKSUM, which is an index into
+// COMBINATIONS. Games that are not in the table get the first one.
+const CHECKSUMS: [u8; 94] = [
+    0x00, 0x88, 0x16, 0x36, 0xD1, 0xDB, 0xF2, 0x3C, 0x8C, 0x92, 0x3D, 0x5C, 0x58, 0xC9, 0x3E, 0x70,
+    0x1D, 0x59, 0x69, 0x19, 0x35, 0xA8, 0x14, 0xAA, 0x75, 0x95, 0x99, 0x34, 0x6F, 0x15, 0xFF, 0x97,
+    0x4B, 0x90, 0x17, 0x10, 0x39, 0xF7, 0xF6, 0xA2, 0x49, 0x4E, 0x43, 0x68, 0xE0, 0x8B, 0xF0, 0xCE,
+    0x0C, 0x29, 0xE8, 0xB7, 0x86, 0x9A, 0x52, 0x01, 0x9D, 0x71, 0x9C, 0xBD, 0x5D, 0x6D, 0x67, 0x3F,
+    0x6B, 0xB3, 0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4, 0xB3,
+    0x46, 0x28, 0xA5, 0xC6, 0xD3, 0x27, 0x61, 0x18, 0x66, 0x6A, 0xBF, 0x0D, 0xF4, 0xB3,
+];
+
+const FOURTH_LETTERS: &[u8; 29] = b"BEFAARBEKEK R-URAR INAILICE R";
+
+const PALETTE_PER_CHECKSUM: [usize; 94] = [
+    0, 4, 5, 35, 34, 3, 31, 15, 10, 5, 19, 36, 7, 37, 30, 44, 21, 32, 31, 20, 5, 33, 13, 14, 5, 29,
+    5, 18, 9, 3, 2, 26, 25, 25, 41, 42, 26, 45, 42, 45, 36, 38, 26, 42, 30, 41, 34, 34, 5, 42, 6,
+    5, 33, 25, 42, 42, 40, 2, 16, 25, 42, 42, 5, 0, 39, 36, 22, 25, 6, 32, 12, 36, 11, 39, 18, 39,
+    24, 31, 50, 17, 46, 6, 27, 0, 47, 41, 41, 0, 0, 19, 34, 23, 18, 29,
+];
+
+// The index of the first colour of the sprite 0, sprite 1 and background
+// palettes in COLORS.
+const COMBINATIONS: [(usize, usize, usize); 51] = [
+    palettes(4, 4, 29),
+    palettes(18, 18, 18),
+    palettes(20, 20, 20),
+    palettes(24, 24, 24),
+    palettes(9, 9, 9),
+    palettes(0, 0, 0),
+    palettes(27, 27, 27),
+    palettes(5, 5, 5),
+    palettes(12, 12, 12),
+    palettes(26, 26, 26),
+    palettes(16, 8, 8),
+    palettes(4, 28, 28),
+    palettes(4, 2, 2),
+    palettes(3, 4, 4),
+    palettes(4, 29, 29),
+    palettes(28, 4, 28),
+    palettes(2, 17, 2),
+    palettes(16, 16, 8),
+    palettes(4, 4, 7),
+    palettes(4, 4, 18),
+    palettes(4, 4, 20),
+    palettes(19, 19, 9),
+    // A few entries of the boot ROM start in the middle of a palette.
+    (4 * 4 - 1, 4 * 4 - 1, 11 * 4),
+    palettes(17, 17, 2),
+    palettes(4, 4, 2),
+    palettes(4, 4, 3),
+    palettes(28, 28, 0),
+    palettes(3, 3, 0),
+    palettes(0, 0, 1),
+    palettes(18, 22, 18),
+    palettes(20, 22, 20),
+    palettes(24, 22, 24),
+    palettes(16, 22, 8),
+    palettes(17, 4, 13),
+    (28 * 4 - 1, 0, 14 * 4),
+    (28 * 4 - 1, 4 * 4, 15 * 4),
+    palettes(19, 22, 9),
+    palettes(16, 28, 10),
+    palettes(4, 23, 28),
+    palettes(17, 22, 2),
+    palettes(4, 0, 2),
+    palettes(4, 28, 3),
+    palettes(28, 3, 0),
+    palettes(3, 28, 4),
+    palettes(21, 28, 4),
+    palettes(3, 28, 0),
+    palettes(25, 3, 28),
+    palettes(0, 28, 8),
+    palettes(4, 3, 28),
+    palettes(28, 3, 6),
+    palettes(4, 28, 29),
+];
+
+// 30 palettes of 4 colours in RGB555.
+const COLORS: [u16; 120] = [
+    0x7FFF, 0x32BF, 0x00D0, 0x0000, // 0
+    0x639F, 0x4279, 0x15B0, 0x04CB, // 1
+    0x7FFF, 0x6E31, 0x454A, 0x0000, // 2
+    0x7FFF, 0x1BEF, 0x0200, 0x0000, // 3
+    0x7FFF, 0x421F, 0x1CF2, 0x0000, // 4
+    0x7FFF, 0x5294, 0x294A, 0x0000, // 5
+    0x7FFF, 0x03FF, 0x012F, 0x0000, // 6
+    0x7FFF, 0x03EF, 0x01D6, 0x0000, // 7
+    0x7FFF, 0x42B5, 0x3DC8, 0x0000, // 8
+    0x7E74, 0x03FF, 0x0180, 0x0000, // 9
+    0x67FF, 0x77AC, 0x1A13, 0x2D6B, // 10
+    0x7ED6, 0x4BFF, 0x2175, 0x0000, // 11
+    0x53FF, 0x4A5F, 0x7E52, 0x0000, // 12
+    0x4FFF, 0x7ED2, 0x3A4C, 0x1CE0, // 13
+    0x03ED, 0x7FFF, 0x255F, 0x0000, // 14
+    0x036A, 0x021F, 0x03FF, 0x7FFF, // 15
+    0x7FFF, 0x01DF, 0x0112, 0x0000, // 16
+    0x231F, 0x035F, 0x00F2, 0x0009, // 17
+    0x7FFF, 0x03EA, 0x011F, 0x0000, // 18
+    0x299F, 0x001A, 0x000C, 0x0000, // 19
+    0x7FFF, 0x027F, 0x001F, 0x0000, // 20
+    0x7FFF, 0x03E0, 0x0206, 0x0120, // 21
+    0x7FFF, 0x7EEB, 0x001F, 0x7C00, // 22
+    0x7FFF, 0x3FFF, 0x7E00, 0x001F, // 23
+    0x7FFF, 0x03FF, 0x001F, 0x0000, // 24
+    0x03FF, 0x001F, 0x000C, 0x0000, // 25
+    0x7FFF, 0x033F, 0x0193, 0x0000, // 26
+    0x0000, 0x4200, 0x037F, 0x7FFF, // 27
+    0x7FFF, 0x7E8C, 0x7C00, 0x0000, // 28
+    0x7FFF, 0x1BEF, 0x6180, 0x0000, // 29
+];
+
+const fn palettes(obj0: usize, obj1: usize, bg: usize) -> (usize, usize, usize) {
+    (obj0 * 4, obj1 * 4, bg * 4)
+}
+
+/// The palettes that can be chosen by holding a direction, and optionally A
+/// or B, while the CGB boot ROM shows the logo.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ManualPalette {
+    Up,
+    UpA,
+    UpB,
+    Left,
+    LeftA,
+    LeftB,
+    Down,
+    DownA,
+    DownB,
+    Right,
+    RightA,
+    RightB,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct UnknownPalette(String);
+
+impl fmt::Display for UnknownPalette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown palette {}: expected a direction (up, down, left or right), optionally followed by +a or +b",
+            self.0
+        )
+    }
+}
+
+impl FromStr for ManualPalette {
+    type Err = UnknownPalette;
+
+    fn from_str(name: &str) -> Result<ManualPalette, UnknownPalette> {
+        match name.to_lowercase().as_str() {
+            "up" => Ok(ManualPalette::Up),
+            "up+a" => Ok(ManualPalette::UpA),
+            "up+b" => Ok(ManualPalette::UpB),
+            "left" => Ok(ManualPalette::Left),
+            "left+a" => Ok(ManualPalette::LeftA),
+            "left+b" => Ok(ManualPalette::LeftB),
+            "down" => Ok(ManualPalette::Down),
+            "down+a" => Ok(ManualPalette::DownA),
+            "down+b" => Ok(ManualPalette::DownB),
+            "right" => Ok(ManualPalette::Right),
+            "right+a" => Ok(ManualPalette::RightA),
+            "right+b" => Ok(ManualPalette::RightB),
+            _ => Err(UnknownPalette(String::from(name))),
+        }
+    }
+}
+
+impl ManualPalette {
+    pub fn palettes(self) -> DmgPalettes {
+        let combination = match self {
+            ManualPalette::Up => 5,
+            ManualPalette::UpA => 43,
+            ManualPalette::UpB => 28,
+            ManualPalette::Left => 48,
+            ManualPalette::LeftA => 40,
+            ManualPalette::LeftB => 7,
+            ManualPalette::Down => 8,
+            ManualPalette::DownA => 3,
+            ManualPalette::DownB => 49,
+            ManualPalette::Right => 1,
+            ManualPalette::RightA => 0,
+            ManualPalette::RightB => 6,
+        };
+
+        combination_palettes(combination)
+    }
+}
+
+/// The palettes that the CGB boot ROM chooses for a DMG game. Only games
+/// from Nintendo are identified, by the checksum of the title.
+pub fn dmg_palettes(memory: &Memory) -> DmgPalettes {
+    let fourth_letter = memory.read_byte(FOURTH_LETTER_ADDR);
+
+    let index = title_checksum(memory).and_then(|checksum| {
+        CHECKSUMS.iter().enumerate().position(|(index, &entry)| {
+            entry == checksum
+                && (index < FIRST_DUPLICATE
+                    || FOURTH_LETTERS[index - FIRST_DUPLICATE] == fourth_letter)
+        })
+    });
+
+    combination_palettes(PALETTE_PER_CHECKSUM[index.unwrap_or(0)])
+}
+
+fn combination_palettes(combination: usize) -> DmgPalettes {
+    let (obj0, obj1, bg) = COMBINATIONS[combination];
+
+    DmgPalettes {
+        bg: palette(bg),
+        obj0: palette(obj0),
+        obj1: palette(obj1),
+    }
+}
+
+fn palette(first_color: usize) -> [u16; 4] {
+    let mut colors = [0; 4];
+    colors.copy_from_slice(&COLORS[first_color..first_color + 4]);
+    colors
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::tests::rom_with_header;
+    use crate::cartridge::{Cartridge, CartridgeHeader};
+
+    fn memory_with_title(title: &str, licensee: u8) -> Memory {
+        let mut rom = rom_with_header(title, 0x00, 0x00, 0x00);
+        rom[0x014B] = licensee;
+        rom[0x014D] = CartridgeHeader::compute_header_checksum(&rom);
+        Memory::with_cartridge(Cartridge::new(rom).unwrap())
+    }
+
+    #[test]
+    fn palettes_from_title() {
+        // Red background, green sprites with OBP0 and red ones with OBP1.
+        let memory = memory_with_title("POKEMON RED", 0x01);
+
+        let palettes = dmg_palettes(&memory);
+
+        assert_eq!(palettes.bg, [0x7FFF, 0x421F, 0x1CF2, 0x0000]);
+        assert_eq!(palettes.obj0, [0x7FFF, 0x1BEF, 0x0200, 0x0000]);
+        assert_eq!(palettes.obj1, [0x7FFF, 0x421F, 0x1CF2, 0x0000]);
+    }
+
+    #[test]
+    fn same_checksum_uses_fourth_letter() {
+        // Both titles add up to 0x46.
+        let mario = memory_with_title("SUPER MARIOLAND", 0x01);
+        let other = memory_with_title("SUEPR MARIOLAND", 0x01);
+
+        assert_eq!(dmg_palettes(&mario), combination_palettes(22));
+        assert_eq!(dmg_palettes(&other), combination_palettes(0));
+    }
+
+    #[test]
+    fn default_palettes_for_other_licensees() {
+        let memory = memory_with_title("POKEMON RED", 0x08);
+
+        assert_eq!(dmg_palettes(&memory), combination_palettes(0));
+    }
+
+    #[test]
+    fn palettes_starting_in_the_middle_of_another() {
+        let palettes = combination_palettes(22);
+
+        assert_eq!(palettes.obj0, [0x0000, 0x7FFF, 0x421F, 0x1CF2]);
+        assert_eq!(palettes.bg, [0x7ED6, 0x4BFF, 0x2175, 0x0000]);
+    }
+
+    #[test]
+    fn manual_palettes() {
+        assert_eq!("Left+B".parse(), Ok(ManualPalette::LeftB));
+        assert_eq!(
+            "up+c".parse::<ManualPalette>(),
+            Err(UnknownPalette(String::from("up+c")))
+        );
+
+        // Inverted grayscale.
+        let palettes = ManualPalette::RightB.palettes();
+        assert_eq!(palettes.bg, [0x0000, 0x4200, 0x037F, 0x7FFF]);
+        // Green and red.
+        let palettes = ManualPalette::Right.palettes();
+        assert_eq!(palettes.bg, [0x7FFF, 0x03EA, 0x011F, 0x0000]);
+    }
+}
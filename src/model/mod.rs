@@ -0,0 +1,294 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::cartridge::{CGB_FLAG_ADDR, HEADER_CHECKSUM_ADDR, TITLE_BEGIN, TITLE_END};
+use crate::memory::Memory;
+pub use crate::model::dmg_palettes::{dmg_palettes, ManualPalette, UnknownPalette};
+
+mod dmg_palettes;
+
+/// The hardware revisions of the Game Boy. They behave slightly differently,
+/// and the boot ROM of each one leaves different values in the registers,
+/// which games use to detect where they are running.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Model {
+    Dmg0,
+    #[default]
+    Dmg,
+    Mgb,
+    Sgb,
+    Sgb2,
+    Cgb,
+    Agb,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct UnknownModel(String);
+
+impl fmt::Display for UnknownModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unknown model {}: expected dmg0, dmg, mgb, sgb, sgb2, cgb or agb",
+            self.0
+        )
+    }
+}
+
+impl FromStr for Model {
+    type Err = UnknownModel;
+
+    fn from_str(name: &str) -> Result<Model, UnknownModel> {
+        match name.to_lowercase().as_str() {
+            "dmg0" => Ok(Model::Dmg0),
+            "dmg" => Ok(Model::Dmg),
+            "mgb" => Ok(Model::Mgb),
+            "sgb" => Ok(Model::Sgb),
+            "sgb2" => Ok(Model::Sgb2),
+            "cgb" => Ok(Model::Cgb),
+            "agb" => Ok(Model::Agb),
+            _ => Err(UnknownModel(String::from(name))),
+        }
+    }
+}
+
+/// The registers of the CPU when the boot ROM jumps to the cartridge.
+#[derive(Debug, PartialEq)]
+pub struct PostBootRegisters {
+    pub af: u16,
+    pub bc: u16,
+    pub de: u16,
+    pub hl: u16,
+}
+
+impl Model {
+    pub fn is_cgb(self) -> bool {
+        matches!(self, Model::Cgb | Model::Agb)
+    }
+
+    /// The registers after the boot ROM. Some of them depend on the header of
+    /// the cartridge in memory, that the boot ROM reads.
+    pub fn post_boot_registers(self, memory: &Memory) -> PostBootRegisters {
+        // The DMG and MGB boot ROMs leave H and C set unless the header
+        // checksum is 0, because of the way they compute it.
+        let checksum_flags = if memory.read_byte(HEADER_CHECKSUM_ADDR as u16) == 0 {
+            0x80
+        } else {
+            0xB0
+        };
+
+        match self {
+            Model::Dmg0 => PostBootRegisters {
+                af: 0x0100,
+                bc: 0xFF13,
+                de: 0x00C1,
+                hl: 0x8403,
+            },
+            Model::Dmg => PostBootRegisters {
+                af: 0x0100 | checksum_flags,
+                bc: 0x0013,
+                de: 0x00D8,
+                hl: 0x014D,
+            },
+            Model::Mgb => PostBootRegisters {
+                af: 0xFF00 | checksum_flags,
+                bc: 0x0013,
+                de: 0x00D8,
+                hl: 0x014D,
+            },
+            Model::Sgb => PostBootRegisters {
+                af: 0x0100,
+                bc: 0x0014,
+                de: 0x0000,
+                hl: 0xC060,
+            },
+            Model::Sgb2 => PostBootRegisters {
+                af: 0xFF00,
+                bc: 0x0014,
+                de: 0x0000,
+                hl: 0xC060,
+            },
+            Model::Cgb => Model::cgb_registers(memory),
+            Model::Agb => {
+                // The AGB boot ROM runs an extra INC B.
+                let mut registers = Model::cgb_registers(memory);
+                let b = (registers.bc >> 8) as u8;
+                let z = if b == 0xFF { 0x80 } else { 0x00 };
+                let h = if b & 0x0F == 0x0F { 0x20 } else { 0x00 };
+
+                registers.bc = u16::from(b.wrapping_add(1)) << 8 | (registers.bc & 0x00FF);
+                registers.af = (registers.af & 0xFF00) | z | h;
+                registers
+            }
+        }
+    }
+
+    fn cgb_registers(memory: &Memory) -> PostBootRegisters {
+        if memory.read_byte(CGB_FLAG_ADDR as u16) & 0x80 != 0 {
+            return PostBootRegisters {
+                af: 0x1180,
+                bc: 0x0000,
+                de: 0xFF56,
+                hl: 0x000D,
+            };
+        }
+
+        // In DMG compatibility mode, B has the checksum of the title that is
+        // used to choose a palette, and HL points to the tile map when the
+        // boot ROM asks the user to choose one.
+        let b = title_checksum(memory).unwrap_or(0);
+        let hl = if b == 0x43 || b == 0x58 {
+            0x991A
+        } else {
+            0x007C
+        };
+
+        PostBootRegisters {
+            af: 0x1180,
+            bc: u16::from(b) << 8,
+            de: 0x0008,
+            hl,
+        }
+    }
+
+    /// The value of the 16-bit counter behind DIV after the boot ROM. The
+    /// DMG0, DMG and MGB values come from Pan Docs; the rest are the ones
+    /// used by other emulators, as they are not documented.
+    pub fn post_boot_div_counter(self) -> u16 {
+        match self {
+            Model::Dmg0 => 0x1830,
+            Model::Dmg | Model::Mgb => 0xABCC,
+            Model::Sgb | Model::Sgb2 => 0xD85C,
+            Model::Cgb | Model::Agb => 0x1EA0,
+        }
+    }
+
+    /// The I/O registers written by the boot ROM, in the order they have to
+    /// be written.
+    pub fn post_boot_io_registers(self) -> Vec<(u16, u8)> {
+        // The SGB boot ROM does not play the startup sound, so channel 1 is
+        // not triggered.
+        let nr14 = match self {
+            Model::Sgb | Model::Sgb2 => 0x3F,
+            _ => 0xBF,
+        };
+
+        vec![
+            (0xFF00, 0xCF),
+            (0xFF07, 0xF8),
+            (0xFF0F, 0xE1),
+            // NR52 has to be written first to power the APU on.
+            (0xFF26, 0x80),
+            (0xFF10, 0x80),
+            (0xFF11, 0xBF),
+            (0xFF12, 0xF3),
+            (0xFF13, 0xFF),
+            (0xFF14, nr14),
+            (0xFF16, 0x3F),
+            (0xFF17, 0x00),
+            (0xFF18, 0xFF),
+            (0xFF19, 0xBF),
+            (0xFF1A, 0x7F),
+            (0xFF1B, 0xFF),
+            (0xFF1C, 0x9F),
+            (0xFF1D, 0xFF),
+            (0xFF1E, 0xBF),
+            (0xFF20, 0xFF),
+            (0xFF21, 0x00),
+            (0xFF22, 0x00),
+            (0xFF23, 0xBF),
+            (0xFF24, 0x77),
+            (0xFF25, 0xF3),
+            (0xFF40, 0x91),
+            (0xFF47, 0xFC),
+        ]
+    }
+}
+
+/// The sum of the bytes of the title, if the licensee is Nintendo. The CGB
+/// boot ROM uses it to identify DMG games.
+pub(crate) fn title_checksum(memory: &Memory) -> Option<u8> {
+    if !memory.cartridge()?.header().licensee.is_nintendo() {
+        return None;
+    }
+
+    let checksum = (TITLE_BEGIN..=TITLE_END)
+        .map(|address| memory.read_byte(address as u16))
+        .fold(0_u8, |sum, byte| sum.wrapping_add(byte));
+
+    Some(checksum)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cartridge::tests::rom_with_header;
+    use crate::cartridge::{Cartridge, CartridgeHeader};
+
+    #[test]
+    fn model_from_name() {
+        assert_eq!("SGB2".parse(), Ok(Model::Sgb2));
+        assert_eq!(
+            "gbc".parse::<Model>(),
+            Err(UnknownModel(String::from("gbc")))
+        );
+    }
+
+    #[test]
+    fn dmg_flags_depend_on_header_checksum() {
+        let mut mem = Memory::new();
+
+        assert_eq!(Model::Dmg.post_boot_registers(&mem).af, 0x0180);
+
+        mem.write_byte(HEADER_CHECKSUM_ADDR as u16, 0x42);
+        assert_eq!(Model::Dmg.post_boot_registers(&mem).af, 0x01B0);
+        assert_eq!(Model::Mgb.post_boot_registers(&mem).af, 0xFFB0);
+    }
+
+    #[test]
+    fn cgb_game_on_cgb_and_agb() {
+        let mut mem = Memory::new();
+        mem.write_byte(CGB_FLAG_ADDR as u16, 0x80);
+
+        let cgb = Model::Cgb.post_boot_registers(&mem);
+        let agb = Model::Agb.post_boot_registers(&mem);
+
+        assert_eq!((cgb.af, cgb.bc), (0x1180, 0x0000));
+        assert_eq!((agb.af, agb.bc), (0x1100, 0x0100));
+    }
+
+    #[test]
+    fn dmg_game_on_cgb() {
+        // Old licensee 0x01 is Nintendo.
+        let rom = rom_with_header("TETRIS", 0x00, 0x00, 0x00);
+        let mem = Memory::with_cartridge(Cartridge::new(rom).unwrap());
+        let sum = b"TETRIS"
+            .iter()
+            .fold(0_u8, |sum, &byte| sum.wrapping_add(byte));
+
+        let registers = Model::Cgb.post_boot_registers(&mem);
+
+        assert_eq!(registers.bc, u16::from(sum) << 8);
+        assert_eq!(registers.de, 0x0008);
+    }
+
+    #[test]
+    fn title_checksum_needs_nintendo_licensee() {
+        let memory_with_licensee = |old: u8, new: &[u8; 2]| {
+            let mut rom = rom_with_header("\x10", 0x00, 0x00, 0x00);
+            rom[0x014B] = old;
+            rom[0x0144..0x0146].copy_from_slice(new);
+            rom[HEADER_CHECKSUM_ADDR] = CartridgeHeader::compute_header_checksum(&rom);
+            Memory::with_cartridge(Cartridge::new(rom).unwrap())
+        };
+
+        // The new licensee code is used when the old one is 0x33.
+        assert_eq!(title_checksum(&memory_with_licensee(0x08, b"01")), None);
+        assert_eq!(title_checksum(&memory_with_licensee(0x33, b"08")), None);
+        assert_eq!(
+            title_checksum(&memory_with_licensee(0x33, b"01")),
+            Some(0x10)
+        );
+        assert_eq!(title_checksum(&Memory::new()), None);
+    }
+}
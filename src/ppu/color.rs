@@ -0,0 +1,183 @@
+// Palette specification bits
+const AUTO_INCREMENT: u8 = 0b1000_0000;
+const INDEX_MASK: u8 = 0b0011_1111;
+
+const PALETTE_RAM_SIZE: usize = 64;
+
+/// How RGB555 colours are converted to RGB888. The CGB screen is darker and
+/// less saturated than a modern display, so games look too bright without
+/// correction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorCorrection {
+    None,
+    Lcd,
+}
+
+/// Colours of the background and the two sprite palettes of a DMG game
+/// running on a CGB, in RGB555. The shades chosen with BGP, OBP0 and OBP1
+/// index them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DmgPalettes {
+    pub bg: [u16; 4],
+    pub obj0: [u16; 4],
+    pub obj1: [u16; 4],
+}
+
+/// CGB palette memory: 8 palettes of 4 colours, 2 bytes per colour in
+/// RGB555, little endian. It is accessed through a specification register,
+/// with the index and whether to increase it after each write, and a data
+/// register.
+#[derive(Clone)]
+pub struct PaletteRam {
+    data: [u8; PALETTE_RAM_SIZE],
+    spec: u8,
+}
+
+impl Default for PaletteRam {
+    fn default() -> PaletteRam {
+        PaletteRam::new()
+    }
+}
+
+impl PaletteRam {
+    pub fn new() -> PaletteRam {
+        PaletteRam {
+            data: [0; PALETTE_RAM_SIZE],
+            spec: 0,
+        }
+    }
+
+    // Bit 6 is not used.
+    pub fn read_spec(&self) -> u8 {
+        0x40 | self.spec
+    }
+
+    pub fn write_spec(&mut self, value: u8) {
+        self.spec = value & (AUTO_INCREMENT | INDEX_MASK);
+    }
+
+    pub fn read_data(&self) -> u8 {
+        self.data[usize::from(self.spec & INDEX_MASK)]
+    }
+
+    /// Writes are ignored while the PPU is drawing, but the index is still
+    /// increased.
+    pub fn write_data(&mut self, value: u8, blocked: bool) {
+        if !blocked {
+            self.data[usize::from(self.spec & INDEX_MASK)] = value;
+        }
+
+        if self.spec & AUTO_INCREMENT != 0 {
+            let index = (self.spec + 1) & INDEX_MASK;
+            self.spec = AUTO_INCREMENT | index;
+        }
+    }
+
+    pub fn set_color(&mut self, palette: u8, color: u8, value: u16) {
+        let index = usize::from(palette & 0x07) * 8 + usize::from(color) * 2;
+
+        self.data[index] = value as u8;
+        self.data[index + 1] = (value >> 8) as u8;
+    }
+
+    /// RGB555 colour with red in the lowest bits.
+    pub fn color(&self, palette: u8, color: u8) -> u16 {
+        let index = usize::from(palette & 0x07) * 8 + usize::from(color) * 2;
+
+        u16::from(self.data[index]) | (u16::from(self.data[index + 1]) << 8)
+    }
+}
+
+pub fn rgb555_to_rgb888(color: u16, correction: ColorCorrection) -> [u8; 3] {
+    let r = u32::from(color & 0x1F);
+    let g = u32::from((color >> 5) & 0x1F);
+    let b = u32::from((color >> 10) & 0x1F);
+
+    match correction {
+        ColorCorrection::None => [expand(r), expand(g), expand(b)],
+        // Mixes the channels and reduces the brightness like the LCD. The
+        // weights are the ones used by other emulators, like higan.
+        ColorCorrection::Lcd => [
+            ((r * 26 + g * 4 + b * 2).min(960) >> 2) as u8,
+            ((g * 24 + b * 8).min(960) >> 2) as u8,
+            ((r * 6 + g * 4 + b * 22).min(960) >> 2) as u8,
+        ],
+    }
+}
+
+// 5 bits to 8, so that 31 is 255.
+fn expand(channel: u32) -> u8 {
+    ((channel << 3) | (channel >> 2)) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn auto_increment() {
+        let mut palettes = PaletteRam::new();
+        palettes.write_spec(AUTO_INCREMENT | 0x3E);
+
+        palettes.write_data(0x1F, false);
+        palettes.write_data(0x7C, false);
+        palettes.write_data(0x42, false);
+
+        assert_eq!(palettes.color(7, 3), 0x7C1F);
+        assert_eq!(palettes.read_spec(), 0xC1);
+        palettes.write_spec(0x00);
+        assert_eq!(palettes.read_data(), 0x42);
+    }
+
+    #[test]
+    fn without_auto_increment() {
+        let mut palettes = PaletteRam::new();
+        palettes.write_spec(0x02);
+
+        palettes.write_data(0x11, false);
+        palettes.write_data(0x22, false);
+
+        assert_eq!(palettes.read_spec(), 0x42);
+        assert_eq!(palettes.read_data(), 0x22);
+    }
+
+    #[test]
+    fn blocked_writes_still_increment() {
+        let mut palettes = PaletteRam::new();
+        palettes.write_spec(AUTO_INCREMENT);
+
+        palettes.write_data(0x11, true);
+
+        assert_eq!(palettes.color(0, 0), 0x0000);
+        assert_eq!(palettes.read_spec(), 0xC1);
+    }
+
+    #[test]
+    fn set_color() {
+        let mut palettes = PaletteRam::new();
+
+        palettes.set_color(1, 2, 0x1234);
+
+        assert_eq!(palettes.color(1, 2), 0x1234);
+        palettes.write_spec(0x0C);
+        assert_eq!(palettes.read_data(), 0x34);
+    }
+
+    #[test]
+    fn rgb888_without_correction() {
+        assert_eq!(
+            rgb555_to_rgb888(0x7FFF, ColorCorrection::None),
+            [255, 255, 255]
+        );
+        assert_eq!(rgb555_to_rgb888(0x001F, ColorCorrection::None), [255, 0, 0]);
+    }
+
+    #[test]
+    fn rgb888_with_correction() {
+        assert_eq!(
+            rgb555_to_rgb888(0x7FFF, ColorCorrection::Lcd),
+            [240, 240, 240]
+        );
+        assert_eq!(rgb555_to_rgb888(0x001F, ColorCorrection::Lcd), [201, 0, 46]);
+    }
+}
@@ -0,0 +1,536 @@
+use std::collections::VecDeque;
+
+use crate::ppu::{
+    BgPixel, Ppu, Sprite, BG_TILE_MAP, OBJ_ENABLE, SCREEN_WIDTH, VRAM_BEGIN, VRAM_SIZE,
+    WINDOW_ENABLE, WINDOW_TILE_MAP, X_FLIP, Y_FLIP,
+};
+
+// At the start of the drawing mode, the fetcher fetches a tile that is then
+// thrown away.
+const DISCARDED_FETCH_DOTS: u8 = 6;
+
+// A sprite fetch takes 6 dots after waiting for the fetcher to have a tile
+// ready, which gives the 6 to 11 dots of penalty documented in Pan Docs. It
+// does not model the extra dots of several sprites in the same tile.
+const SPRITE_FETCH_DOTS: u8 = 6;
+
+// Each step of the fetcher takes 2 dots, except pushing, which is retried
+// every dot until the background FIFO is empty.
+#[derive(Clone, Copy, PartialEq)]
+enum FetchStep {
+    TileNumber,
+    DataLow,
+    DataHigh,
+    Push,
+}
+
+#[derive(Clone, Copy)]
+struct SpritePixel {
+    color: u8,
+    attributes: u8,
+    oam_index: u8,
+}
+
+/// State of the pixel FIFO renderer during the drawing mode. Instead of
+/// drawing the whole line at once, pixels are fetched and shifted out one dot
+/// at a time, so the length of the mode depends on the scroll, the window and
+/// the sprites, and register writes in the middle of the line take effect.
+#[derive(Clone)]
+pub struct PixelFifo {
+    bg: VecDeque<BgPixel>,
+    sprites: VecDeque<SpritePixel>,
+
+    step: FetchStep,
+    // Whether the first of the 2 dots of the current step has passed.
+    step_started: bool,
+    // Tile column being fetched, relative to the start of the line or of the
+    // window.
+    fetcher_x: u8,
+    tile: u8,
+    attributes: u8,
+    data_low: u8,
+    data_high: u8,
+
+    startup_dots: u8,
+
+    // Pixels thrown away at the start of the line because of SCX.
+    pixels_to_discard: u8,
+
+    // Position of the next pixel on screen.
+    x: u8,
+
+    in_window: bool,
+
+    // The line sprites are sorted by X, so they are fetched in order.
+    sprites_fetched: usize,
+    sprite_fetch_dots: u8,
+}
+
+impl Default for PixelFifo {
+    fn default() -> PixelFifo {
+        PixelFifo::new()
+    }
+}
+
+impl PixelFifo {
+    pub fn new() -> PixelFifo {
+        PixelFifo {
+            bg: VecDeque::with_capacity(16),
+            sprites: VecDeque::with_capacity(8),
+            step: FetchStep::TileNumber,
+            step_started: false,
+            fetcher_x: 0,
+            tile: 0,
+            attributes: 0,
+            data_low: 0,
+            data_high: 0,
+            startup_dots: 0,
+            pixels_to_discard: 0,
+            x: 0,
+            in_window: false,
+            sprites_fetched: 0,
+            sprite_fetch_dots: 0,
+        }
+    }
+
+    fn restart_fetcher(&mut self) {
+        self.step = FetchStep::TileNumber;
+        self.step_started = false;
+        self.fetcher_x = 0;
+    }
+}
+
+impl Ppu {
+    pub(super) fn start_fifo_line(&mut self) {
+        let fifo = &mut self.fifo;
+
+        fifo.bg.clear();
+        fifo.sprites.clear();
+        fifo.restart_fetcher();
+        fifo.startup_dots = DISCARDED_FETCH_DOTS;
+        fifo.pixels_to_discard = self.scx % 8;
+        fifo.x = 0;
+        fifo.in_window = false;
+        fifo.sprites_fetched = 0;
+        fifo.sprite_fetch_dots = 0;
+    }
+
+    /// Advances the drawing mode one dot. Returns true when the line is
+    /// complete.
+    pub(super) fn step_fifo(&mut self) -> bool {
+        if self.fifo.startup_dots > 0 {
+            self.fifo.startup_dots -= 1;
+            return false;
+        }
+
+        // The fetcher and the output are paused while a sprite is fetched. On
+        // its last dot, the sprite is loaded and output resumes.
+        if self.fifo.sprite_fetch_dots > 0 {
+            self.fifo.sprite_fetch_dots -= 1;
+
+            if self.fifo.sprite_fetch_dots > 0 {
+                return false;
+            }
+
+            self.load_next_sprite();
+        }
+
+        self.step_fetcher();
+
+        if self.fifo.bg.is_empty() {
+            return false;
+        }
+
+        if self.fifo.pixels_to_discard > 0 {
+            self.fifo.bg.pop_front();
+            self.fifo.pixels_to_discard -= 1;
+            return false;
+        }
+
+        if !self.fifo.in_window && self.window_starts_here() {
+            self.fifo.in_window = true;
+            self.fifo.bg.clear();
+            self.fifo.restart_fetcher();
+            return false;
+        }
+
+        // A sprite fetch waits until the fetcher has a tile ready to push.
+        if self.next_sprite_starts_here() {
+            // This dot is the first of the fetch.
+            if self.fifo.step == FetchStep::Push {
+                self.fifo.sprite_fetch_dots = SPRITE_FETCH_DOTS - 1;
+            }
+
+            return false;
+        }
+
+        self.output_pixel();
+
+        if usize::from(self.fifo.x) == SCREEN_WIDTH {
+            if self.fifo.in_window {
+                self.window_line += 1;
+            }
+
+            return true;
+        }
+
+        false
+    }
+
+    fn step_fetcher(&mut self) {
+        let fifo = &mut self.fifo;
+
+        if fifo.step != FetchStep::Push && !fifo.step_started {
+            fifo.step_started = true;
+            return;
+        }
+
+        fifo.step_started = false;
+
+        match fifo.step {
+            FetchStep::TileNumber => {
+                let map_index = self.fetcher_map_index();
+                self.fifo.tile = self.vram[map_index];
+                self.fifo.attributes = self.bg_attributes(map_index);
+                self.fifo.step = FetchStep::DataLow;
+            }
+            FetchStep::DataLow => {
+                self.fifo.data_low = self.fetcher_tile_data(0);
+                self.fifo.step = FetchStep::DataHigh;
+            }
+            FetchStep::DataHigh => {
+                self.fifo.data_high = self.fetcher_tile_data(1);
+                self.fifo.step = FetchStep::Push;
+            }
+            FetchStep::Push => {
+                if fifo.bg.is_empty() {
+                    for col in 0..8 {
+                        let bit = if fifo.attributes & X_FLIP != 0 {
+                            col
+                        } else {
+                            7 - col
+                        };
+                        let color =
+                            (((fifo.data_high >> bit) & 1) << 1) | ((fifo.data_low >> bit) & 1);
+                        fifo.bg.push_back(BgPixel {
+                            color,
+                            attributes: fifo.attributes,
+                        });
+                    }
+
+                    fifo.fetcher_x = fifo.fetcher_x.wrapping_add(1);
+                    fifo.step = FetchStep::TileNumber;
+                }
+            }
+        }
+    }
+
+    // SCX and SCY are read when each tile is fetched, so changing them in the
+    // middle of a line moves the rest of it.
+    fn fetcher_map_index(&self) -> usize {
+        let (map_select_bit, x, y) = if self.fifo.in_window {
+            (WINDOW_TILE_MAP, self.fifo.fetcher_x, self.window_line / 8)
+        } else {
+            (
+                BG_TILE_MAP,
+                (self.scx / 8).wrapping_add(self.fifo.fetcher_x) & 31,
+                self.scy.wrapping_add(self.ly) / 8,
+            )
+        };
+
+        self.tile_map_index(map_select_bit, x, y)
+    }
+
+    fn fetcher_tile_data(&self, byte: usize) -> u8 {
+        let mut row = if self.fifo.in_window {
+            self.window_line % 8
+        } else {
+            self.scy.wrapping_add(self.ly) % 8
+        };
+
+        if self.fifo.attributes & Y_FLIP != 0 {
+            row = 7 - row;
+        }
+
+        let bank = self.tile_bank(self.fifo.attributes);
+        let address =
+            bank * VRAM_SIZE + usize::from(self.tile_address(self.fifo.tile) - VRAM_BEGIN);
+        self.vram[address + usize::from(row) * 2 + byte]
+    }
+
+    fn window_starts_here(&self) -> bool {
+        self.lcdc & WINDOW_ENABLE != 0
+            && self.window_triggered
+            && u16::from(self.fifo.x) + 7 >= u16::from(self.wx)
+    }
+
+    fn next_sprite_starts_here(&self) -> bool {
+        if self.lcdc & OBJ_ENABLE == 0 {
+            return false;
+        }
+
+        match self.line_sprites.get(self.fifo.sprites_fetched) {
+            Some(sprite) => u16::from(sprite.x) <= u16::from(self.fifo.x) + 8,
+            None => false,
+        }
+    }
+
+    // Sprites that were loaded before, which have a smaller X or come first
+    // in OAM, keep their non transparent pixels. In CGB mode, only the OAM
+    // position matters.
+    fn load_next_sprite(&mut self) {
+        let sprite: Sprite = self.line_sprites[self.fifo.sprites_fetched];
+        self.fifo.sprites_fetched += 1;
+
+        // Sprites partially off the left of the screen lose their first
+        // pixels.
+        let hidden = 8u8.saturating_sub(sprite.x);
+
+        for (i, col) in (hidden..8).enumerate() {
+            let pixel = SpritePixel {
+                color: self.sprite_pixel(&sprite, col),
+                attributes: sprite.attributes,
+                oam_index: sprite.index,
+            };
+            let cgb_mode = self.cgb_mode;
+
+            match self.fifo.sprites.get_mut(i) {
+                Some(existing) if existing.color == 0 => *existing = pixel,
+                Some(existing)
+                    if cgb_mode && pixel.color != 0 && pixel.oam_index < existing.oam_index =>
+                {
+                    *existing = pixel
+                }
+                Some(_) => (),
+                None => self.fifo.sprites.push_back(pixel),
+            }
+        }
+    }
+
+    // The palettes are applied as pixels are shifted out, so they can change
+    // in the middle of a line too.
+    fn output_pixel(&mut self) {
+        let bg_pixel = match self.fifo.bg.pop_front() {
+            Some(pixel) if self.bg_enabled() => pixel,
+            _ => BgPixel::default(),
+        };
+
+        let sprite = self.fifo.sprites.pop_front();
+
+        let output = match sprite {
+            Some(pixel)
+                if pixel.color != 0
+                    && self.lcdc & OBJ_ENABLE != 0
+                    && self.sprite_visible(bg_pixel, pixel.attributes) =>
+            {
+                self.sprite_output(pixel.attributes, pixel.color)
+            }
+            _ => self.bg_output(bg_pixel),
+        };
+
+        self.put_pixel(usize::from(self.fifo.x), output);
+        self.fifo.x += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ppu::{
+        Mode, Renderer, BCPD_ADDR, BCPS_ADDR, BEHIND_BG, BGP_ADDR, BG_ENABLE, LCDC_ADDR,
+        LCD_ENABLE, OBP0_ADDR, OCPD_ADDR, OCPS_ADDR, SCREEN_HEIGHT, SCX_ADDR, TILE_DATA, VBK_ADDR,
+        WX_ADDR, WY_ADDR,
+    };
+
+    fn ppu(renderer: Renderer) -> Ppu {
+        let mut ppu = Ppu::with_renderer(renderer);
+        ppu.write(BGP_ADDR, 0b1110_0100);
+        ppu.write(OBP0_ADDR, 0b1110_0100);
+
+        // Tile 1 is all colour 3 and tile 2 has a vertical line of colour 1.
+        for i in 0..16 {
+            ppu.write(0x8010 + i, 0xFF);
+        }
+        for i in 0..8 {
+            ppu.write(0x8020 + i * 2, 0b0001_0000);
+        }
+
+        // A checkerboard of tiles 1 and 2.
+        for i in 0..0x400 {
+            ppu.write(0x9800 + i, 1 + (i % 2 + i / 32) as u8 % 2);
+            ppu.write(0x9C00 + i, 2);
+        }
+
+        ppu
+    }
+
+    fn run_until_frame(ppu: &mut Ppu) {
+        let frames = ppu.frames();
+
+        while ppu.frames() == frames {
+            ppu.tick(1);
+        }
+    }
+
+    // M-cycles from the start of the first line until HBlank.
+    fn first_line_drawing_end(ppu: &mut Ppu) -> u32 {
+        let mut m_cycles = 0;
+
+        while ppu.mode() != Mode::HBlank {
+            ppu.tick(1);
+            m_cycles += 1;
+        }
+
+        m_cycles
+    }
+
+    fn add_sprite(ppu: &mut Ppu, index: u16, y: u8, x: u8, tile: u8, attributes: u8) {
+        ppu.write(0xFE00 + index * 4, y);
+        ppu.write(0xFE01 + index * 4, x);
+        ppu.write(0xFE02 + index * 4, tile);
+        ppu.write(0xFE03 + index * 4, attributes);
+    }
+
+    #[test]
+    fn same_frame_as_scanline_renderer() {
+        let mut frames = Vec::new();
+
+        for &renderer in [Renderer::Scanline, Renderer::PixelFifo].iter() {
+            let mut ppu = ppu(renderer);
+            add_sprite(&mut ppu, 0, 20, 30, 2, 0);
+            add_sprite(&mut ppu, 1, 24, 34, 1, BEHIND_BG);
+            add_sprite(&mut ppu, 2, 40, 4, 1, 0);
+            ppu.write(SCX_ADDR, 3);
+            ppu.write(WY_ADDR, 100);
+            ppu.write(WX_ADDR, 87);
+            ppu.write(
+                LCDC_ADDR,
+                LCD_ENABLE | BG_ENABLE | TILE_DATA | OBJ_ENABLE | WINDOW_ENABLE | WINDOW_TILE_MAP,
+            );
+
+            run_until_frame(&mut ppu);
+            frames.push(ppu.frame().to_vec());
+        }
+
+        assert_eq!(frames[0].len(), SCREEN_WIDTH * SCREEN_HEIGHT);
+        assert!(frames[0] == frames[1]);
+    }
+
+    #[test]
+    fn same_cgb_frame_as_scanline_renderer() {
+        let mut frames = Vec::new();
+
+        for &renderer in [Renderer::Scanline, Renderer::PixelFifo].iter() {
+            let mut ppu = ppu(renderer);
+            ppu.set_cgb_mode(true);
+
+            // A different colour for each palette and colour number.
+            ppu.write(BCPS_ADDR, 0x80);
+            ppu.write(OCPS_ADDR, 0x80);
+            for i in 0..32 {
+                ppu.write(BCPD_ADDR, i as u8);
+                ppu.write(BCPD_ADDR, 0);
+                ppu.write(OCPD_ADDR, 0);
+                ppu.write(OCPD_ADDR, i as u8);
+            }
+
+            // Tile 2 in bank 1 has a horizontal line, and the attributes
+            // flip and use other palettes.
+            ppu.write(VBK_ADDR, 1);
+            ppu.write(0x8020, 0xFF);
+            for i in 0..0x400 {
+                ppu.write(
+                    0x9800 + i,
+                    (i % 8) as u8 | ((i as u8 % 3) << 5) | ((i as u8 & 1) << 3),
+                );
+            }
+            ppu.write(VBK_ADDR, 0);
+
+            add_sprite(&mut ppu, 0, 20, 30, 2, 0x03);
+            add_sprite(&mut ppu, 1, 24, 26, 1, 0x05 | 0x08);
+            add_sprite(&mut ppu, 2, 40, 4, 1, BEHIND_BG | 0x01);
+            ppu.write(SCX_ADDR, 3);
+            ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA | OBJ_ENABLE);
+
+            run_until_frame(&mut ppu);
+            frames.push(ppu.frame_rgb555().to_vec());
+        }
+
+        assert!(frames[0] == frames[1]);
+    }
+
+    #[test]
+    fn drawing_takes_172_dots() {
+        let mut ppu = ppu(Renderer::PixelFifo);
+        ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA);
+
+        assert_eq!(first_line_drawing_end(&mut ppu), 63);
+    }
+
+    #[test]
+    fn fine_scroll_lengthens_drawing() {
+        let mut ppu = ppu(Renderer::PixelFifo);
+        ppu.write(SCX_ADDR, 7);
+        ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA);
+
+        // 252 + 7 dots
+        assert_eq!(first_line_drawing_end(&mut ppu), 65);
+    }
+
+    #[test]
+    fn sprites_lengthen_drawing() {
+        let mut ppu = ppu(Renderer::PixelFifo);
+        add_sprite(&mut ppu, 0, 16, 8, 1, 0);
+        ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA | OBJ_ENABLE);
+
+        // The fetcher finishes the tile in 6 dots, then the sprite takes 6.
+        assert_eq!(first_line_drawing_end(&mut ppu), 66);
+    }
+
+    #[test]
+    fn sprite_penalty_depends_on_the_fetcher() {
+        let mut ppu = ppu(Renderer::PixelFifo);
+        // 5 pixels into the tile, the fetcher is already pushing, so the
+        // penalty is only the 6 dots of the sprite fetch.
+        add_sprite(&mut ppu, 0, 16, 13, 1, 0);
+        ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA | OBJ_ENABLE);
+
+        // 252 + 6 dots
+        assert_eq!(first_line_drawing_end(&mut ppu), 65);
+    }
+
+    #[test]
+    fn window_lengthens_drawing() {
+        let mut ppu = ppu(Renderer::PixelFifo);
+        ppu.write(WX_ADDR, 87);
+        ppu.write(
+            LCDC_ADDR,
+            LCD_ENABLE | BG_ENABLE | TILE_DATA | WINDOW_ENABLE | WINDOW_TILE_MAP,
+        );
+
+        assert_eq!(first_line_drawing_end(&mut ppu), 65);
+    }
+
+    #[test]
+    fn palette_change_in_the_middle_of_a_line() {
+        let mut ppu = ppu(Renderer::PixelFifo);
+        // The first row of tiles is all colour 3, so every pixel of the line
+        // shows the shade that BGP gives to it.
+        for i in 0..32 {
+            ppu.write(0x9800 + i, 1);
+        }
+        ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA);
+
+        // 80 dots of OAM scan, 12 before the first pixel and 80 more, so the
+        // write lands between pixels 79 and 80. Colour 3 becomes shade 1.
+        ppu.tick(43);
+        ppu.write(BGP_ADDR, 0b0110_0100);
+        run_until_frame(&mut ppu);
+
+        assert_eq!(ppu.frame()[0], 3);
+        assert_eq!(ppu.frame()[79], 3);
+        assert_eq!(ppu.frame()[80], 1);
+        assert_eq!(ppu.frame()[SCREEN_WIDTH - 1], 1);
+        assert_eq!(ppu.frame()[SCREEN_WIDTH], 1);
+    }
+}
@@ -0,0 +1,1250 @@
+use crate::ppu::color::PaletteRam;
+pub use crate::ppu::color::{rgb555_to_rgb888, ColorCorrection, DmgPalettes};
+use crate::ppu::fifo::PixelFifo;
+
+mod color;
+mod fifo;
+
+pub const SCREEN_WIDTH: usize = 160;
+pub const SCREEN_HEIGHT: usize = 144;
+
+pub const VRAM_BEGIN: u16 = 0x8000;
+pub const VRAM_END: u16 = 0x9FFF;
+pub const OAM_BEGIN: u16 = 0xFE00;
+pub const OAM_END: u16 = 0xFE9F;
+
+pub const LCDC_ADDR: u16 = 0xFF40;
+pub const STAT_ADDR: u16 = 0xFF41;
+pub const SCY_ADDR: u16 = 0xFF42;
+pub const SCX_ADDR: u16 = 0xFF43;
+pub const LY_ADDR: u16 = 0xFF44;
+pub const LYC_ADDR: u16 = 0xFF45;
+pub const BGP_ADDR: u16 = 0xFF47;
+pub const OBP0_ADDR: u16 = 0xFF48;
+pub const OBP1_ADDR: u16 = 0xFF49;
+pub const WY_ADDR: u16 = 0xFF4A;
+pub const WX_ADDR: u16 = 0xFF4B;
+pub const VBK_ADDR: u16 = 0xFF4F;
+pub const BCPS_ADDR: u16 = 0xFF68;
+pub const BCPD_ADDR: u16 = 0xFF69;
+pub const OCPS_ADDR: u16 = 0xFF6A;
+pub const OCPD_ADDR: u16 = 0xFF6B;
+
+const VRAM_SIZE: usize = 0x2000;
+const VRAM_BANKS: usize = 2;
+const OAM_SIZE: usize = 0xA0;
+
+// Timings, in dots. There is one dot per clock cycle, so 4 per M-cycle.
+const DOTS_PER_LINE: u16 = 456;
+const OAM_SCAN_DOTS: u16 = 80;
+const MIN_DRAWING_DOTS: u16 = 172;
+const LINES_PER_FRAME: u8 = 154;
+
+const MAX_SPRITES_PER_LINE: usize = 10;
+
+// LCDC bits
+const BG_ENABLE: u8 = 0b0000_0001;
+const OBJ_ENABLE: u8 = 0b0000_0010;
+const OBJ_SIZE: u8 = 0b0000_0100;
+const BG_TILE_MAP: u8 = 0b0000_1000;
+const TILE_DATA: u8 = 0b0001_0000;
+const WINDOW_ENABLE: u8 = 0b0010_0000;
+const WINDOW_TILE_MAP: u8 = 0b0100_0000;
+const LCD_ENABLE: u8 = 0b1000_0000;
+
+// STAT bits
+const COINCIDENCE: u8 = 0b0000_0100;
+const HBLANK_STAT_INTERRUPT: u8 = 0b0000_1000;
+const VBLANK_STAT_INTERRUPT: u8 = 0b0001_0000;
+const OAM_STAT_INTERRUPT: u8 = 0b0010_0000;
+const COINCIDENCE_STAT_INTERRUPT: u8 = 0b0100_0000;
+
+// Sprite attribute bits
+const BEHIND_BG: u8 = 0b1000_0000;
+const Y_FLIP: u8 = 0b0100_0000;
+const X_FLIP: u8 = 0b0010_0000;
+const DMG_PALETTE: u8 = 0b0001_0000;
+const TILE_BANK: u8 = 0b0000_1000;
+const CGB_PALETTE: u8 = 0b0000_0111;
+
+// Background attribute bits, in VRAM bank 1 in CGB mode. The bank, palette
+// and flip bits are the same as for sprites.
+const BG_PRIORITY: u8 = 0b1000_0000;
+
+// RGB555 colours of the DMG shades.
+const DMG_COLORS: [u16; 4] = [0x7FFF, 0x56B5, 0x294A, 0x0000];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    HBlank = 0,
+    VBlank = 1,
+    OamScan = 2,
+    Drawing = 3,
+}
+
+/// How lines are drawn. The scanline renderer draws each line at once at the
+/// end of the drawing mode, and its length is an approximation. The pixel
+/// FIFO renderer is slower, but emulates the length of the drawing mode and
+/// changes to the registers in the middle of a line.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Renderer {
+    Scanline,
+    PixelFifo,
+}
+
+/// The interrupts the PPU requests during a tick.
+#[derive(Debug, Default, PartialEq)]
+pub struct PpuInterrupts {
+    pub vblank: bool,
+    pub stat: bool,
+}
+
+#[derive(Clone, Copy)]
+struct Sprite {
+    y: u8,
+    x: u8,
+    tile: u8,
+    attributes: u8,
+    // Position in OAM, which decides the priority in CGB mode.
+    index: u8,
+}
+
+// A background or window pixel before applying the palette.
+#[derive(Clone, Copy, Default)]
+struct BgPixel {
+    color: u8,
+    attributes: u8,
+}
+
+#[derive(Clone)]
+pub struct Ppu {
+    // Bank 1 is only used in CGB mode.
+    vram: [u8; VRAM_SIZE * VRAM_BANKS],
+    vram_bank: usize,
+    oam: [u8; OAM_SIZE],
+
+    cgb_mode: bool,
+    // A CGB running a DMG game still colours it with the first background
+    // palette and the first two sprite palettes.
+    dmg_compatibility: bool,
+    bg_palettes: PaletteRam,
+    obj_palettes: PaletteRam,
+
+    lcdc: u8,
+    stat: u8,
+    scy: u8,
+    scx: u8,
+    ly: u8,
+    lyc: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    wy: u8,
+    wx: u8,
+
+    mode: Mode,
+
+    // Dot within the current line, 0-455.
+    line_dot: u16,
+
+    // Depends on the scroll and the sprites of the line.
+    drawing_dots: u16,
+
+    // Sprites found during the OAM scan of the current line.
+    line_sprites: Vec<Sprite>,
+
+    // The window is only drawn after LY has been equal to WY in the frame.
+    // It has its own line counter that only advances on lines where it is
+    // drawn.
+    window_triggered: bool,
+    window_line: u8,
+
+    // The STAT interrupt is requested on the rising edge of the OR of all its
+    // enabled conditions.
+    stat_line: bool,
+
+    renderer: Renderer,
+    fifo: PixelFifo,
+
+    // Shades 0 (white) to 3 (black) after applying the palettes, or colour
+    // numbers in CGB mode.
+    frame: [u8; SCREEN_WIDTH * SCREEN_HEIGHT],
+    rgb_frame: [u16; SCREEN_WIDTH * SCREEN_HEIGHT],
+    frames: u64,
+}
+
+impl Default for Ppu {
+    fn default() -> Ppu {
+        Ppu::new()
+    }
+}
+
+impl Ppu {
+    pub fn new() -> Ppu {
+        Ppu::with_renderer(Renderer::Scanline)
+    }
+
+    pub fn with_renderer(renderer: Renderer) -> Ppu {
+        Ppu {
+            vram: [0; VRAM_SIZE * VRAM_BANKS],
+            vram_bank: 0,
+            oam: [0; OAM_SIZE],
+            cgb_mode: false,
+            dmg_compatibility: false,
+            bg_palettes: PaletteRam::new(),
+            obj_palettes: PaletteRam::new(),
+            lcdc: 0,
+            stat: 0,
+            scy: 0,
+            scx: 0,
+            ly: 0,
+            lyc: 0,
+            bgp: 0,
+            obp0: 0,
+            obp1: 0,
+            wy: 0,
+            wx: 0,
+            mode: Mode::HBlank,
+            line_dot: 0,
+            drawing_dots: MIN_DRAWING_DOTS,
+            line_sprites: Vec::with_capacity(MAX_SPRITES_PER_LINE),
+            window_triggered: false,
+            window_line: 0,
+            stat_line: false,
+            renderer,
+            fifo: PixelFifo::new(),
+            frame: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
+            rgb_frame: [DMG_COLORS[0]; SCREEN_WIDTH * SCREEN_HEIGHT],
+            frames: 0,
+        }
+    }
+
+    pub fn handles(address: u16) -> bool {
+        (VRAM_BEGIN..=VRAM_END).contains(&address)
+            || (OAM_BEGIN..=OAM_END).contains(&address)
+            || (LCDC_ADDR..=LYC_ADDR).contains(&address)
+            || (BGP_ADDR..=WX_ADDR).contains(&address)
+            || address == VBK_ADDR
+            || (BCPS_ADDR..=OCPD_ADDR).contains(&address)
+    }
+
+    /// Enables the CGB features: VRAM banks, background attributes and
+    /// colour palettes.
+    pub fn set_cgb_mode(&mut self, cgb_mode: bool) {
+        self.cgb_mode = cgb_mode;
+
+        if !cgb_mode {
+            self.vram_bank = 0;
+        }
+    }
+
+    pub fn cgb_mode(&self) -> bool {
+        self.cgb_mode
+    }
+
+    /// Enables the colours of a CGB in DMG mode, set with `set_dmg_palettes`.
+    pub fn set_dmg_compatibility(&mut self, dmg_compatibility: bool) {
+        self.dmg_compatibility = dmg_compatibility;
+    }
+
+    /// Writes the palettes that the CGB boot ROM chooses for DMG games.
+    pub fn set_dmg_palettes(&mut self, palettes: &DmgPalettes) {
+        for color in 0..4 {
+            let index = usize::from(color);
+            self.bg_palettes.set_color(0, color, palettes.bg[index]);
+            self.obj_palettes.set_color(0, color, palettes.obj0[index]);
+            self.obj_palettes.set_color(1, color, palettes.obj1[index]);
+        }
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            VRAM_BEGIN..=VRAM_END => {
+                if self.mode == Mode::Drawing {
+                    0xFF
+                } else {
+                    self.vram[self.cpu_vram_index(address)]
+                }
+            }
+            OAM_BEGIN..=OAM_END => {
+                if self.oam_blocked() {
+                    0xFF
+                } else {
+                    self.oam[usize::from(address - OAM_BEGIN)]
+                }
+            }
+            LCDC_ADDR => self.lcdc,
+            STAT_ADDR => 0x80 | self.stat | self.mode as u8,
+            SCY_ADDR => self.scy,
+            SCX_ADDR => self.scx,
+            LY_ADDR => self.ly,
+            LYC_ADDR => self.lyc,
+            BGP_ADDR => self.bgp,
+            OBP0_ADDR => self.obp0,
+            OBP1_ADDR => self.obp1,
+            WY_ADDR => self.wy,
+            WX_ADDR => self.wx,
+            VBK_ADDR if self.cgb_mode => 0xFE | self.vram_bank as u8,
+            VBK_ADDR => 0xFF,
+            BCPS_ADDR..=OCPD_ADDR if self.cgb_mode => self.read_palettes(address),
+            BCPS_ADDR..=OCPD_ADDR => 0xFF,
+            _ => panic!("Invalid PPU address: {:X}", address),
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            VRAM_BEGIN..=VRAM_END => {
+                if self.mode != Mode::Drawing {
+                    let index = self.cpu_vram_index(address);
+                    self.vram[index] = value;
+                }
+            }
+            OAM_BEGIN..=OAM_END => {
+                if !self.oam_blocked() {
+                    self.oam[usize::from(address - OAM_BEGIN)] = value;
+                }
+            }
+            LCDC_ADDR => self.write_lcdc(value),
+            // Only the interrupt selection bits can be written.
+            STAT_ADDR => self.stat = (self.stat & COINCIDENCE) | (value & 0x78),
+            SCY_ADDR => self.scy = value,
+            SCX_ADDR => self.scx = value,
+            LY_ADDR => (),
+            LYC_ADDR => {
+                self.lyc = value;
+                self.update_coincidence();
+            }
+            BGP_ADDR => self.bgp = value,
+            OBP0_ADDR => self.obp0 = value,
+            OBP1_ADDR => self.obp1 = value,
+            WY_ADDR => self.wy = value,
+            WX_ADDR => self.wx = value,
+            VBK_ADDR if self.cgb_mode => self.vram_bank = usize::from(value & 0x01),
+            VBK_ADDR => (),
+            BCPS_ADDR..=OCPD_ADDR if self.cgb_mode => self.write_palettes(address, value),
+            BCPS_ADDR..=OCPD_ADDR => (),
+            _ => panic!("Invalid PPU address: {:X}", address),
+        }
+    }
+
+    /// Writes to OAM regardless of the mode. Used by OAM DMA.
+    pub fn write_oam(&mut self, index: usize, value: u8) {
+        self.oam[index] = value;
+    }
+
+    pub fn tick(&mut self, m_cycles: u8) -> PpuInterrupts {
+        let mut interrupts = PpuInterrupts::default();
+
+        if self.lcdc & LCD_ENABLE == 0 {
+            return interrupts;
+        }
+
+        for _ in 0..(u16::from(m_cycles) * 4) {
+            self.step_dot(&mut interrupts);
+        }
+
+        interrupts
+    }
+
+    /// One shade (0-3) per pixel, row by row. Lines are drawn as the PPU
+    /// goes through them, so the frame is complete when VBlank starts. In CGB
+    /// mode, there are colour numbers instead, without the palettes.
+    pub fn frame(&self) -> &[u8] {
+        &self.frame
+    }
+
+    /// One RGB555 colour per pixel, row by row, with red in the lowest bits.
+    pub fn frame_rgb555(&self) -> &[u16] {
+        &self.rgb_frame
+    }
+
+    /// The frame as RGB888, 3 bytes per pixel.
+    pub fn frame_rgb(&self, correction: ColorCorrection) -> Vec<u8> {
+        let mut rgb = Vec::with_capacity(SCREEN_WIDTH * SCREEN_HEIGHT * 3);
+
+        for &color in self.rgb_frame.iter() {
+            rgb.extend_from_slice(&rgb555_to_rgb888(color, correction));
+        }
+
+        rgb
+    }
+
+    /// Number of frames completed. It increases when VBlank starts.
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn renderer(&self) -> Renderer {
+        self.renderer
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    fn step_dot(&mut self, interrupts: &mut PpuInterrupts) {
+        self.line_dot += 1;
+
+        match self.mode {
+            Mode::OamScan if self.line_dot == OAM_SCAN_DOTS => {
+                self.mode = Mode::Drawing;
+
+                if self.renderer == Renderer::PixelFifo {
+                    self.start_fifo_line();
+                }
+            }
+            Mode::Drawing => {
+                let line_complete = match self.renderer {
+                    Renderer::Scanline => {
+                        if self.line_dot == OAM_SCAN_DOTS + self.drawing_dots {
+                            self.render_scanline();
+                            true
+                        } else {
+                            false
+                        }
+                    }
+                    Renderer::PixelFifo => self.step_fifo(),
+                };
+
+                if line_complete {
+                    self.mode = Mode::HBlank;
+                }
+            }
+            Mode::HBlank | Mode::VBlank if self.line_dot == DOTS_PER_LINE => {
+                self.line_dot = 0;
+                self.ly = (self.ly + 1) % LINES_PER_FRAME;
+
+                if self.ly == SCREEN_HEIGHT as u8 {
+                    self.mode = Mode::VBlank;
+                    self.frames += 1;
+                    interrupts.vblank = true;
+                } else if self.ly == 0 {
+                    self.window_triggered = false;
+                    self.window_line = 0;
+                    self.start_line();
+                } else if self.ly < SCREEN_HEIGHT as u8 {
+                    self.start_line();
+                }
+
+                self.update_coincidence();
+            }
+            _ => (),
+        }
+
+        self.update_stat_line(interrupts);
+    }
+
+    fn write_lcdc(&mut self, value: u8) {
+        let was_enabled = self.lcdc & LCD_ENABLE != 0;
+        self.lcdc = value;
+
+        if was_enabled && value & LCD_ENABLE == 0 {
+            self.ly = 0;
+            self.line_dot = 0;
+            self.mode = Mode::HBlank;
+            self.stat_line = false;
+        } else if !was_enabled && value & LCD_ENABLE != 0 {
+            self.window_triggered = false;
+            self.window_line = 0;
+            self.start_line();
+            self.update_coincidence();
+        }
+    }
+
+    // Scans OAM for the sprites in the line. The drawing mode takes longer
+    // with fine scrolling and with sprites. This is an approximation of the
+    // real penalties.
+    fn start_line(&mut self) {
+        self.mode = Mode::OamScan;
+
+        if self.ly == self.wy {
+            self.window_triggered = true;
+        }
+
+        self.scan_oam();
+
+        self.drawing_dots =
+            MIN_DRAWING_DOTS + u16::from(self.scx % 8) + 6 * self.line_sprites.len() as u16;
+    }
+
+    fn scan_oam(&mut self) {
+        let height = self.sprite_height();
+        self.line_sprites.clear();
+
+        for (index, entry) in self.oam.chunks(4).enumerate() {
+            let sprite = Sprite {
+                y: entry[0],
+                x: entry[1],
+                tile: entry[2],
+                attributes: entry[3],
+                index: index as u8,
+            };
+
+            // Y is the position of the bottom of a 16 pixel sprite. 0 means
+            // hidden above the screen.
+            let top = i16::from(sprite.y) - 16;
+            let line = i16::from(self.ly);
+
+            if line >= top && line < top + i16::from(height) {
+                self.line_sprites.push(sprite);
+
+                if self.line_sprites.len() == MAX_SPRITES_PER_LINE {
+                    break;
+                }
+            }
+        }
+
+        // On DMG, the sprite with the smaller X has priority, and with the
+        // same X, the one that comes first in OAM. The sort is stable. In CGB
+        // mode, the one that comes first in OAM has priority, but they are
+        // sorted too because they are fetched from left to right.
+        self.line_sprites.sort_by_key(|sprite| sprite.x);
+    }
+
+    fn update_coincidence(&mut self) {
+        if self.ly == self.lyc {
+            self.stat |= COINCIDENCE;
+        } else {
+            self.stat &= !COINCIDENCE;
+        }
+    }
+
+    fn update_stat_line(&mut self, interrupts: &mut PpuInterrupts) {
+        let line = (self.stat & COINCIDENCE_STAT_INTERRUPT != 0 && self.ly == self.lyc)
+            || match self.mode {
+                Mode::HBlank => self.stat & HBLANK_STAT_INTERRUPT != 0,
+                Mode::VBlank => self.stat & VBLANK_STAT_INTERRUPT != 0,
+                Mode::OamScan => self.stat & OAM_STAT_INTERRUPT != 0,
+                Mode::Drawing => false,
+            };
+
+        if line && !self.stat_line {
+            interrupts.stat = true;
+        }
+
+        self.stat_line = line;
+    }
+
+    fn read_palettes(&self, address: u16) -> u8 {
+        match address {
+            BCPS_ADDR => self.bg_palettes.read_spec(),
+            OCPS_ADDR => self.obj_palettes.read_spec(),
+            // Palette RAM can not be accessed while drawing.
+            _ if self.mode == Mode::Drawing => 0xFF,
+            BCPD_ADDR => self.bg_palettes.read_data(),
+            _ => self.obj_palettes.read_data(),
+        }
+    }
+
+    fn write_palettes(&mut self, address: u16, value: u8) {
+        let blocked = self.mode == Mode::Drawing;
+
+        match address {
+            BCPS_ADDR => self.bg_palettes.write_spec(value),
+            BCPD_ADDR => self.bg_palettes.write_data(value, blocked),
+            OCPS_ADDR => self.obj_palettes.write_spec(value),
+            _ => self.obj_palettes.write_data(value, blocked),
+        }
+    }
+
+    // The CPU accesses the bank selected with VBK.
+    fn cpu_vram_index(&self, address: u16) -> usize {
+        self.vram_bank * VRAM_SIZE + usize::from(address - VRAM_BEGIN)
+    }
+
+    fn oam_blocked(&self) -> bool {
+        self.mode == Mode::OamScan || self.mode == Mode::Drawing
+    }
+
+    fn sprite_height(&self) -> u8 {
+        if self.lcdc & OBJ_SIZE != 0 {
+            16
+        } else {
+            8
+        }
+    }
+
+    fn render_scanline(&mut self) {
+        // Colour numbers and attributes before applying the palette. Sprites
+        // need them to know whether they are behind the background.
+        let mut bg_pixels = [BgPixel::default(); SCREEN_WIDTH];
+
+        let draw_window = self.lcdc & WINDOW_ENABLE != 0 && self.window_triggered && self.wx < 167;
+
+        if self.bg_enabled() {
+            for (x, pixel) in bg_pixels.iter_mut().enumerate() {
+                *pixel = if draw_window && x + 7 >= usize::from(self.wx) {
+                    let window_x = (x + 7 - usize::from(self.wx)) as u8;
+                    self.tile_map_pixel(WINDOW_TILE_MAP, window_x, self.window_line)
+                } else {
+                    let bg_x = self.scx.wrapping_add(x as u8);
+                    let bg_y = self.scy.wrapping_add(self.ly);
+                    self.tile_map_pixel(BG_TILE_MAP, bg_x, bg_y)
+                };
+            }
+        }
+
+        if draw_window {
+            self.window_line += 1;
+        }
+
+        for (x, &pixel) in bg_pixels.iter().enumerate() {
+            self.put_pixel(x, self.bg_output(pixel));
+        }
+
+        if self.lcdc & OBJ_ENABLE != 0 {
+            self.render_sprites(&bg_pixels);
+        }
+    }
+
+    fn render_sprites(&mut self, bg_pixels: &[BgPixel; SCREEN_WIDTH]) {
+        for (x, &bg_pixel) in bg_pixels.iter().enumerate() {
+            // The sprite with priority with a non transparent pixel wins, even
+            // if it ends up behind the background.
+            let mut pixels = self.line_sprites.iter().filter_map(|sprite| {
+                let sprite_x = x + 8;
+                let left = usize::from(sprite.x);
+
+                if sprite_x < left || sprite_x >= left + 8 {
+                    return None;
+                }
+
+                match self.sprite_pixel(sprite, (sprite_x - left) as u8) {
+                    0 => None,
+                    color => Some((sprite, color)),
+                }
+            });
+
+            let pixel = if self.cgb_mode {
+                pixels.min_by_key(|(sprite, _)| sprite.index)
+            } else {
+                pixels.next()
+            };
+
+            if let Some((sprite, color)) = pixel {
+                if self.sprite_visible(bg_pixel, sprite.attributes) {
+                    let output = self.sprite_output(sprite.attributes, color);
+                    self.put_pixel(x, output);
+                }
+            }
+        }
+    }
+
+    // On DMG, LCDC bit 0 disables both the background and the window. In CGB
+    // mode, they are always drawn and the bit makes sprites go over them.
+    fn bg_enabled(&self) -> bool {
+        self.cgb_mode || self.lcdc & BG_ENABLE != 0
+    }
+
+    fn sprite_visible(&self, bg_pixel: BgPixel, sprite_attributes: u8) -> bool {
+        if bg_pixel.color == 0 || (self.cgb_mode && self.lcdc & BG_ENABLE == 0) {
+            return true;
+        }
+
+        sprite_attributes & BEHIND_BG == 0 && bg_pixel.attributes & BG_PRIORITY == 0
+    }
+
+    // The value for the shade frame and the RGB555 colour of a pixel.
+    fn bg_output(&self, pixel: BgPixel) -> (u8, u16) {
+        if self.cgb_mode {
+            let palette = pixel.attributes & CGB_PALETTE;
+            (pixel.color, self.bg_palettes.color(palette, pixel.color))
+        } else {
+            let shade = Ppu::shade(self.bgp, pixel.color);
+            (shade, self.dmg_color(&self.bg_palettes, 0, shade))
+        }
+    }
+
+    fn sprite_output(&self, attributes: u8, color: u8) -> (u8, u16) {
+        if self.cgb_mode {
+            let palette = attributes & CGB_PALETTE;
+            (color, self.obj_palettes.color(palette, color))
+        } else {
+            let shade = Ppu::shade(self.sprite_palette(attributes), color);
+            let palette = (attributes & DMG_PALETTE != 0) as u8;
+            (shade, self.dmg_color(&self.obj_palettes, palette, shade))
+        }
+    }
+
+    fn dmg_color(&self, palettes: &PaletteRam, palette: u8, shade: u8) -> u16 {
+        if self.dmg_compatibility {
+            palettes.color(palette, shade)
+        } else {
+            DMG_COLORS[usize::from(shade)]
+        }
+    }
+
+    fn put_pixel(&mut self, x: usize, (value, color): (u8, u16)) {
+        let index = usize::from(self.ly) * SCREEN_WIDTH + x;
+        self.frame[index] = value;
+        self.rgb_frame[index] = color;
+    }
+
+    // Colour number of the pixel in the given column, 0 being the leftmost on
+    // screen, of the sprite in the current line.
+    fn sprite_pixel(&self, sprite: &Sprite, col: u8) -> u8 {
+        let height = self.sprite_height();
+        let mut col = col;
+        let mut row = self.ly.wrapping_add(16).wrapping_sub(sprite.y);
+
+        if sprite.attributes & X_FLIP != 0 {
+            col = 7 - col;
+        }
+
+        if sprite.attributes & Y_FLIP != 0 {
+            row = height - 1 - row;
+        }
+
+        // In 8x16 mode, the top tile is always even.
+        let tile = if height == 16 {
+            sprite.tile & 0xFE
+        } else {
+            sprite.tile
+        };
+
+        self.tile_pixel(
+            self.tile_bank(sprite.attributes),
+            0x8000 + u16::from(tile) * 16,
+            row,
+            col,
+        )
+    }
+
+    fn sprite_palette(&self, attributes: u8) -> u8 {
+        if attributes & DMG_PALETTE != 0 {
+            self.obp1
+        } else {
+            self.obp0
+        }
+    }
+
+    // Pixel at (x, y) of the 256x256 map selected by the given LCDC bit.
+    fn tile_map_pixel(&self, map_select_bit: u8, x: u8, y: u8) -> BgPixel {
+        let map_index = self.tile_map_index(map_select_bit, x / 8, y / 8);
+        let tile = self.vram[map_index];
+        let attributes = self.bg_attributes(map_index);
+
+        let mut row = y % 8;
+        let mut col = x % 8;
+
+        if attributes & X_FLIP != 0 {
+            col = 7 - col;
+        }
+
+        if attributes & Y_FLIP != 0 {
+            row = 7 - row;
+        }
+
+        BgPixel {
+            color: self.tile_pixel(
+                self.tile_bank(attributes),
+                self.tile_address(tile),
+                row,
+                col,
+            ),
+            attributes,
+        }
+    }
+
+    // Index in VRAM of the tile number in the given column and row of a map.
+    fn tile_map_index(&self, map_select_bit: u8, col: u8, row: u8) -> usize {
+        let map = if self.lcdc & map_select_bit != 0 {
+            0x9C00
+        } else {
+            0x9800
+        };
+
+        usize::from(map + u16::from(row) * 32 + u16::from(col) - VRAM_BEGIN)
+    }
+
+    // In CGB mode, the attributes of each tile in the maps are in VRAM bank 1.
+    fn bg_attributes(&self, map_index: usize) -> u8 {
+        if self.cgb_mode {
+            self.vram[VRAM_SIZE + map_index]
+        } else {
+            0
+        }
+    }
+
+    // VRAM bank of the tile data, that can only be 1 in CGB mode.
+    fn tile_bank(&self, attributes: u8) -> usize {
+        if self.cgb_mode && attributes & TILE_BANK != 0 {
+            1
+        } else {
+            0
+        }
+    }
+
+    // The background and the window either use tiles 0-255 from 0x8000, or
+    // tiles -128-127 from 0x9000.
+    fn tile_address(&self, tile: u8) -> u16 {
+        if self.lcdc & TILE_DATA != 0 {
+            0x8000 + u16::from(tile) * 16
+        } else {
+            (0x9000 + i32::from(tile as i8) * 16) as u16
+        }
+    }
+
+    // Each row of a tile is 2 bytes. The first has the low bits of the 8
+    // pixels and the second the high bits, leftmost pixel first.
+    fn tile_pixel(&self, bank: usize, tile_address: u16, row: u8, col: u8) -> u8 {
+        let address =
+            bank * VRAM_SIZE + usize::from(tile_address - VRAM_BEGIN) + usize::from(row) * 2;
+        let low = self.vram[address];
+        let high = self.vram[address + 1];
+        let bit = 7 - col;
+
+        (((high >> bit) & 1) << 1) | ((low >> bit) & 1)
+    }
+
+    fn shade(palette: u8, color: u8) -> u8 {
+        (palette >> (color * 2)) & 0b11
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The LCD is off so that VRAM and OAM can be written at any time.
+    fn ppu_with_palettes() -> Ppu {
+        let mut ppu = Ppu::new();
+        ppu.write(BGP_ADDR, 0b1110_0100);
+        ppu.write(OBP0_ADDR, 0b1110_0100);
+        ppu
+    }
+
+    fn enabled_ppu() -> Ppu {
+        let mut ppu = ppu_with_palettes();
+        ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA);
+        ppu
+    }
+
+    fn run_until_frame(ppu: &mut Ppu) {
+        let frames = ppu.frames();
+
+        while ppu.frames() == frames {
+            ppu.tick(1);
+        }
+    }
+
+    // Makes tile 1 all colour 3 and puts it in the first position of the
+    // background map.
+    fn draw_black_tile_at_origin(ppu: &mut Ppu) {
+        for i in 0..16 {
+            ppu.write(0x8010 + i, 0xFF);
+        }
+
+        ppu.write(0x9800, 1);
+    }
+
+    #[test]
+    fn registers_around_oam_dma_are_handled() {
+        assert!(Ppu::handles(LYC_ADDR));
+        assert!(!Ppu::handles(0xFF46));
+        assert!(Ppu::handles(BGP_ADDR));
+    }
+
+    #[test]
+    fn mode_timings() {
+        let mut ppu = enabled_ppu();
+        assert_eq!(ppu.mode(), Mode::OamScan);
+
+        ppu.tick(20);
+        assert_eq!(ppu.mode(), Mode::Drawing);
+
+        ppu.tick(43);
+        assert_eq!(ppu.mode(), Mode::HBlank);
+
+        ppu.tick(51);
+        assert_eq!(ppu.mode(), Mode::OamScan);
+        assert_eq!(ppu.read(LY_ADDR), 1);
+    }
+
+    #[test]
+    fn vblank_interrupt_at_line_144() {
+        let mut ppu = enabled_ppu();
+
+        for _ in 0..143 {
+            assert!(!ppu.tick(114).vblank);
+        }
+
+        assert!(ppu.tick(114).vblank);
+        assert_eq!(ppu.read(LY_ADDR), 144);
+        assert_eq!(ppu.mode(), Mode::VBlank);
+    }
+
+    #[test]
+    fn frame_is_154_lines() {
+        let mut ppu = enabled_ppu();
+
+        for _ in 0..154 {
+            ppu.tick(114);
+        }
+
+        assert_eq!(ppu.read(LY_ADDR), 0);
+        assert_eq!(ppu.mode(), Mode::OamScan);
+    }
+
+    #[test]
+    fn lyc_stat_interrupt() {
+        let mut ppu = enabled_ppu();
+        ppu.write(LYC_ADDR, 2);
+        ppu.write(STAT_ADDR, COINCIDENCE_STAT_INTERRUPT);
+
+        assert!(!ppu.tick(114).stat);
+        assert!(ppu.tick(114).stat);
+        assert_eq!(ppu.read(STAT_ADDR) & COINCIDENCE, COINCIDENCE);
+    }
+
+    #[test]
+    fn hblank_stat_interrupt() {
+        let mut ppu = enabled_ppu();
+        ppu.write(STAT_ADDR, HBLANK_STAT_INTERRUPT);
+
+        assert!(!ppu.tick(62).stat);
+        assert!(ppu.tick(1).stat);
+        assert!(!ppu.tick(1).stat);
+    }
+
+    #[test]
+    fn stat_register() {
+        let mut ppu = enabled_ppu();
+
+        ppu.write(STAT_ADDR, 0xFF);
+
+        assert_eq!(ppu.read(STAT_ADDR), 0xFC | Mode::OamScan as u8);
+    }
+
+    #[test]
+    fn lcd_off_resets_ly() {
+        let mut ppu = enabled_ppu();
+        for _ in 0..3 {
+            ppu.tick(114);
+        }
+
+        ppu.write(LCDC_ADDR, 0);
+
+        assert_eq!(ppu.read(LY_ADDR), 0);
+        assert_eq!(ppu.read(STAT_ADDR) & 0x03, 0);
+        for _ in 0..154 {
+            assert_eq!(ppu.tick(114), PpuInterrupts::default());
+        }
+    }
+
+    #[test]
+    fn vram_banks_in_cgb_mode() {
+        let mut ppu = Ppu::new();
+        ppu.set_cgb_mode(true);
+
+        ppu.write(0x8000, 0x11);
+        ppu.write(VBK_ADDR, 0x01);
+        ppu.write(0x8000, 0x22);
+
+        assert_eq!(ppu.read(VBK_ADDR), 0xFF);
+        assert_eq!(ppu.read(0x8000), 0x22);
+
+        ppu.write(VBK_ADDR, 0x00);
+        assert_eq!(ppu.read(VBK_ADDR), 0xFE);
+        assert_eq!(ppu.read(0x8000), 0x11);
+    }
+
+    #[test]
+    fn no_vram_banks_in_dmg_mode() {
+        let mut ppu = Ppu::new();
+
+        ppu.write(VBK_ADDR, 0x01);
+        ppu.write(0x8000, 0x22);
+
+        assert_eq!(ppu.read(VBK_ADDR), 0xFF);
+        assert_eq!(ppu.vram[VRAM_SIZE], 0x00);
+    }
+
+    #[test]
+    fn vram_not_accessible_while_drawing() {
+        let mut ppu = enabled_ppu();
+        ppu.tick(20);
+
+        ppu.write(0x8000, 0x42);
+
+        assert_eq!(ppu.read(0x8000), 0xFF);
+        ppu.tick(43);
+        assert_eq!(ppu.read(0x8000), 0);
+    }
+
+    #[test]
+    fn render_background() {
+        let mut ppu = ppu_with_palettes();
+        draw_black_tile_at_origin(&mut ppu);
+
+        ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA);
+
+        run_until_frame(&mut ppu);
+
+        assert_eq!(ppu.frame()[0], 3);
+        assert_eq!(ppu.frame()[7 * SCREEN_WIDTH + 7], 3);
+        assert_eq!(ppu.frame()[8], 0);
+        assert_eq!(ppu.frame()[8 * SCREEN_WIDTH], 0);
+    }
+
+    #[test]
+    fn render_scrolled_background() {
+        let mut ppu = ppu_with_palettes();
+        draw_black_tile_at_origin(&mut ppu);
+        ppu.write(SCX_ADDR, 4);
+        ppu.write(SCY_ADDR, 4);
+
+        ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA);
+
+        run_until_frame(&mut ppu);
+
+        assert_eq!(ppu.frame()[3 * SCREEN_WIDTH + 3], 3);
+        assert_eq!(ppu.frame()[4], 0);
+        assert_eq!(ppu.frame()[4 * SCREEN_WIDTH], 0);
+    }
+
+    #[test]
+    fn render_window() {
+        let mut ppu = ppu_with_palettes();
+        for i in 0..16 {
+            ppu.write(0x8010 + i, 0xFF);
+        }
+        ppu.write(0x9C00, 1);
+        ppu.write(WY_ADDR, 10);
+        ppu.write(WX_ADDR, 27);
+        ppu.write(
+            LCDC_ADDR,
+            LCD_ENABLE | BG_ENABLE | TILE_DATA | WINDOW_ENABLE | WINDOW_TILE_MAP,
+        );
+
+        run_until_frame(&mut ppu);
+
+        assert_eq!(ppu.frame()[10 * SCREEN_WIDTH + 20], 3);
+        assert_eq!(ppu.frame()[10 * SCREEN_WIDTH + 19], 0);
+        assert_eq!(ppu.frame()[9 * SCREEN_WIDTH + 20], 0);
+    }
+
+    #[test]
+    fn render_sprite() {
+        let mut ppu = ppu_with_palettes();
+        for i in 0..16 {
+            ppu.write(0x8010 + i, 0xFF);
+        }
+        ppu.write(0xFE00, 16 + 2);
+        ppu.write(0xFE01, 8 + 3);
+        ppu.write(0xFE02, 1);
+        ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA | OBJ_ENABLE);
+
+        run_until_frame(&mut ppu);
+
+        assert_eq!(ppu.frame()[2 * SCREEN_WIDTH + 3], 3);
+        assert_eq!(ppu.frame()[2 * SCREEN_WIDTH + 2], 0);
+        assert_eq!(ppu.frame()[SCREEN_WIDTH + 3], 0);
+    }
+
+    #[test]
+    fn sprite_behind_background() {
+        let mut ppu = ppu_with_palettes();
+        draw_black_tile_at_origin(&mut ppu);
+        // Tile 2 is all colour 1.
+        for i in 0..8 {
+            ppu.write(0x8020 + i * 2, 0xFF);
+        }
+        ppu.write(0xFE00, 16);
+        ppu.write(0xFE01, 8 + 4);
+        ppu.write(0xFE02, 2);
+        ppu.write(0xFE03, BEHIND_BG);
+        ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA | OBJ_ENABLE);
+
+        run_until_frame(&mut ppu);
+
+        assert_eq!(ppu.frame()[4], 3);
+        assert_eq!(ppu.frame()[8], 1);
+    }
+
+    #[test]
+    fn at_most_10_sprites_per_line() {
+        let mut ppu = ppu_with_palettes();
+        for i in 0..16 {
+            ppu.write(0x8010 + i, 0xFF);
+        }
+        for sprite in 0..11 {
+            ppu.write(0xFE00 + sprite * 4, 16);
+            ppu.write(0xFE01 + sprite * 4, 8 + sprite as u8 * 8);
+            ppu.write(0xFE02 + sprite * 4, 1);
+        }
+        ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA | OBJ_ENABLE);
+
+        run_until_frame(&mut ppu);
+
+        assert_eq!(ppu.frame()[9 * 8], 3);
+        assert_eq!(ppu.frame()[10 * 8], 0);
+    }
+
+    // CGB mode with a different colour in each palette entry. The LCD is off.
+    fn cgb_ppu() -> Ppu {
+        let mut ppu = Ppu::new();
+        ppu.set_cgb_mode(true);
+
+        ppu.write(BCPS_ADDR, 0x80);
+        ppu.write(OCPS_ADDR, 0x80);
+        for i in 0..32 {
+            ppu.write(BCPD_ADDR, i as u8);
+            ppu.write(BCPD_ADDR, 0x00);
+            ppu.write(OCPD_ADDR, i as u8);
+            ppu.write(OCPD_ADDR, 0x40);
+        }
+
+        // Tile 1 is all colour 3 and tile 2 colour 1.
+        for i in 0..16 {
+            ppu.write(0x8010 + i, 0xFF);
+        }
+        for i in 0..8 {
+            ppu.write(0x8020 + i * 2, 0xFF);
+        }
+
+        ppu
+    }
+
+    #[test]
+    fn palette_registers_only_in_cgb_mode() {
+        let mut ppu = Ppu::new();
+
+        ppu.write(BCPS_ADDR, 0x81);
+
+        assert_eq!(ppu.read(BCPS_ADDR), 0xFF);
+        ppu.set_cgb_mode(true);
+        ppu.write(BCPS_ADDR, 0x81);
+        assert_eq!(ppu.read(BCPS_ADDR), 0xC1);
+    }
+
+    #[test]
+    fn palette_data_not_accessible_while_drawing() {
+        let mut ppu = cgb_ppu();
+        ppu.write(LCDC_ADDR, LCD_ENABLE);
+        ppu.tick(20);
+        assert_eq!(ppu.mode(), Mode::Drawing);
+
+        ppu.write(BCPS_ADDR, 0x00);
+
+        assert_eq!(ppu.read(BCPD_ADDR), 0xFF);
+    }
+
+    #[test]
+    fn render_cgb_background_attributes() {
+        let mut ppu = cgb_ppu();
+        ppu.write(0x9800, 1);
+        ppu.write(0x9801, 2);
+        ppu.write(VBK_ADDR, 1);
+        // Palette 2, and tile 2 from bank 1, which is empty and flipped.
+        ppu.write(0x9800, 0x02);
+        ppu.write(0x9801, 0x08 | X_FLIP);
+        ppu.write(0x8020, 0x01);
+        ppu.write(VBK_ADDR, 0);
+        ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA);
+
+        run_until_frame(&mut ppu);
+
+        assert_eq!(ppu.frame_rgb555()[0], 2 * 4 + 3);
+        // Colour 1 of palette 0, only in the first pixel because of the flip.
+        assert_eq!(ppu.frame_rgb555()[8], 1);
+        assert_eq!(ppu.frame_rgb555()[9], 0);
+        assert_eq!(ppu.frame_rgb555()[SCREEN_WIDTH + 8], 0);
+    }
+
+    #[test]
+    fn render_cgb_sprite_palette() {
+        let mut ppu = cgb_ppu();
+        ppu.write(0xFE00, 16);
+        ppu.write(0xFE01, 8);
+        ppu.write(0xFE02, 1);
+        ppu.write(0xFE03, 0x05);
+        ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA | OBJ_ENABLE);
+
+        run_until_frame(&mut ppu);
+
+        assert_eq!(ppu.frame_rgb555()[0], 0x4000 | (5 * 4 + 3));
+    }
+
+    #[test]
+    fn cgb_sprite_priority_is_oam_order() {
+        let mut ppu = cgb_ppu();
+        ppu.write(0xFE00, 16);
+        ppu.write(0xFE01, 12);
+        ppu.write(0xFE02, 1);
+        ppu.write(0xFE03, 0x01);
+        ppu.write(0xFE04, 16);
+        ppu.write(0xFE05, 8);
+        ppu.write(0xFE06, 1);
+        ppu.write(0xFE07, 0x02);
+        ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA | OBJ_ENABLE);
+
+        run_until_frame(&mut ppu);
+
+        assert_eq!(ppu.frame_rgb555()[4] & 0xFF, 4 + 3);
+    }
+
+    #[test]
+    fn cgb_master_priority() {
+        let mut ppu = cgb_ppu();
+        ppu.write(0x9800, 1);
+        ppu.write(VBK_ADDR, 1);
+        ppu.write(0x9800, BG_PRIORITY);
+        ppu.write(VBK_ADDR, 0);
+        ppu.write(0xFE00, 16);
+        ppu.write(0xFE01, 8);
+        ppu.write(0xFE02, 2);
+        ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA | OBJ_ENABLE);
+
+        run_until_frame(&mut ppu);
+        assert_eq!(ppu.frame_rgb555()[0], 3);
+
+        // Without LCDC bit 0, sprites are always on top, but the background
+        // is still drawn.
+        ppu.write(LCDC_ADDR, LCD_ENABLE | TILE_DATA | OBJ_ENABLE);
+        run_until_frame(&mut ppu);
+        assert_eq!(ppu.frame_rgb555()[0], 0x4000 | 1);
+        assert_eq!(ppu.frame_rgb555()[8], 0);
+    }
+
+    #[test]
+    fn dmg_frame_in_rgb() {
+        let mut ppu = ppu_with_palettes();
+        draw_black_tile_at_origin(&mut ppu);
+        ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA);
+
+        run_until_frame(&mut ppu);
+
+        let rgb = ppu.frame_rgb(ColorCorrection::None);
+        assert_eq!(rgb.len(), SCREEN_WIDTH * SCREEN_HEIGHT * 3);
+        assert_eq!(&rgb[0..3], &[0, 0, 0]);
+        assert_eq!(&rgb[24..27], &[255, 255, 255]);
+    }
+
+    #[test]
+    fn dmg_compatibility_palettes() {
+        let mut ppu = ppu_with_palettes();
+        ppu.set_dmg_compatibility(true);
+        ppu.set_dmg_palettes(&DmgPalettes {
+            bg: [0x0001, 0x0002, 0x0003, 0x0004],
+            obj0: [0x0011, 0x0012, 0x0013, 0x0014],
+            obj1: [0x0021, 0x0022, 0x0023, 0x0024],
+        });
+        // OBP1 maps colour 3 to shade 2.
+        ppu.write(OBP1_ADDR, 0b1000_0000);
+        draw_black_tile_at_origin(&mut ppu);
+        ppu.write(0xFE00, 16);
+        ppu.write(0xFE01, 16);
+        ppu.write(0xFE02, 1);
+        ppu.write(0xFE03, DMG_PALETTE);
+        ppu.write(LCDC_ADDR, LCD_ENABLE | BG_ENABLE | TILE_DATA | OBJ_ENABLE);
+
+        run_until_frame(&mut ppu);
+
+        assert_eq!(ppu.frame_rgb555()[0], 0x0004);
+        assert_eq!(ppu.frame_rgb555()[8], 0x0023);
+        assert_eq!(ppu.frame_rgb555()[16], 0x0001);
+        assert_eq!(ppu.frame()[8], 2);
+    }
+}
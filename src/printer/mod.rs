@@ -0,0 +1,527 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::serial::SerialDevice;
+
+mod png;
+
+pub const PRINTOUT_WIDTH: usize = 160;
+
+const TILES_PER_ROW: usize = PRINTOUT_WIDTH / 8;
+const BYTES_PER_TILE: usize = 16;
+
+// The printer memory holds 9 bands of 2 rows of tiles.
+const BUFFER_SIZE: usize = 9 * 2 * TILES_PER_ROW * BYTES_PER_TILE;
+
+// Blank lines added for each unit of the margins of the print command.
+const LINES_PER_MARGIN_UNIT: usize = 8;
+
+// Shades 0 (white) to 3 (black) in the PNG.
+const GREYS: [u8; 4] = [0xFF, 0xAA, 0x55, 0x00];
+
+// Commands
+const INIT: u8 = 0x01;
+const PRINT: u8 = 0x02;
+const DATA: u8 = 0x04;
+const STATUS: u8 = 0x0F;
+
+// Status bits
+const CHECKSUM_ERROR: u8 = 0b0000_0001;
+const PRINTING: u8 = 0b0000_0010;
+const UNPROCESSED_DATA: u8 = 0b0000_1000;
+
+// The answer to the first byte after the checksum, to say that a printer is
+// connected.
+const ALIVE: u8 = 0x81;
+
+// A print is reported as in progress in this many packets after it.
+const PRINTING_PACKETS: u8 = 2;
+
+/// An image printed on paper. Pixels are shades from 0 (white) to 3
+/// (black), row by row.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Printout {
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl Printout {
+    pub fn width(&self) -> usize {
+        PRINTOUT_WIDTH
+    }
+
+    pub fn to_png(&self) -> Vec<u8> {
+        let greys: Vec<u8> = self
+            .pixels
+            .iter()
+            .map(|&shade| GREYS[usize::from(shade)])
+            .collect();
+
+        png::encode_greyscale(PRINTOUT_WIDTH, self.height, &greys)
+    }
+}
+
+// Position in the packet being received. Packets are: 0x88, 0x33, command,
+// compression flag, data length (2 bytes, little endian), data, checksum (2
+// bytes, little endian), and 2 more bytes to which the printer answers with
+// ALIVE and its status.
+#[derive(Clone, Copy, PartialEq)]
+enum PacketState {
+    Magic1,
+    Magic2,
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    Alive,
+    Status,
+}
+
+/// The Game Boy Printer. It is connected to the serial port, with the Game
+/// Boy as the master.
+pub struct Printer {
+    state: PacketState,
+    command: u8,
+    compressed: bool,
+    length: u16,
+    data: Vec<u8>,
+    checksum: u16,
+    computed_checksum: u16,
+
+    status: u8,
+    printing_packets: u8,
+
+    // Tile data received with data packets, decompressed.
+    buffer: Vec<u8>,
+
+    // Printed lines that have not been cut yet. A print with no margin after
+    // it continues on the next print.
+    page: Vec<u8>,
+
+    printouts: Rc<RefCell<Vec<Printout>>>,
+    output_dir: Option<PathBuf>,
+}
+
+impl Default for Printer {
+    fn default() -> Printer {
+        Printer::new()
+    }
+}
+
+impl Printer {
+    /// Creates a printer that keeps the printouts in memory.
+    pub fn new() -> Printer {
+        Printer {
+            state: PacketState::Magic1,
+            command: 0,
+            compressed: false,
+            length: 0,
+            data: Vec::new(),
+            checksum: 0,
+            computed_checksum: 0,
+            status: 0,
+            printing_packets: 0,
+            buffer: Vec::with_capacity(BUFFER_SIZE),
+            page: Vec::new(),
+            printouts: Rc::new(RefCell::new(Vec::new())),
+            output_dir: None,
+        }
+    }
+
+    /// Creates a printer that also writes each printout to a PNG file in the
+    /// given directory.
+    pub fn with_output_dir<P: Into<PathBuf>>(output_dir: P) -> Printer {
+        let mut printer = Printer::new();
+        printer.output_dir = Some(output_dir.into());
+        printer
+    }
+
+    /// A handle to the printouts, that can be kept after the printer is
+    /// connected.
+    pub fn printouts(&self) -> Rc<RefCell<Vec<Printout>>> {
+        Rc::clone(&self.printouts)
+    }
+
+    fn receive(&mut self, byte: u8) -> u8 {
+        let mut reply = 0x00;
+
+        self.state = match self.state {
+            PacketState::Magic1 if byte == 0x88 => PacketState::Magic2,
+            PacketState::Magic1 => PacketState::Magic1,
+            PacketState::Magic2 if byte == 0x33 => PacketState::Command,
+            PacketState::Magic2 => PacketState::Magic1,
+            PacketState::Command => {
+                self.command = byte;
+                self.computed_checksum = u16::from(byte);
+                PacketState::Compression
+            }
+            PacketState::Compression => {
+                self.compressed = byte & 0x01 != 0;
+                self.add_to_checksum(byte);
+                PacketState::LengthLow
+            }
+            PacketState::LengthLow => {
+                self.length = u16::from(byte);
+                self.add_to_checksum(byte);
+                PacketState::LengthHigh
+            }
+            PacketState::LengthHigh => {
+                self.length |= u16::from(byte) << 8;
+                self.add_to_checksum(byte);
+                self.data.clear();
+
+                if self.length == 0 {
+                    PacketState::ChecksumLow
+                } else {
+                    PacketState::Data
+                }
+            }
+            PacketState::Data => {
+                self.data.push(byte);
+                self.add_to_checksum(byte);
+
+                if self.data.len() == usize::from(self.length) {
+                    PacketState::ChecksumLow
+                } else {
+                    PacketState::Data
+                }
+            }
+            PacketState::ChecksumLow => {
+                self.checksum = u16::from(byte);
+                PacketState::ChecksumHigh
+            }
+            PacketState::ChecksumHigh => {
+                self.checksum |= u16::from(byte) << 8;
+                self.process_packet();
+                PacketState::Alive
+            }
+            PacketState::Alive => {
+                reply = ALIVE;
+                PacketState::Status
+            }
+            PacketState::Status => {
+                reply = self.status;
+                PacketState::Magic1
+            }
+        };
+
+        reply
+    }
+
+    fn add_to_checksum(&mut self, byte: u8) {
+        self.computed_checksum = self.computed_checksum.wrapping_add(u16::from(byte));
+    }
+
+    fn process_packet(&mut self) {
+        if self.printing_packets > 0 {
+            self.printing_packets -= 1;
+
+            if self.printing_packets == 0 {
+                self.status &= !PRINTING;
+            }
+        }
+
+        if self.checksum != self.computed_checksum {
+            self.status |= CHECKSUM_ERROR;
+            return;
+        }
+
+        self.status &= !CHECKSUM_ERROR;
+
+        match self.command {
+            INIT => {
+                self.buffer.clear();
+                self.status = 0;
+                self.printing_packets = 0;
+            }
+            DATA => {
+                let data = if self.compressed {
+                    Printer::decompress(&self.data)
+                } else {
+                    self.data.clone()
+                };
+
+                let space = BUFFER_SIZE - self.buffer.len();
+                self.buffer
+                    .extend_from_slice(&data[..data.len().min(space)]);
+
+                if !self.buffer.is_empty() {
+                    self.status |= UNPROCESSED_DATA;
+                }
+            }
+            PRINT if self.data.len() == 4 => {
+                self.print(self.data[1], self.data[2]);
+                self.status = (self.status & !UNPROCESSED_DATA) | PRINTING;
+                self.printing_packets = PRINTING_PACKETS;
+            }
+            // Status packets only ask for the status, which is always sent.
+            STATUS => (),
+            _ => (),
+        }
+    }
+
+    // Run length encoding: a control byte with bit 7 set is followed by a
+    // byte repeated (control & 0x7F) + 2 times. Otherwise, it is followed by
+    // control + 1 bytes to copy.
+    fn decompress(data: &[u8]) -> Vec<u8> {
+        let mut decompressed = Vec::new();
+        let mut i = 0;
+
+        while i < data.len() {
+            let control = data[i];
+            i += 1;
+
+            if control & 0x80 != 0 {
+                if let Some(&byte) = data.get(i) {
+                    let count = usize::from(control & 0x7F) + 2;
+                    decompressed.resize(decompressed.len() + count, byte);
+                }
+                i += 1;
+            } else {
+                let end = (i + usize::from(control) + 1).min(data.len());
+                decompressed.extend_from_slice(&data[i..end]);
+                i = end;
+            }
+        }
+
+        decompressed
+    }
+
+    // The margins byte has the lines to feed before the image in the upper
+    // nibble, and after it in the lower one. The palette maps colour numbers
+    // to shades like BGP, 0 meaning the default one.
+    fn print(&mut self, margins: u8, palette: u8) {
+        let palette = if palette == 0 { 0xE4 } else { palette };
+        let margin_before = usize::from(margins >> 4) * LINES_PER_MARGIN_UNIT;
+        let margin_after = usize::from(margins & 0x0F) * LINES_PER_MARGIN_UNIT;
+
+        self.feed_paper(margin_before);
+
+        let tile_rows = self.buffer.len() / (TILES_PER_ROW * BYTES_PER_TILE);
+
+        for y in 0..tile_rows * 8 {
+            for x in 0..PRINTOUT_WIDTH {
+                let tile = (y / 8) * TILES_PER_ROW + x / 8;
+                let address = tile * BYTES_PER_TILE + (y % 8) * 2;
+                let low = self.buffer[address];
+                let high = self.buffer[address + 1];
+                let bit = 7 - (x % 8);
+                let color = (((high >> bit) & 1) << 1) | ((low >> bit) & 1);
+
+                self.page.push((palette >> (color * 2)) & 0b11);
+            }
+        }
+
+        self.buffer.clear();
+
+        if margin_after > 0 {
+            self.feed_paper(margin_after);
+            self.cut_page();
+        }
+    }
+
+    fn feed_paper(&mut self, lines: usize) {
+        self.page
+            .resize(self.page.len() + lines * PRINTOUT_WIDTH, 0);
+    }
+
+    fn cut_page(&mut self) {
+        let printout = Printout {
+            height: self.page.len() / PRINTOUT_WIDTH,
+            pixels: self.page.split_off(0),
+        };
+
+        if let Some(dir) = &self.output_dir {
+            let number = self.printouts.borrow().len() + 1;
+            let path = dir.join(format!("printout-{:03}.png", number));
+
+            if let Err(err) = Printer::write_png(&path, &printout) {
+                eprintln!("Could not write {}: {}", path.display(), err);
+            }
+        }
+
+        self.printouts.borrow_mut().push(printout);
+    }
+
+    fn write_png(path: &Path, printout: &Printout) -> io::Result<()> {
+        fs::write(path, printout.to_png())
+    }
+}
+
+impl SerialDevice for Printer {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        self.receive(byte)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Sends a packet and returns the 2 bytes of the answer.
+    fn send_packet(printer: &mut Printer, command: u8, compressed: bool, data: &[u8]) -> (u8, u8) {
+        let length = data.len() as u16;
+        let mut packet = vec![0x88, 0x33, command, compressed as u8];
+        packet.extend_from_slice(&length.to_le_bytes());
+        packet.extend_from_slice(data);
+
+        let checksum = packet[2..]
+            .iter()
+            .fold(0_u16, |sum, &byte| sum.wrapping_add(u16::from(byte)));
+        packet.extend_from_slice(&checksum.to_le_bytes());
+
+        for &byte in packet.iter() {
+            assert_eq!(printer.exchange(byte), 0);
+        }
+
+        (printer.exchange(0), printer.exchange(0))
+    }
+
+    // 2 rows of 20 tiles. The first tile is colour 3, the rest colour 0.
+    fn band() -> Vec<u8> {
+        let mut band = vec![0; 2 * TILES_PER_ROW * BYTES_PER_TILE];
+        for byte in band.iter_mut().take(BYTES_PER_TILE) {
+            *byte = 0xFF;
+        }
+        band
+    }
+
+    #[test]
+    fn answers_alive_and_status() {
+        let mut printer = Printer::new();
+
+        assert_eq!(send_packet(&mut printer, INIT, false, &[]), (ALIVE, 0));
+    }
+
+    #[test]
+    fn data_is_unprocessed_until_printed() {
+        let mut printer = Printer::new();
+        send_packet(&mut printer, INIT, false, &[]);
+
+        let (_, status) = send_packet(&mut printer, DATA, false, &band());
+
+        assert_eq!(status, UNPROCESSED_DATA);
+    }
+
+    #[test]
+    fn wrong_checksum() {
+        let mut printer = Printer::new();
+
+        for &byte in [0x88, 0x33, STATUS, 0, 0, 0, 0x42, 0x00].iter() {
+            printer.exchange(byte);
+        }
+
+        assert_eq!(printer.exchange(0), ALIVE);
+        assert_eq!(printer.exchange(0), CHECKSUM_ERROR);
+    }
+
+    #[test]
+    fn print_with_margins() {
+        let mut printer = Printer::new();
+        let printouts = printer.printouts();
+        send_packet(&mut printer, INIT, false, &[]);
+        send_packet(&mut printer, DATA, false, &band());
+        send_packet(&mut printer, DATA, false, &[]);
+
+        let (_, status) = send_packet(&mut printer, PRINT, false, &[1, 0x11, 0xE4, 0x40]);
+
+        assert_eq!(status & PRINTING, PRINTING);
+
+        let printouts = printouts.borrow();
+        let printout = &printouts[0];
+        assert_eq!(printout.height, 8 + 16 + 8);
+        assert_eq!(printout.pixels[7 * PRINTOUT_WIDTH], 0);
+        assert_eq!(printout.pixels[8 * PRINTOUT_WIDTH], 3);
+        assert_eq!(printout.pixels[8 * PRINTOUT_WIDTH + 8], 0);
+    }
+
+    #[test]
+    fn printing_finishes() {
+        let mut printer = Printer::new();
+        send_packet(&mut printer, DATA, false, &band());
+        send_packet(&mut printer, PRINT, false, &[1, 0x01, 0xE4, 0x40]);
+
+        assert_eq!(send_packet(&mut printer, STATUS, false, &[]).1, PRINTING);
+        assert_eq!(send_packet(&mut printer, STATUS, false, &[]).1, 0);
+    }
+
+    #[test]
+    fn palette() {
+        let mut printer = Printer::new();
+        let printouts = printer.printouts();
+        send_packet(&mut printer, DATA, false, &band());
+
+        // Colour 3 is printed as shade 1, colour 0 as shade 2.
+        send_packet(&mut printer, PRINT, false, &[1, 0x01, 0b0110_0010, 0x40]);
+
+        let printouts = printouts.borrow();
+        assert_eq!(printouts[0].pixels[0], 1);
+        assert_eq!(printouts[0].pixels[8], 2);
+    }
+
+    #[test]
+    fn prints_without_margin_after_continue_the_page() {
+        let mut printer = Printer::new();
+        let printouts = printer.printouts();
+
+        send_packet(&mut printer, DATA, false, &band());
+        send_packet(&mut printer, PRINT, false, &[1, 0x00, 0xE4, 0x40]);
+        assert!(printouts.borrow().is_empty());
+
+        send_packet(&mut printer, DATA, false, &band());
+        send_packet(&mut printer, PRINT, false, &[1, 0x01, 0xE4, 0x40]);
+
+        assert_eq!(printouts.borrow()[0].height, 16 + 16 + 8);
+    }
+
+    #[test]
+    fn decompress_runs_and_literals() {
+        let data = [0x81, 0xAA, 0x01, 0x12, 0x34];
+
+        assert_eq!(
+            Printer::decompress(&data),
+            vec![0xAA, 0xAA, 0xAA, 0x12, 0x34]
+        );
+    }
+
+    #[test]
+    fn compressed_data_packet() {
+        let mut printer = Printer::new();
+        let printouts = printer.printouts();
+
+        // 16 bytes of 0xFF and 624 of 0x00, in runs of 129 and 108 bytes,
+        // which is exactly 2 rows of tiles.
+        let mut compressed = vec![0x8E, 0xFF];
+        for _ in 0..4 {
+            compressed.extend_from_slice(&[0xFF, 0x00]);
+        }
+        compressed.extend_from_slice(&[0x80 | 106, 0x00]);
+
+        send_packet(&mut printer, DATA, true, &compressed);
+        assert_eq!(printer.buffer.len(), 640);
+        send_packet(&mut printer, PRINT, false, &[1, 0x01, 0xE4, 0x40]);
+
+        let printouts = printouts.borrow();
+        assert_eq!(printouts[0].height, 16 + 8);
+        assert_eq!(printouts[0].pixels[0], 3);
+        assert_eq!(printouts[0].pixels[8], 0);
+    }
+
+    #[test]
+    fn png_of_printout() {
+        let printout = Printout {
+            height: 1,
+            pixels: vec![3; PRINTOUT_WIDTH],
+        };
+
+        let png = printout.to_png();
+
+        assert_eq!(&png[1..4], b"PNG");
+    }
+}
@@ -0,0 +1,188 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::cpu::CPU;
+use crate::serial::SerialDevice;
+
+// What each end of the cable has seen from its Game Boy, indexed by end.
+#[derive(Default)]
+struct CableState {
+    // SB of the Game Boys waiting for an external clock.
+    waiting: [Option<u8>; 2],
+
+    // Bytes sent by a master and not yet received.
+    incoming: [Option<u8>; 2],
+}
+
+/// One end of a link cable between two emulators in the same process.
+pub struct CableEnd {
+    state: Rc<RefCell<CableState>>,
+    end: usize,
+}
+
+/// Creates a link cable. Each end is connected to the serial port of a
+/// different emulator.
+pub fn cable() -> (CableEnd, CableEnd) {
+    let state = Rc::new(RefCell::new(CableState::default()));
+
+    (
+        CableEnd {
+            state: Rc::clone(&state),
+            end: 0,
+        },
+        CableEnd { state, end: 1 },
+    )
+}
+
+impl SerialDevice for CableEnd {
+    // The other side only receives the byte if it is waiting for an external
+    // clock. Otherwise nothing drives the data line and 0xFF is received.
+    fn exchange(&mut self, byte: u8) -> u8 {
+        let mut state = self.state.borrow_mut();
+        let other = 1 - self.end;
+
+        match state.waiting[other].take() {
+            Some(reply) => {
+                state.incoming[other] = Some(byte);
+                reply
+            }
+            None => 0xFF,
+        }
+    }
+
+    fn external_clock(&mut self, byte: u8) -> Option<u8> {
+        let mut state = self.state.borrow_mut();
+
+        match state.incoming[self.end].take() {
+            Some(received) => Some(received),
+            None => {
+                state.waiting[self.end] = Some(byte);
+                None
+            }
+        }
+    }
+}
+
+/// Runs two CPUs for the given number of M-cycles, always running the next
+/// instruction of the one that is behind. With their serial ports connected
+/// with `cable`, transfers happen at the same emulated time on both sides,
+/// so runs are deterministic.
+pub fn run_linked(first: &mut CPU, second: &mut CPU, m_cycles: u64) {
+    let mut first_cycles = 0;
+    let mut second_cycles = 0;
+
+    while first_cycles < m_cycles || second_cycles < m_cycles {
+        if first_cycles <= second_cycles {
+            first_cycles += u64::from(first.run_next_instruction());
+        } else {
+            second_cycles += u64::from(second.run_next_instruction());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interrupts::InterruptKind;
+    use crate::memory::Memory;
+    use crate::model::Model;
+
+    // Puts `sb` in SB, starts a transfer with the given SC and, when it
+    // completes, copies the received byte to 0xC000.
+    fn load_transfer_program(memory: &mut Memory, sb: u8, sc: u8) {
+        let program = [
+            0x3E, sb, // LD A, sb
+            0xE0, 0x01, // LDH (SB), A
+            0x3E, sc, // LD A, sc
+            0xE0, 0x02, // LDH (SC), A
+            0xF0, 0x02, // LDH A, (SC)
+            0xCB, 0x7F, // BIT 7, A
+            0x20, 0xFA, // JR NZ, -6
+            0xF0, 0x01, // LDH A, (SB)
+            0xEA, 0x00, 0xC0, // LD (0xC000), A
+            0x18, 0xFE, // JR -2
+        ];
+
+        for (i, &byte) in program.iter().enumerate() {
+            memory.write_byte(0x100 + i as u16, byte);
+        }
+    }
+
+    #[test]
+    fn exchange_bytes_in_lockstep() {
+        let (master_end, slave_end) = cable();
+        let mut master_memory = Memory::new();
+        let mut slave_memory = Memory::new();
+        master_memory.connect_serial(Box::new(master_end));
+        slave_memory.connect_serial(Box::new(slave_end));
+        load_transfer_program(&mut master_memory, 0x42, 0x81);
+        load_transfer_program(&mut slave_memory, 0x99, 0x80);
+
+        {
+            let mut slave = CPU::new_at_0x100(&mut slave_memory, Model::Dmg);
+            let mut master = CPU::new_at_0x100(&mut master_memory, Model::Dmg);
+
+            // The slave starts waiting before the master sends.
+            run_linked(&mut slave, &mut master, 2000);
+        }
+
+        assert_eq!(master_memory.read_byte(0xC000), 0x99);
+        assert_eq!(slave_memory.read_byte(0xC000), 0x42);
+        assert!(master_memory.interrupts.is_pending(&InterruptKind::SERIAL));
+        assert!(slave_memory.interrupts.is_pending(&InterruptKind::SERIAL));
+    }
+
+    #[test]
+    fn transfers_finish_at_the_same_time() {
+        let (master_end, slave_end) = cable();
+        let mut master_memory = Memory::new();
+        let mut slave_memory = Memory::new();
+        master_memory.connect_serial(Box::new(master_end));
+        slave_memory.connect_serial(Box::new(slave_end));
+        load_transfer_program(&mut master_memory, 0x42, 0x81);
+        load_transfer_program(&mut slave_memory, 0x99, 0x80);
+
+        let mut slave = CPU::new_at_0x100(&mut slave_memory, Model::Dmg);
+        let mut master = CPU::new_at_0x100(&mut master_memory, Model::Dmg);
+
+        let mut cycles = [0, 0];
+        let mut started = [false, false];
+        let mut done = [None, None];
+
+        while done.contains(&None) {
+            let side = if cycles[0] <= cycles[1] { 0 } else { 1 };
+            let cpu = if side == 0 { &mut slave } else { &mut master };
+
+            cycles[side] += u32::from(cpu.run_next_instruction());
+
+            let transferring = cpu.memory().read_byte(0xFF02) & 0x80 != 0;
+
+            if transferring {
+                started[side] = true;
+            } else if started[side] && done[side].is_none() {
+                done[side] = Some(cycles[side]);
+            }
+        }
+
+        let (slave_done, master_done) = (done[0].unwrap(), done[1].unwrap());
+        let difference = (i64::from(master_done) - i64::from(slave_done)).abs();
+        assert!(difference <= 4);
+    }
+
+    #[test]
+    fn master_without_slave_receives_0xff() {
+        let (master_end, _slave_end) = cable();
+        let mut memory = Memory::new();
+        memory.connect_serial(Box::new(master_end));
+        load_transfer_program(&mut memory, 0x42, 0x81);
+
+        {
+            let mut master = CPU::new_at_0x100(&mut memory, Model::Dmg);
+            for _ in 0..2000 {
+                master.run_next_instruction();
+            }
+        }
+
+        assert_eq!(memory.read_byte(0xC000), 0xFF);
+    }
+}
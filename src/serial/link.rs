@@ -0,0 +1,226 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+#[cfg(unix)]
+use std::os::unix::net::{UnixListener, UnixStream};
+#[cfg(unix)]
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use crate::serial::SerialDevice;
+
+// Each message is a tag, the id of the transfer and a byte. A reply has the
+// id of the transfer it answers, so late replies are not mistaken for the
+// answer to a newer transfer.
+const TRANSFER: u8 = 0x01;
+const REPLY: u8 = 0x02;
+
+// How long the master waits for the other side to answer before giving up
+// and receiving 0xFF, as if nothing was connected. The master is frozen
+// meanwhile, so this is kept short for when the other side is not waiting
+// for an external clock.
+const REPLY_TIMEOUT: Duration = Duration::from_millis(250);
+
+/// Link cable to another emulator over a socket.
+///
+/// The side that uses its internal clock sends its byte and stalls until the
+/// other side, which waits for an external clock, answers with the byte in
+/// its SB. This hides the latency of the connection at the cost of slowing
+/// down the master, which freezes for up to `REPLY_TIMEOUT` on each byte
+/// that the other side does not answer.
+pub struct SocketLink {
+    writer: Box<dyn Write + Send>,
+
+    // Messages from the other side, read by a separate thread so that the
+    // slave can check for them without blocking.
+    messages: Receiver<(u8, u8, u8)>,
+
+    next_transfer_id: u8,
+}
+
+impl SocketLink {
+    pub fn new<R, W>(reader: R, writer: W) -> SocketLink
+    where
+        R: Read + Send + 'static,
+        W: Write + Send + 'static,
+    {
+        let (sender, messages) = mpsc::channel();
+
+        thread::spawn(move || {
+            let mut reader = reader;
+            let mut message = [0; 3];
+
+            while reader.read_exact(&mut message).is_ok() {
+                if sender.send((message[0], message[1], message[2])).is_err() {
+                    break;
+                }
+            }
+        });
+
+        SocketLink {
+            writer: Box::new(writer),
+            messages,
+            next_transfer_id: 0,
+        }
+    }
+
+    /// Waits for the other emulator to connect to the given TCP address.
+    pub fn listen_tcp<A: ToSocketAddrs>(address: A) -> io::Result<SocketLink> {
+        let (stream, _) = TcpListener::bind(address)?.accept()?;
+        SocketLink::from_tcp(stream)
+    }
+
+    pub fn connect_tcp<A: ToSocketAddrs>(address: A) -> io::Result<SocketLink> {
+        SocketLink::from_tcp(TcpStream::connect(address)?)
+    }
+
+    /// Waits for the other emulator to connect to the Unix socket at the
+    /// given path.
+    #[cfg(unix)]
+    pub fn listen_unix<P: AsRef<Path>>(path: P) -> io::Result<SocketLink> {
+        let (stream, _) = UnixListener::bind(path)?.accept()?;
+        Ok(SocketLink::new(stream.try_clone()?, stream))
+    }
+
+    #[cfg(unix)]
+    pub fn connect_unix<P: AsRef<Path>>(path: P) -> io::Result<SocketLink> {
+        let stream = UnixStream::connect(path)?;
+        Ok(SocketLink::new(stream.try_clone()?, stream))
+    }
+
+    fn from_tcp(stream: TcpStream) -> io::Result<SocketLink> {
+        // Messages are tiny and latency matters more than throughput.
+        stream.set_nodelay(true)?;
+        Ok(SocketLink::new(stream.try_clone()?, stream))
+    }
+
+    fn send(&mut self, tag: u8, id: u8, byte: u8) -> bool {
+        self.writer.write_all(&[tag, id, byte]).is_ok() && self.writer.flush().is_ok()
+    }
+}
+
+impl SerialDevice for SocketLink {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        let id = self.next_transfer_id;
+        self.next_transfer_id = id.wrapping_add(1);
+
+        if !self.send(TRANSFER, id, byte) {
+            return 0xFF;
+        }
+
+        loop {
+            match self.messages.recv_timeout(REPLY_TIMEOUT) {
+                Ok((REPLY, reply_id, reply)) if reply_id == id => return reply,
+                // Both sides started a transfer with their internal clock.
+                // Nobody is listening on this side.
+                Ok((TRANSFER, transfer_id, _)) => {
+                    self.send(REPLY, transfer_id, 0xFF);
+                }
+                // Late replies to transfers that already timed out.
+                Ok(_) => (),
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => {
+                    return 0xFF
+                }
+            }
+        }
+    }
+
+    fn external_clock(&mut self, byte: u8) -> Option<u8> {
+        loop {
+            match self.messages.try_recv() {
+                Ok((TRANSFER, id, received)) => {
+                    self.send(REPLY, id, byte);
+                    return Some(received);
+                }
+                // A late reply to a transfer that already timed out.
+                Ok(_) => (),
+                Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tcp_pair() -> (SocketLink, SocketLink) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let client = thread::spawn(move || SocketLink::connect_tcp(address).unwrap());
+        let (stream, _) = listener.accept().unwrap();
+
+        (
+            SocketLink::from_tcp(stream).unwrap(),
+            client.join().unwrap(),
+        )
+    }
+
+    // The slave polls from another thread, like another emulator would.
+    fn exchange_with_slave(mut master: SocketLink, mut slave: SocketLink) {
+        let slave_thread = thread::spawn(move || loop {
+            if let Some(byte) = slave.external_clock(0x99) {
+                return byte;
+            }
+
+            thread::sleep(Duration::from_millis(1));
+        });
+
+        assert_eq!(master.exchange(0x42), 0x99);
+        assert_eq!(slave_thread.join().unwrap(), 0x42);
+    }
+
+    #[test]
+    fn exchange_over_tcp() {
+        let (master, slave) = tcp_pair();
+
+        exchange_with_slave(master, slave);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exchange_over_unix_socket() {
+        let (a, b) = UnixStream::pair().unwrap();
+        let master = SocketLink::new(a.try_clone().unwrap(), a);
+        let slave = SocketLink::new(b.try_clone().unwrap(), b);
+
+        exchange_with_slave(master, slave);
+    }
+
+    #[test]
+    fn late_reply_is_dropped() {
+        let (mut master, mut slave) = tcp_pair();
+
+        // The slave answers after the master gave up.
+        assert_eq!(master.exchange(0x11), 0xFF);
+        assert_eq!(slave.external_clock(0x22), Some(0x11));
+
+        let slave_thread = thread::spawn(move || loop {
+            if let Some(byte) = slave.external_clock(0x33) {
+                return byte;
+            }
+
+            thread::sleep(Duration::from_millis(1));
+        });
+
+        assert_eq!(master.exchange(0x44), 0x33);
+        assert_eq!(slave_thread.join().unwrap(), 0x44);
+    }
+
+    #[test]
+    fn slave_without_master_receives_nothing() {
+        let (_master, mut slave) = tcp_pair();
+
+        assert_eq!(slave.external_clock(0x99), None);
+    }
+
+    #[test]
+    fn disconnected_peer_sends_0xff() {
+        let (mut master, slave) = tcp_pair();
+        drop(slave);
+
+        assert_eq!(master.exchange(0x42), 0xFF);
+    }
+}
@@ -0,0 +1,300 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+pub use crate::serial::cable::{cable, run_linked, CableEnd};
+pub use crate::serial::link::SocketLink;
+
+mod cable;
+mod link;
+
+pub const SB_ADDR: u16 = 0xFF01;
+pub const SC_ADDR: u16 = 0xFF02;
+
+// SC bits
+const TRANSFER_START: u8 = 0b1000_0000;
+const INTERNAL_CLOCK: u8 = 0b0000_0001;
+
+// The internal clock runs at 8192 Hz, so a bit is shifted every 128 M-cycles.
+const M_CYCLES_PER_BIT: u16 = 128;
+
+/// What is connected to the serial port.
+pub trait SerialDevice {
+    /// Called when the Game Boy starts a transfer with its internal clock,
+    /// sending `byte`. Returns the byte that the device sends back.
+    fn exchange(&mut self, byte: u8) -> u8;
+
+    /// Called while the Game Boy waits for a transfer clocked by the device,
+    /// with `byte` in SB. Returns the byte received when the device has
+    /// made the transfer.
+    fn external_clock(&mut self, _byte: u8) -> Option<u8> {
+        None
+    }
+}
+
+/// Nothing connected. The data line is pulled up, so 0xFF is received.
+pub struct Disconnected;
+
+impl SerialDevice for Disconnected {
+    fn exchange(&mut self, _byte: u8) -> u8 {
+        0xFF
+    }
+}
+
+/// Prints the bytes sent as characters. Blargg's test ROMs send their output
+/// this way, which allows us to see the results without a screen.
+pub struct StdoutSink;
+
+impl SerialDevice for StdoutSink {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        print!("{}", byte as char);
+        io::stdout().flush().ok();
+        0xFF
+    }
+}
+
+/// Keeps the bytes sent, so they can be inspected, for example from tests.
+#[derive(Default)]
+pub struct BufferSink {
+    output: Rc<RefCell<Vec<u8>>>,
+}
+
+impl BufferSink {
+    pub fn new() -> BufferSink {
+        BufferSink::default()
+    }
+
+    /// A handle to the bytes sent, that can be kept after the sink is
+    /// connected.
+    pub fn output(&self) -> Rc<RefCell<Vec<u8>>> {
+        Rc::clone(&self.output)
+    }
+}
+
+impl SerialDevice for BufferSink {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        self.output.borrow_mut().push(byte);
+        0xFF
+    }
+}
+
+pub struct Serial {
+    sb: u8,
+    sc: u8,
+
+    device: Box<dyn SerialDevice>,
+
+    // The byte from the device, shifted into SB one bit at a time, most
+    // significant bit first.
+    incoming: u8,
+    bits_left: u8,
+    timer: u16,
+}
+
+impl Default for Serial {
+    fn default() -> Serial {
+        Serial::new()
+    }
+}
+
+impl Serial {
+    pub fn new() -> Serial {
+        Serial {
+            sb: 0,
+            sc: 0,
+            device: Box::new(Disconnected),
+            incoming: 0,
+            bits_left: 0,
+            timer: 0,
+        }
+    }
+
+    pub fn connect(&mut self, device: Box<dyn SerialDevice>) {
+        self.device = device;
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            SB_ADDR => self.sb,
+            SC_ADDR => 0x7E | self.sc,
+            _ => panic!("Invalid serial address: {:X}", address),
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            SB_ADDR => self.sb = value,
+            SC_ADDR => {
+                self.sc = value & (TRANSFER_START | INTERNAL_CLOCK);
+
+                if self.sc == TRANSFER_START | INTERNAL_CLOCK {
+                    self.incoming = self.device.exchange(self.sb);
+                    self.bits_left = 8;
+                    self.timer = M_CYCLES_PER_BIT;
+                } else {
+                    self.bits_left = 0;
+                }
+            }
+            _ => panic!("Invalid serial address: {:X}", address),
+        }
+    }
+
+    /// Returns whether the serial interrupt must be requested, which happens
+    /// when a transfer completes.
+    pub fn tick(&mut self, m_cycles: u8) -> bool {
+        if self.sc & TRANSFER_START == 0 {
+            return false;
+        }
+
+        // With the external clock, the byte is shifted in once the device
+        // sends it. The bits take as long as with the internal clock, so that
+        // both sides finish the transfer at the same time.
+        if self.sc & INTERNAL_CLOCK == 0 && self.bits_left == 0 {
+            match self.device.external_clock(self.sb) {
+                Some(byte) => {
+                    self.incoming = byte;
+                    self.bits_left = 8;
+                    self.timer = M_CYCLES_PER_BIT;
+                }
+                None => return false,
+            }
+        }
+
+        for _ in 0..m_cycles {
+            self.timer -= 1;
+
+            if self.timer > 0 {
+                continue;
+            }
+
+            self.timer = M_CYCLES_PER_BIT;
+            self.sb = (self.sb << 1) | (self.incoming >> 7);
+            self.incoming <<= 1;
+            self.bits_left -= 1;
+
+            if self.bits_left == 0 {
+                self.sc &= !TRANSFER_START;
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Echo;
+
+    impl SerialDevice for Echo {
+        fn exchange(&mut self, byte: u8) -> u8 {
+            byte.wrapping_add(1)
+        }
+    }
+
+    struct ExternalMaster(Option<u8>);
+
+    impl SerialDevice for ExternalMaster {
+        fn exchange(&mut self, _byte: u8) -> u8 {
+            0xFF
+        }
+
+        fn external_clock(&mut self, _byte: u8) -> Option<u8> {
+            self.0.take()
+        }
+    }
+
+    fn run(serial: &mut Serial, m_cycles: u32) -> bool {
+        let mut interrupt = false;
+
+        for _ in 0..m_cycles {
+            interrupt |= serial.tick(1);
+        }
+
+        interrupt
+    }
+
+    #[test]
+    fn transfer_with_internal_clock() {
+        let mut serial = Serial::new();
+        serial.connect(Box::new(Echo));
+        serial.write(SB_ADDR, 0x41);
+
+        serial.write(SC_ADDR, 0x81);
+
+        assert!(!run(&mut serial, 8 * 128 - 1));
+        assert_eq!(serial.read(SC_ADDR), 0xFF);
+        assert!(run(&mut serial, 1));
+        assert_eq!(serial.read(SB_ADDR), 0x42);
+        assert_eq!(serial.read(SC_ADDR), 0x7F);
+    }
+
+    #[test]
+    fn bits_are_shifted_one_at_a_time() {
+        let mut serial = Serial::new();
+        serial.connect(Box::new(Echo));
+        serial.write(SB_ADDR, 0x0F);
+        serial.write(SC_ADDR, 0x81);
+
+        run(&mut serial, 4 * 128);
+
+        assert_eq!(serial.read(SB_ADDR), 0xF1);
+    }
+
+    #[test]
+    fn no_transfer_without_start_bit() {
+        let sink = BufferSink::new();
+        let output = sink.output();
+        let mut serial = Serial::new();
+        serial.connect(Box::new(sink));
+        serial.write(SB_ADDR, 0x41);
+
+        serial.write(SC_ADDR, 0x01);
+
+        assert!(!run(&mut serial, 2000));
+        assert!(output.borrow().is_empty());
+    }
+
+    #[test]
+    fn sink_receives_bytes() {
+        let sink = BufferSink::new();
+        let output = sink.output();
+        let mut serial = Serial::new();
+        serial.connect(Box::new(sink));
+
+        for &byte in b"ok" {
+            serial.write(SB_ADDR, byte);
+            serial.write(SC_ADDR, 0x81);
+            run(&mut serial, 8 * 128);
+        }
+
+        assert_eq!(*output.borrow(), b"ok".to_vec());
+    }
+
+    #[test]
+    fn disconnected_receives_0xff() {
+        let mut serial = Serial::new();
+        serial.write(SB_ADDR, 0x00);
+        serial.write(SC_ADDR, 0x81);
+
+        run(&mut serial, 8 * 128);
+
+        assert_eq!(serial.read(SB_ADDR), 0xFF);
+    }
+
+    #[test]
+    fn external_clock_waits_for_the_device() {
+        let mut serial = Serial::new();
+        serial.write(SC_ADDR, 0x80);
+
+        assert!(!run(&mut serial, 10_000));
+
+        serial.connect(Box::new(ExternalMaster(Some(0x42))));
+
+        assert!(!run(&mut serial, 8 * 128 - 1));
+        assert!(run(&mut serial, 1));
+        assert_eq!(serial.read(SB_ADDR), 0x42);
+    }
+}
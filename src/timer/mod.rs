@@ -0,0 +1,282 @@
+pub const DIV_ADDR: u16 = 0xFF04;
+pub const TIMA_ADDR: u16 = 0xFF05;
+pub const TMA_ADDR: u16 = 0xFF06;
+pub const TAC_ADDR: u16 = 0xFF07;
+
+const CLOCK_CYCLES_PER_M_CYCLE: u16 = 4;
+
+// Bits 0-1 of TAC select the frequency. Bit 2 starts and stops the timer.
+const TAC_ENABLE_BIT: u8 = 2;
+const TAC_UNUSED_BITS: u8 = 0b1111_1000;
+
+// After TIMA overflows, it holds 0 for one M-cycle. It is reloaded with TMA
+// and the interrupt is requested in the next one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Overflow {
+    None,
+    Pending,
+    Reloading,
+}
+
+#[derive(Clone)]
+pub struct Timer {
+    // Internal 16-bit counter increased in every clock cycle. DIV is its most
+    // significant byte.
+    counter: u16,
+    tima: u8,
+    tma: u8,
+    tac: u8,
+    overflow: Overflow,
+}
+
+impl Default for Timer {
+    fn default() -> Timer {
+        Timer::new()
+    }
+}
+
+impl Timer {
+    pub fn new() -> Timer {
+        Timer {
+            counter: 0,
+            tima: 0,
+            tma: 0,
+            tac: 0,
+            overflow: Overflow::None,
+        }
+    }
+
+    /// Sets the internal counter, to start with the phase that the boot ROM
+    /// leaves.
+    pub fn set_counter(&mut self, counter: u16) {
+        self.counter = counter;
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            DIV_ADDR => (self.counter >> 8) as u8,
+            TIMA_ADDR => self.tima,
+            TMA_ADDR => self.tma,
+            TAC_ADDR => TAC_UNUSED_BITS | self.tac,
+            _ => panic!("Invalid timer address: {:X}", address),
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            DIV_ADDR => {
+                // Resetting the counter can produce a falling edge in the bit
+                // that drives TIMA, so it might increase.
+                let before = self.timer_signal();
+                self.counter = 0;
+                self.increase_tima_on_falling_edge(before);
+            }
+            TIMA_ADDR => match self.overflow {
+                // Writing TIMA in the cycle after an overflow cancels the
+                // reload and the interrupt.
+                Overflow::Pending => {
+                    self.overflow = Overflow::None;
+                    self.tima = value;
+                }
+                // In the cycle of the reload, TMA wins.
+                Overflow::Reloading => (),
+                Overflow::None => self.tima = value,
+            },
+            TMA_ADDR => {
+                self.tma = value;
+
+                if self.overflow == Overflow::Reloading {
+                    self.tima = value;
+                }
+            }
+            TAC_ADDR => {
+                // Changing the frequency or disabling the timer can also
+                // produce a falling edge.
+                let before = self.timer_signal();
+                self.tac = value & !TAC_UNUSED_BITS;
+                self.increase_tima_on_falling_edge(before);
+            }
+            _ => panic!("Invalid timer address: {:X}", address),
+        }
+    }
+
+    /// Advances the timer the given number of M-cycles. Returns whether the
+    /// timer interrupt needs to be requested.
+    pub fn tick(&mut self, m_cycles: u8) -> bool {
+        let mut interrupt = false;
+
+        for _ in 0..m_cycles {
+            match self.overflow {
+                Overflow::Pending => {
+                    self.tima = self.tma;
+                    self.overflow = Overflow::Reloading;
+                    interrupt = true;
+                }
+                Overflow::Reloading => self.overflow = Overflow::None,
+                Overflow::None => (),
+            }
+
+            let before = self.timer_signal();
+            self.counter = self.counter.wrapping_add(CLOCK_CYCLES_PER_M_CYCLE);
+            self.increase_tima_on_falling_edge(before);
+        }
+
+        interrupt
+    }
+
+    // TIMA is increased when the bit of the counter selected by TAC, ANDed
+    // with the enable bit of TAC, goes from 1 to 0.
+    fn timer_signal(&self) -> bool {
+        let enabled = self.tac & (1 << TAC_ENABLE_BIT) != 0;
+        let counter_bit = match self.tac & 0b11 {
+            0b00 => 9, // 4096 Hz
+            0b01 => 3, // 262144 Hz
+            0b10 => 5, // 65536 Hz
+            _ => 7,    // 16384 Hz
+        };
+
+        enabled && (self.counter & (1 << counter_bit)) != 0
+    }
+
+    fn increase_tima_on_falling_edge(&mut self, signal_before: bool) {
+        if signal_before && !self.timer_signal() {
+            let (new_val, overflow) = self.tima.overflowing_add(1);
+            self.tima = new_val;
+
+            if overflow {
+                self.overflow = Overflow::Pending;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn div_increases_every_64_m_cycles() {
+        let mut timer = Timer::new();
+
+        timer.tick(63);
+        assert_eq!(timer.read(DIV_ADDR), 0);
+
+        timer.tick(1);
+        assert_eq!(timer.read(DIV_ADDR), 1);
+    }
+
+    #[test]
+    fn write_div_resets_it() {
+        let mut timer = Timer::new();
+        timer.tick(200);
+
+        timer.write(DIV_ADDR, 0xAB);
+
+        assert_eq!(timer.read(DIV_ADDR), 0);
+    }
+
+    #[test]
+    fn tima_does_not_increase_when_disabled() {
+        let mut timer = Timer::new();
+        timer.write(TAC_ADDR, 0b01);
+
+        timer.tick(100);
+
+        assert_eq!(timer.read(TIMA_ADDR), 0);
+    }
+
+    #[test]
+    fn tima_increases_at_selected_frequency() {
+        let mut timer = Timer::new();
+        timer.write(TAC_ADDR, 0b101); // Every 4 M-cycles
+
+        timer.tick(16);
+
+        assert_eq!(timer.read(TIMA_ADDR), 4);
+    }
+
+    #[test]
+    fn tima_overflow_reloads_tma_one_cycle_later() {
+        let mut timer = Timer::new();
+        timer.write(TAC_ADDR, 0b101);
+        timer.write(TMA_ADDR, 0x42);
+        timer.write(TIMA_ADDR, 0xFF);
+
+        assert!(!timer.tick(4));
+        assert_eq!(timer.read(TIMA_ADDR), 0);
+
+        assert!(timer.tick(1));
+        assert_eq!(timer.read(TIMA_ADDR), 0x42);
+    }
+
+    #[test]
+    fn write_tima_after_overflow_cancels_reload() {
+        let mut timer = Timer::new();
+        timer.write(TAC_ADDR, 0b101);
+        timer.write(TMA_ADDR, 0x42);
+        timer.write(TIMA_ADDR, 0xFF);
+        timer.tick(4);
+
+        timer.write(TIMA_ADDR, 0x10);
+
+        assert!(!timer.tick(1));
+        assert_eq!(timer.read(TIMA_ADDR), 0x10);
+    }
+
+    #[test]
+    fn write_tima_in_reload_cycle_is_ignored() {
+        let mut timer = Timer::new();
+        timer.write(TAC_ADDR, 0b101);
+        timer.write(TMA_ADDR, 0x42);
+        timer.write(TIMA_ADDR, 0xFF);
+        timer.tick(5);
+
+        timer.write(TIMA_ADDR, 0x10);
+
+        assert_eq!(timer.read(TIMA_ADDR), 0x42);
+    }
+
+    #[test]
+    fn write_tma_in_reload_cycle_is_loaded_in_tima() {
+        let mut timer = Timer::new();
+        timer.write(TAC_ADDR, 0b101);
+        timer.write(TMA_ADDR, 0x42);
+        timer.write(TIMA_ADDR, 0xFF);
+        timer.tick(5);
+
+        timer.write(TMA_ADDR, 0x24);
+
+        assert_eq!(timer.read(TIMA_ADDR), 0x24);
+    }
+
+    #[test]
+    fn write_div_on_falling_edge_increases_tima() {
+        let mut timer = Timer::new();
+        timer.write(TAC_ADDR, 0b101);
+        timer.tick(2); // Bit 3 of the counter is set now
+
+        timer.write(DIV_ADDR, 0);
+
+        assert_eq!(timer.read(TIMA_ADDR), 1);
+    }
+
+    #[test]
+    fn disable_timer_on_falling_edge_increases_tima() {
+        let mut timer = Timer::new();
+        timer.write(TAC_ADDR, 0b101);
+        timer.tick(2);
+
+        timer.write(TAC_ADDR, 0b001);
+
+        assert_eq!(timer.read(TIMA_ADDR), 1);
+    }
+
+    #[test]
+    fn tac_unused_bits_read_as_1() {
+        let mut timer = Timer::new();
+
+        timer.write(TAC_ADDR, 0b101);
+
+        assert_eq!(timer.read(TAC_ADDR), 0b1111_1101);
+    }
+}